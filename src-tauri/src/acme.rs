@@ -0,0 +1,894 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNumContext;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::stack::Stack;
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509NameBuilder, X509ReqBuilder, X509};
+use regex::Regex;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::commands;
+use crate::health::{self, AlertSeverity};
+use crate::instances::{self, NginxInstance};
+use crate::nginx_config::{self, Directive};
+
+// Defaults for RUSTINX_ACME_DIRECTORY, RUSTINX_ACME_DATA_DIR and RUSTINX_ACME_RENEW_DAYS
+const DEFAULT_DIRECTORY_URL: &str = "https://acme-v02.api.letsencrypt.org/directory";
+const DEFAULT_DATA_DIR: &str = "/var/lib/rustinx/acme";
+const DEFAULT_RENEW_DAYS: i64 = 30;
+
+const CHALLENGE_PATH: &str = "/.well-known/acme-challenge/";
+
+// The location snippet is written next to nginx.conf under this name
+const SNIPPET_FILE: &str = "rustinx-acme-challenge.conf";
+
+const RENEWAL_CHECK_INTERVAL: Duration = Duration::from_secs(12 * 3600);
+
+// Orders and authorizations are polled this often, this many times, before giving up
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: usize = 30;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static::lazy_static! {
+    static ref DOMAIN_NAME: Regex =
+        Regex::new(r"^([a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?\.)+[a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?$").unwrap();
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AcmeSettings {
+    /// Let's Encrypt by default; point it at Pebble or staging for testing
+    pub directory_url: String,
+    pub contact_email: Option<String>,
+    /// Account key and issued certificates live here
+    pub data_dir: PathBuf,
+    /// Served at `/.well-known/acme-challenge/` through the managed snippet
+    pub webroot: PathBuf,
+    /// Extra root certificate to trust for the directory, e.g. Pebble's test CA
+    pub ca_bundle: Option<String>,
+    pub renew_days: i64,
+}
+
+impl AcmeSettings {
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let data_dir = PathBuf::from(var("RUSTINX_ACME_DATA_DIR").unwrap_or_else(|| DEFAULT_DATA_DIR.to_string()));
+        AcmeSettings {
+            directory_url: var("RUSTINX_ACME_DIRECTORY").unwrap_or_else(|| DEFAULT_DIRECTORY_URL.to_string()),
+            contact_email: var("RUSTINX_ACME_EMAIL"),
+            webroot: var("RUSTINX_ACME_WEBROOT").map(PathBuf::from).unwrap_or_else(|| data_dir.join("webroot")),
+            data_dir,
+            ca_bundle: var("RUSTINX_ACME_CA_BUNDLE"),
+            renew_days: var("RUSTINX_ACME_RENEW_DAYS")
+                .and_then(|value| value.parse::<i64>().ok())
+                .filter(|days| *days > 0)
+                .unwrap_or(DEFAULT_RENEW_DAYS),
+        }
+    }
+
+    fn certificate_dir(&self, name: &str) -> PathBuf {
+        self.data_dir.join("certs").join(name)
+    }
+}
+
+/// A certificate Rustinx issued and keeps renewed
+#[derive(Clone, Debug, Serialize)]
+pub struct ManagedCertificate {
+    pub name: String,
+    /// The nginx instance that serves the challenge and is reloaded on renewal
+    pub instance: String,
+    pub domains: Vec<String>,
+    /// Point `ssl_certificate` here
+    pub certificate: String,
+    /// Point `ssl_certificate_key` here
+    pub key: String,
+    pub not_after: Option<String>,
+    pub days_remaining: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueCertificateRequest {
+    pub domains: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Order {
+    status: String,
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    token: String,
+    error: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    challenges: Vec<Challenge>,
+}
+
+struct AcmeResponse {
+    location: Option<String>,
+    body: String,
+}
+
+impl AcmeResponse {
+    fn json<T: for<'de> Deserialize<'de>>(&self) -> Result<T, String> {
+        serde_json::from_str(&self.body).map_err(|e| format!("Unexpected ACME response: {}", e))
+    }
+}
+
+fn base64url(data: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(data)
+}
+
+/// Writes a file only the owner can read, for private keys
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    file.write_all(contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn generate_p256_key() -> Result<EcKey<Private>, String> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).map_err(|e| e.to_string())?;
+    EcKey::generate(&group).map_err(|e| e.to_string())
+}
+
+/// Loads the account key, creating one on first use
+fn load_account_key(settings: &AcmeSettings) -> Result<EcKey<Private>, String> {
+    let path = settings.data_dir.join("account.key");
+    if path.exists() {
+        let pem = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        return EcKey::private_key_from_pem(&pem).map_err(|e| format!("Failed to parse {}: {}", path.display(), e));
+    }
+
+    fs::create_dir_all(&settings.data_dir)
+        .map_err(|e| format!("Failed to create {}: {}", settings.data_dir.display(), e))?;
+    let key = generate_p256_key()?;
+    let pem = key.private_key_to_pem().map_err(|e| e.to_string())?;
+    write_private(&path, &pem)?;
+    Ok(key)
+}
+
+/// Speaks just enough RFC 8555 for HTTP-01 issuance, signing every request with ES256
+struct AcmeClient {
+    http: Client,
+    directory: Directory,
+    key: EcKey<Private>,
+    /// The account JWK, with members in the order the thumbprint requires
+    jwk: String,
+    kid: Option<String>,
+    nonce: Option<String>,
+}
+
+impl AcmeClient {
+    fn connect(settings: &AcmeSettings) -> Result<Self, String> {
+        let mut builder = Client::builder().timeout(REQUEST_TIMEOUT).user_agent("rustinx");
+        if let Some(bundle) = &settings.ca_bundle {
+            let pem = fs::read(bundle).map_err(|e| format!("Failed to read {}: {}", bundle, e))?;
+            let certificate = reqwest::Certificate::from_pem(&pem).map_err(|e| format!("Failed to parse {}: {}", bundle, e))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        let http = builder.build().map_err(|e| e.to_string())?;
+
+        let directory: Directory = http
+            .get(&settings.directory_url)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .map_err(|e| format!("Failed to fetch the ACME directory {}: {}", settings.directory_url, e))?;
+
+        let key = load_account_key(settings)?;
+        let jwk = Self::jwk(&key)?;
+        let mut client = AcmeClient { http, directory, key, jwk, kid: None, nonce: None };
+
+        // newAccount returns the existing account when the key is already registered
+        let mut account = json!({ "termsOfServiceAgreed": true });
+        if let Some(email) = &settings.contact_email {
+            account["contact"] = json!([format!("mailto:{}", email)]);
+        }
+        let url = client.directory.new_account.clone();
+        let response = client.post(&url, Some(&account))?;
+        client.kid = Some(response.location.ok_or("ACME server did not return an account URL")?);
+        Ok(client)
+    }
+
+    fn jwk(key: &EcKey<Private>) -> Result<String, String> {
+        let mut context = BigNumContext::new().map_err(|e| e.to_string())?;
+        let mut x = openssl::bn::BigNum::new().map_err(|e| e.to_string())?;
+        let mut y = openssl::bn::BigNum::new().map_err(|e| e.to_string())?;
+        key.public_key()
+            .affine_coordinates(key.group(), &mut x, &mut y, &mut context)
+            .map_err(|e| e.to_string())?;
+        let x = x.to_vec_padded(32).map_err(|e| e.to_string())?;
+        let y = y.to_vec_padded(32).map_err(|e| e.to_string())?;
+        Ok(format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            base64url(&x),
+            base64url(&y)
+        ))
+    }
+
+    /// The token plus account thumbprint the CA expects to find at the challenge URL
+    fn key_authorization(&self, token: &str) -> Result<String, String> {
+        let digest = hash(MessageDigest::sha256(), self.jwk.as_bytes()).map_err(|e| e.to_string())?;
+        Ok(format!("{}.{}", token, base64url(&digest)))
+    }
+
+    fn sign(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let digest = hash(MessageDigest::sha256(), data).map_err(|e| e.to_string())?;
+        let signature = EcdsaSig::sign(&digest, &self.key).map_err(|e| e.to_string())?;
+        // JWS wants the raw r || s, not the DER encoding
+        let mut raw = signature.r().to_vec_padded(32).map_err(|e| e.to_string())?;
+        raw.extend(signature.s().to_vec_padded(32).map_err(|e| e.to_string())?);
+        Ok(raw)
+    }
+
+    fn fresh_nonce(&mut self) -> Result<String, String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        let response = self
+            .http
+            .head(&self.directory.new_nonce)
+            .send()
+            .map_err(|e| format!("Failed to get an ACME nonce: {}", e))?;
+        response
+            .headers()
+            .get("Replay-Nonce")
+            .and_then(|nonce| nonce.to_str().ok())
+            .map(|nonce| nonce.to_string())
+            .ok_or_else(|| "ACME server did not return a nonce".to_string())
+    }
+
+    /// Sends a signed request; `None` as the payload makes it a POST-as-GET
+    fn post(&mut self, url: &str, payload: Option<&Value>) -> Result<AcmeResponse, String> {
+        // A nonce can go stale between requests; the server says so with badNonce and we retry once
+        for attempt in 0..2 {
+            let mut protected = json!({ "alg": "ES256", "nonce": self.fresh_nonce()?, "url": url });
+            match &self.kid {
+                Some(kid) => protected["kid"] = json!(kid),
+                None => protected["jwk"] = serde_json::from_str(&self.jwk).map_err(|e| e.to_string())?,
+            }
+            let protected = base64url(protected.to_string().as_bytes());
+            let payload = payload.map(|payload| base64url(payload.to_string().as_bytes())).unwrap_or_default();
+            let signature = base64url(&self.sign(format!("{}.{}", protected, payload).as_bytes())?);
+
+            let response = self
+                .http
+                .post(url)
+                .header("Content-Type", "application/jose+json")
+                .body(json!({ "protected": protected, "payload": payload, "signature": signature }).to_string())
+                .send()
+                .map_err(|e| format!("ACME request to {} failed: {}", url, e))?;
+
+            self.nonce = response
+                .headers()
+                .get("Replay-Nonce")
+                .and_then(|nonce| nonce.to_str().ok())
+                .map(|nonce| nonce.to_string());
+            let status = response.status();
+            let location = response
+                .headers()
+                .get("Location")
+                .and_then(|location| location.to_str().ok())
+                .map(|location| location.to_string());
+            let body = response.text().map_err(|e| e.to_string())?;
+
+            if status.is_success() {
+                return Ok(AcmeResponse { location, body });
+            }
+            let problem: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+            if attempt == 0 && problem["type"] == "urn:ietf:params:acme:error:badNonce" {
+                continue;
+            }
+            return Err(format!(
+                "ACME server rejected {} ({}): {}",
+                url,
+                status,
+                problem["detail"].as_str().unwrap_or(&body)
+            ));
+        }
+        unreachable!("the second attempt always returns")
+    }
+
+    /// POST-as-GETs `url` until `done` says the resource settled
+    fn poll<T, F>(&mut self, url: &str, done: F) -> Result<T, String>
+    where
+        T: for<'de> Deserialize<'de>,
+        F: Fn(&T) -> Result<bool, String>,
+    {
+        for _ in 0..POLL_ATTEMPTS {
+            let resource: T = self.post(url, None)?.json()?;
+            if done(&resource)? {
+                return Ok(resource);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        Err(format!("Timed out waiting for {}", url))
+    }
+}
+
+/// Challenge files written for an order, removed again however issuance ends
+struct ChallengeFiles(Vec<PathBuf>);
+
+impl Drop for ChallengeFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn challenge_snippet(webroot: &Path) -> String {
+    format!(
+        "# Managed by Rustinx: answers ACME HTTP-01 challenges\n\
+         location ^~ {} {{\n    root {};\n    default_type text/plain;\n    try_files $uri =404;\n}}\n",
+        CHALLENGE_PATH,
+        webroot.display()
+    )
+}
+
+fn listens_on_port_80(server: &Directive) -> bool {
+    let listens: Vec<&Directive> = server.children().iter().filter(|d| d.name == "listen").collect();
+    // A server without `listen` gets *:80
+    listens.is_empty()
+        || listens.iter().any(|listen| {
+            listen
+                .arg(0)
+                .is_some_and(|address| address == "80" || address.ends_with(":80"))
+        })
+}
+
+fn serves_domain(server: &Directive, domain: &str) -> bool {
+    server
+        .children()
+        .iter()
+        .filter(|d| d.name == "server_name")
+        .flat_map(|d| d.args.iter())
+        .map(|name| name.to_ascii_lowercase())
+        .any(|name| {
+            if let Some(suffix) = name.strip_prefix("*.") {
+                domain.ends_with(&format!(".{}", suffix))
+            } else if let Some(suffix) = name.strip_prefix('.') {
+                domain == suffix || domain.ends_with(&format!(".{}", suffix))
+            } else {
+                name == domain
+            }
+        })
+}
+
+fn has_challenge_location(server: &Directive) -> bool {
+    server
+        .children()
+        .iter()
+        .any(|d| d.name == "location" && d.args.iter().any(|arg| arg.starts_with(CHALLENGE_PATH.trim_end_matches('/'))))
+}
+
+/// Writes the challenge snippet and includes it in every port 80 server for `domains` that doesn't
+/// already answer challenges. Reloads nginx when anything changed, restoring the files if the new
+/// config doesn't validate.
+pub fn ensure_challenge_location(
+    instance: &NginxInstance,
+    settings: &AcmeSettings,
+    domains: &[String],
+) -> Result<Vec<String>, String> {
+    let root = nginx_config::find_main_config(instance).ok_or("Could not find nginx.conf")?;
+    let conf_dir = Path::new(&root).parent().unwrap_or(Path::new("/etc/nginx")).to_path_buf();
+
+    let challenge_dir = settings.webroot.join(CHALLENGE_PATH.trim_matches('/'));
+    fs::create_dir_all(&challenge_dir).map_err(|e| format!("Failed to create {}: {}", challenge_dir.display(), e))?;
+    let _guard = commands::lock_config_changes();
+    let snippet_path = conf_dir.join(SNIPPET_FILE);
+    let snippet = challenge_snippet(&settings.webroot);
+    if fs::read_to_string(&snippet_path).ok().as_deref() != Some(snippet.as_str()) {
+        fs::write(&snippet_path, &snippet).map_err(|e| format!("Failed to write {}: {}", snippet_path.display(), e))?;
+    }
+
+    let originals = insert_challenge_includes(&root, &snippet_path, domains)?;
+    if originals.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if let Err(e) = commands::reload_nginx(instance) {
+        restore_files(&originals);
+        return Err(format!("Reverted the challenge include: {}", e));
+    }
+
+    Ok(originals.into_iter().map(|(file, _)| file).collect())
+}
+
+fn restore_files(originals: &[(String, String)]) {
+    for (file, content) in originals {
+        let _ = fs::write(file, content);
+    }
+}
+
+/// Adds `include <snippet>;` at the top of every port 80 server for `domains` that doesn't already
+/// answer challenges. Returns the previous content of each file it changed; when a write fails the
+/// files changed so far are put back.
+fn insert_challenge_includes(root: &str, snippet_path: &Path, domains: &[String]) -> Result<Vec<(String, String)>, String> {
+    let parsed = nginx_config::parse_config(root);
+    let tree = parsed.expanded();
+    let mut servers: Vec<&Directive> = Vec::new();
+    nginx_config::walk(&tree, &mut |directive, parents| {
+        if directive.name == "server" && parents.last().is_some_and(|parent| parent.name == "http") && listens_on_port_80(directive) {
+            servers.push(directive);
+        }
+    });
+
+    // (file, line to insert before, indentation) for each server that needs the include
+    let mut insertions: Vec<(String, usize, String)> = Vec::new();
+    for domain in domains {
+        let matching: Vec<&&Directive> = servers.iter().filter(|server| serves_domain(server, domain)).collect();
+        if matching.is_empty() {
+            return Err(format!("No server block listens on port 80 with server_name {}", domain));
+        }
+        for server in matching {
+            if has_challenge_location(server) {
+                continue;
+            }
+            let first = server
+                .children()
+                .first()
+                .filter(|child| child.file == server.file && child.line > server.line)
+                .ok_or_else(|| {
+                    format!(
+                        "Can't place the challenge include in the server at {}; add `include {};` to it",
+                        server.location(),
+                        snippet_path.display()
+                    )
+                })?;
+            if !insertions.iter().any(|(file, line, _)| *file == first.file && *line == first.line) {
+                let content = fs::read_to_string(&first.file).map_err(|e| format!("Failed to read {}: {}", first.file, e))?;
+                let indent: String = content
+                    .lines()
+                    .nth(first.line - 1)
+                    .map(|line| line.chars().take_while(|c| c.is_whitespace()).collect())
+                    .unwrap_or_default();
+                insertions.push((first.file.clone(), first.line, indent));
+            }
+        }
+    }
+
+    // Insert bottom-up so earlier line numbers stay valid
+    insertions.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    let mut originals: Vec<(String, String)> = Vec::new();
+    for (file, line, indent) in &insertions {
+        let result = fs::read_to_string(file).and_then(|content| {
+            if !originals.iter().any(|(path, _)| path == file) {
+                originals.push((file.clone(), content.clone()));
+            }
+            let mut lines: Vec<&str> = content.split('\n').collect();
+            let include = format!("{}include {};", indent, snippet_path.display());
+            lines.insert(line - 1, &include);
+            fs::write(file, lines.join("\n"))
+        });
+        if let Err(e) = result {
+            restore_files(&originals);
+            return Err(format!("Failed to update {}: {}", file, e));
+        }
+    }
+
+    Ok(originals)
+}
+
+
+fn validate_domains(domains: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for domain in domains {
+        let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+        if domain.starts_with("*.") {
+            return Err(format!("{}: wildcard certificates need DNS-01, which isn't supported", domain));
+        }
+        if domain.len() > 253 || !DOMAIN_NAME.is_match(&domain) {
+            return Err(format!("{} is not a valid domain name", domain));
+        }
+        if !normalized.contains(&domain) {
+            normalized.push(domain);
+        }
+    }
+    if normalized.is_empty() {
+        return Err("At least one domain is required".to_string());
+    }
+    Ok(normalized)
+}
+
+fn build_csr(key: &PKey<Private>, domains: &[String]) -> Result<Vec<u8>, String> {
+    let mut name = X509NameBuilder::new().map_err(|e| e.to_string())?;
+    name.append_entry_by_nid(Nid::COMMONNAME, &domains[0]).map_err(|e| e.to_string())?;
+
+    let mut san = SubjectAlternativeName::new();
+    for domain in domains {
+        san.dns(domain);
+    }
+
+    let mut builder = X509ReqBuilder::new().map_err(|e| e.to_string())?;
+    builder.set_subject_name(&name.build()).map_err(|e| e.to_string())?;
+    builder.set_pubkey(key).map_err(|e| e.to_string())?;
+    let mut extensions = Stack::new().map_err(|e| e.to_string())?;
+    extensions
+        .push(san.build(&builder.x509v3_context(None)).map_err(|e| e.to_string())?)
+        .map_err(|e| e.to_string())?;
+    builder.add_extensions(&extensions).map_err(|e| e.to_string())?;
+    builder.sign(key, MessageDigest::sha256()).map_err(|e| e.to_string())?;
+    builder.build().to_der().map_err(|e| e.to_string())
+}
+
+fn expiry(certificate_path: &Path) -> Option<(String, i64)> {
+    let pem = fs::read(certificate_path).ok()?;
+    let certificate = X509::from_pem(&pem).ok()?;
+    let now = Asn1Time::days_from_now(0).ok()?;
+    let days = now.diff(certificate.not_after()).ok()?.days as i64;
+    Some((certificate.not_after().to_string(), days))
+}
+
+fn managed_certificate(settings: &AcmeSettings, name: &str, domains: Vec<String>) -> ManagedCertificate {
+    let dir = settings.certificate_dir(name);
+    let certificate = dir.join("fullchain.pem");
+    let expiry = expiry(&certificate);
+    // Certificates issued before instances existed belong to the default one
+    let instance = fs::read_to_string(dir.join("instance"))
+        .map(|id| id.trim().to_string())
+        .unwrap_or_else(|_| instances::DEFAULT_INSTANCE_ID.to_string());
+    ManagedCertificate {
+        name: name.to_string(),
+        instance,
+        domains,
+        certificate: certificate.to_string_lossy().to_string(),
+        key: dir.join("privkey.pem").to_string_lossy().to_string(),
+        not_after: expiry.as_ref().map(|(not_after, _)| not_after.clone()),
+        days_remaining: expiry.map(|(_, days)| days),
+    }
+}
+
+/// Runs a full HTTP-01 order for `domains` through the instance, stores the certificate and reloads it
+pub fn issue_certificate(
+    instance: &NginxInstance,
+    settings: &AcmeSettings,
+    domains: &[String],
+) -> Result<ManagedCertificate, String> {
+    let domains = validate_domains(domains)?;
+    ensure_challenge_location(instance, settings, &domains)?;
+
+    let mut client = AcmeClient::connect(settings)?;
+    let identifiers: Vec<Value> = domains.iter().map(|domain| json!({ "type": "dns", "value": domain })).collect();
+    let new_order = client.directory.new_order.clone();
+    let response = client.post(&new_order, Some(&json!({ "identifiers": identifiers })))?;
+    let order_url = response.location.clone().ok_or("ACME server did not return an order URL")?;
+    let order: Order = response.json()?;
+
+    let challenge_dir = settings.webroot.join(CHALLENGE_PATH.trim_matches('/'));
+    let mut challenge_files = ChallengeFiles(Vec::new());
+    for authorization_url in &order.authorizations {
+        let authorization: Authorization = client.post(authorization_url, None)?.json()?;
+        if authorization.status == "valid" {
+            continue;
+        }
+        let challenge = authorization
+            .challenges
+            .iter()
+            .find(|challenge| challenge.kind == "http-01")
+            .ok_or_else(|| format!("No HTTP-01 challenge offered for {}", authorization.identifier.value))?;
+        // The token ends up in a path; make sure it's just base64url
+        if challenge.token.is_empty() || !challenge.token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Refusing unexpected challenge token {:?}", challenge.token));
+        }
+
+        let path = challenge_dir.join(&challenge.token);
+        fs::write(&path, client.key_authorization(&challenge.token)?)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        challenge_files.0.push(path);
+
+        client.post(&challenge.url, Some(&json!({})))?;
+        client.poll(authorization_url, |authorization: &Authorization| match authorization.status.as_str() {
+            "valid" => Ok(true),
+            "pending" | "processing" => Ok(false),
+            status => {
+                let detail = authorization
+                    .challenges
+                    .iter()
+                    .find_map(|challenge| challenge.error.as_ref())
+                    .and_then(|error| error["detail"].as_str())
+                    .unwrap_or("no detail");
+                Err(format!("Authorization for {} is {}: {}", authorization.identifier.value, status, detail))
+            }
+        })?;
+    }
+
+    let certificate_key = PKey::from_ec_key(generate_p256_key()?).map_err(|e| e.to_string())?;
+    let csr = build_csr(&certificate_key, &domains)?;
+    client.post(&order.finalize, Some(&json!({ "csr": base64url(&csr) })))?;
+    let order: Order = client.poll(&order_url, |order: &Order| match order.status.as_str() {
+        "valid" => Ok(true),
+        "pending" | "ready" | "processing" => Ok(false),
+        status => Err(format!("Order is {}", status)),
+    })?;
+    drop(challenge_files);
+
+    let certificate_url = order.certificate.ok_or("ACME server did not return a certificate URL")?;
+    let chain = client.post(&certificate_url, None)?.body;
+    X509::stack_from_pem(chain.as_bytes()).map_err(|e| format!("ACME server returned an invalid certificate: {}", e))?;
+
+    let name = domains[0].clone();
+    let dir = settings.certificate_dir(&name);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let key_pem = certificate_key.private_key_to_pem_pkcs8().map_err(|e| e.to_string())?;
+    write_private(&dir.join("privkey.pem"), &key_pem)?;
+    fs::write(dir.join("fullchain.pem"), &chain).map_err(|e| format!("Failed to write the certificate: {}", e))?;
+    fs::write(dir.join("domains.json"), json!(domains).to_string()).map_err(|e| format!("Failed to write domains.json: {}", e))?;
+    fs::write(dir.join("instance"), &instance.id).map_err(|e| format!("Failed to write the instance: {}", e))?;
+
+    commands::reload_nginx(instance)?;
+    Ok(managed_certificate(settings, &name, domains))
+}
+
+/// Every certificate stored under the data directory
+pub fn list_certificates(settings: &AcmeSettings) -> Vec<ManagedCertificate> {
+    let entries = match fs::read_dir(settings.data_dir.join("certs")) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut certificates: Vec<ManagedCertificate> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let domains: Vec<String> = fs::read_to_string(entry.path().join("domains.json"))
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())?;
+            Some(managed_certificate(settings, &name, domains))
+        })
+        .collect();
+    certificates.sort_by(|a, b| a.name.cmp(&b.name));
+    certificates
+}
+
+/// Whether the certificate is within `renew_days` of expiry. One whose expiry can't be read is
+/// renewed too, since it is missing or damaged.
+fn renewal_due(certificate: &ManagedCertificate, renew_days: i64) -> bool {
+    match certificate.days_remaining {
+        Some(days) => days <= renew_days,
+        None => true,
+    }
+}
+
+/// Renews managed certificates that are within `renew_days` of expiry
+pub fn renew_due_certificates(settings: &AcmeSettings) {
+    for certificate in list_certificates(settings) {
+        let key = format!("acme_renewal:{}", certificate.name);
+        if !renewal_due(&certificate, settings.renew_days) {
+            continue;
+        }
+        let result = instances::get_instance(Some(&certificate.instance))
+            .and_then(|instance| issue_certificate(&instance, settings, &certificate.domains));
+        match result {
+            Ok(_) => health::clear_instance_alert(&certificate.instance, &key),
+            Err(e) => health::raise_instance_alert(
+                &certificate.instance,
+                &key,
+                AlertSeverity::Warning,
+                &format!("Failed to renew the certificate for {}: {}", certificate.domains.join(", "), e),
+            ),
+        }
+    }
+}
+
+pub fn start_renewal_monitor() {
+    thread::spawn(|| loop {
+        renew_due_certificates(&AcmeSettings::from_env());
+        thread::sleep(RENEWAL_CHECK_INTERVAL);
+    });
+}
+
+#[tauri::command]
+pub(crate) fn list_acme_certificates() -> Vec<ManagedCertificate> {
+    list_certificates(&AcmeSettings::from_env())
+}
+
+#[tauri::command]
+pub(crate) fn issue_acme_certificate(instance: Option<String>, domains: Vec<String>) -> Result<ManagedCertificate, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+    issue_certificate(&instance, &AcmeSettings::from_env(), &domains)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::x509::X509Builder;
+
+    /// A scratch directory under the system temp dir, removed again when the test ends
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rustinx-acme-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn settings(data_dir: &Path) -> AcmeSettings {
+        AcmeSettings {
+            directory_url: DEFAULT_DIRECTORY_URL.to_string(),
+            contact_email: None,
+            data_dir: data_dir.to_path_buf(),
+            webroot: data_dir.join("webroot"),
+            ca_bundle: None,
+            renew_days: DEFAULT_RENEW_DAYS,
+        }
+    }
+
+    /// Stores a self-signed certificate for `name` that expires in `days`
+    fn store_certificate(settings: &AcmeSettings, name: &str, days: u32) {
+        let key = PKey::from_ec_key(generate_p256_key().unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_nid(Nid::COMMONNAME, name).unwrap();
+        let subject = subject.build();
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_issuer_name(&subject).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(days).unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+        let dir = settings.certificate_dir(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("fullchain.pem"), builder.build().to_pem().unwrap()).unwrap();
+        fs::write(dir.join("domains.json"), json!([name]).to_string()).unwrap();
+    }
+
+    const CONFIG: &str = "http {
+    server {
+        listen 80;
+        server_name example.com www.example.com;
+        root /var/www;
+    }
+
+    server {
+        listen 80;
+        server_name other.example.org;
+        location /.well-known/acme-challenge/ {
+            root /srv/challenges;
+        }
+    }
+
+    server {
+        listen 443 ssl;
+        server_name secure.example.net;
+    }
+}
+";
+
+    fn domains(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn challenge_include_is_inserted_once_and_reverted() {
+        let dir = TempDir::new("include");
+        let root = dir.0.join("nginx.conf");
+        fs::write(&root, CONFIG).unwrap();
+        let snippet = dir.0.join(SNIPPET_FILE);
+        let root = root.to_string_lossy().to_string();
+
+        let originals = insert_challenge_includes(&root, &snippet, &domains(&["example.com", "www.example.com"])).unwrap();
+        assert_eq!(originals, vec![(root.clone(), CONFIG.to_string())]);
+
+        let changed = fs::read_to_string(&root).unwrap();
+        let include = format!("        include {};", snippet.display());
+        assert_eq!(changed.matches(&include).count(), 1);
+        let lines: Vec<&str> = changed.lines().collect();
+        assert_eq!(lines[2], include);
+        assert_eq!(lines[3], "        listen 80;");
+
+        // A server that already answers challenges is left alone, and once included nothing changes
+        assert!(insert_challenge_includes(&root, &snippet, &domains(&["other.example.org"])).unwrap().is_empty());
+        fs::write(&snippet, challenge_snippet(&dir.0)).unwrap();
+        assert!(insert_challenge_includes(&root, &snippet, &domains(&["example.com"])).unwrap().is_empty());
+
+        restore_files(&originals);
+        assert_eq!(fs::read_to_string(&root).unwrap(), CONFIG);
+    }
+
+    #[test]
+    fn challenge_include_needs_a_port_80_server_for_every_domain() {
+        let dir = TempDir::new("missing");
+        let root = dir.0.join("nginx.conf");
+        fs::write(&root, CONFIG).unwrap();
+        let snippet = dir.0.join(SNIPPET_FILE);
+        let root = root.to_string_lossy().to_string();
+
+        let error = insert_challenge_includes(&root, &snippet, &domains(&["example.com", "secure.example.net"])).unwrap_err();
+        assert!(error.contains("secure.example.net"), "{}", error);
+        assert_eq!(fs::read_to_string(&root).unwrap(), CONFIG);
+    }
+
+    #[test]
+    fn validate_domains_normalizes_and_rejects() {
+        assert_eq!(
+            validate_domains(&domains(&[" Example.COM. ", "www.example.com", "example.com"])).unwrap(),
+            domains(&["example.com", "www.example.com"])
+        );
+        assert!(validate_domains(&domains(&["*.example.com"])).unwrap_err().contains("DNS-01"));
+        assert!(validate_domains(&domains(&["localhost"])).is_err());
+        assert!(validate_domains(&domains(&["-bad.example.com"])).is_err());
+        assert!(validate_domains(&domains(&["exa mple.com"])).is_err());
+        assert!(validate_domains(&domains(&[&format!("{}.com", "a".repeat(64))])).is_err());
+        assert!(validate_domains(&[]).is_err());
+    }
+
+    #[test]
+    fn certificates_are_due_within_the_renewal_window() {
+        let dir = TempDir::new("renewal");
+        let settings = settings(&dir.0);
+        store_certificate(&settings, "fresh.example.com", 60);
+        store_certificate(&settings, "expiring.example.com", 10);
+        fs::create_dir_all(settings.certificate_dir("broken.example.com")).unwrap();
+        fs::write(settings.certificate_dir("broken.example.com").join("domains.json"), "[\"broken.example.com\"]").unwrap();
+
+        let due: Vec<(String, bool)> = list_certificates(&settings)
+            .iter()
+            .map(|certificate| (certificate.name.clone(), renewal_due(certificate, settings.renew_days)))
+            .collect();
+        assert_eq!(
+            due,
+            vec![
+                ("broken.example.com".to_string(), true),
+                ("expiring.example.com".to_string(), true),
+                ("fresh.example.com".to_string(), false),
+            ]
+        );
+
+        let expiring = managed_certificate(&settings, "expiring.example.com", domains(&["expiring.example.com"]));
+        assert!(matches!(expiring.days_remaining, Some(9..=10)));
+        assert!(!renewal_due(&expiring, 5));
+    }
+}
@@ -1,24 +1,63 @@
 use actix_session::Session;
-use actix_web::{web, HttpResponse, Error};
+use actix_web::{web, HttpRequest, HttpResponse, Error};
+use crate::acme::{self, AcmeSettings, IssueCertificateRequest};
 use crate::commands;
-use crate::auth::get_stored_password;
+use crate::config_format::{self, FormatRequest};
+use crate::deploy::{self, DeployError, DeployRequest};
+use crate::effective_config::{self, ConfigSearchRequest, DumpError};
+use crate::auth::{self, get_stored_password};
+use crate::event_bus;
+use crate::fleet::{self, FleetError};
+use crate::health;
+use crate::instances::{self, InstanceQuery, NginxInstance};
+use crate::lint;
+use crate::log_export::{self, ExportOptions};
+use crate::log_search::{self, LogFilter, LogSearchError, LogSearchRequest, TrafficQuery};
+use crate::nginx_build;
+use crate::nginx_config;
+use crate::rollout::{self, RolloutRequest};
+use crate::route_resolver::{self, ResolveError, RouteQuery};
+use crate::systemd::{self, SystemdLogOptions};
+use crate::templates::{self, PreviewRequest, Template, TemplateError};
+use crate::tls;
+use crate::upstreams;
+use crate::vhosts::{self, DeleteSiteQuery, VhostError, VhostRequest};
+use futures_util::{stream, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+use std::time::Duration;
 use std::process::Command;
 use std::env::consts::OS;
 use std::io::Write;
 use std::process::Stdio;
 use std::path::Path;
 
+// How often an idle event stream sends a keep-alive comment so proxies don't drop it
+const EVENT_STREAM_KEEPALIVE: Duration = Duration::from_secs(15);
+
 async fn check_session(session: Session) -> Result<HttpResponse, Error> {
-    if let Some(logged_in) = session.get::<bool>("logged_in")? {
-        if logged_in {
-            return Ok(HttpResponse::Ok().finish());
-        }
+    match auth::current_session(&session)? {
+        Some(info) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "authenticated": true,
+            "user": info.user,
+            "expires_in": info.expires_in,
+            "idle_expires_in": info.idle_expires_in,
+            "absolute_expires_in": info.absolute_expires_in
+        }))),
+        None => Ok(HttpResponse::Unauthorized().finish()),
     }
-    Ok(HttpResponse::Unauthorized().finish())
 }
 
-fn execute_sudo_command_with_stored_password(args: Vec<&str>) -> Result<std::process::Output, String> {
-    let password = get_stored_password().ok_or("No sudo password stored")?;
+/// `controller` is whether the request came from the fleet controller over the agent's mutual TLS listener
+fn execute_sudo_command_with_stored_password(args: Vec<&str>, controller: bool) -> Result<std::process::Output, String> {
+    let password = match get_stored_password() {
+        Some(password) => password,
+        // Agents act for the controller without anyone logging in; they run as root or with a
+        // NOPASSWD sudoers rule. Everyone else has to log in first.
+        None if controller => {
+            return Command::new("sudo").arg("-n").args(args).output().map_err(|e| e.to_string());
+        }
+        None => return Err("No sudo password stored".to_string()),
+    };
     
     let mut child = Command::new("sudo")
         .arg("-S")
@@ -38,13 +77,13 @@ fn execute_sudo_command_with_stored_password(args: Vec<&str>) -> Result<std::pro
     child.wait_with_output().map_err(|e| e.to_string())
 }
 
-fn start_nginx_browser() -> Result<String, String> {
+fn start_nginx_browser(instance: &NginxInstance, controller: bool) -> Result<String, String> {
     let output = match OS {
-        "linux" => execute_sudo_command_with_stored_password(vec!["systemctl", "start", "nginx"])?,
+        "linux" => execute_sudo_command_with_stored_password(vec!["systemctl", "start", &instance.unit], controller)?,
         "macos" => Command::new("brew")
             .arg("services")
             .arg("start")
-            .arg("nginx")
+            .arg(instance.service_name())
             .output()
             .map_err(|e| e.to_string())?,
         _ => return Err("Unsupported OS".into()),
@@ -59,13 +98,13 @@ fn start_nginx_browser() -> Result<String, String> {
     }
 }
 
-fn stop_nginx_browser() -> Result<String, String> {
+fn stop_nginx_browser(instance: &NginxInstance, controller: bool) -> Result<String, String> {
     let output = match OS {
-        "linux" => execute_sudo_command_with_stored_password(vec!["systemctl", "stop", "nginx"])?,
+        "linux" => execute_sudo_command_with_stored_password(vec!["systemctl", "stop", &instance.unit], controller)?,
         "macos" => Command::new("brew")
             .arg("services")
             .arg("stop")
-            .arg("nginx")
+            .arg(instance.service_name())
             .output()
             .map_err(|e| e.to_string())?,
         _ => return Err("Unsupported OS".into()),
@@ -80,13 +119,13 @@ fn stop_nginx_browser() -> Result<String, String> {
     }
 }
 
-fn restart_nginx_browser() -> Result<String, String> {
+fn restart_nginx_browser(instance: &NginxInstance, controller: bool) -> Result<String, String> {
     let output = match OS {
-        "linux" => execute_sudo_command_with_stored_password(vec!["systemctl", "restart", "nginx"])?,
+        "linux" => execute_sudo_command_with_stored_password(vec!["systemctl", "restart", &instance.unit], controller)?,
         "macos" => Command::new("brew")
             .arg("services")
             .arg("restart")
-            .arg("nginx")
+            .arg(instance.service_name())
             .output()
             .map_err(|e| e.to_string())?,
         _ => return Err("Unsupported OS".into()),
@@ -101,9 +140,37 @@ fn restart_nginx_browser() -> Result<String, String> {
     }
 }
 
-async fn get_system_metrics_http() -> Result<HttpResponse, Error> {
+/// The instance named by `?instance=` (the first one when omitted), or a 404 response
+fn resolve_instance(query: &InstanceQuery) -> Result<NginxInstance, HttpResponse> {
+    instances::get_instance(query.instance.as_deref())
+        .map_err(|e| HttpResponse::NotFound().json(serde_json::json!({ "error": e })))
+}
+
+/// Every nginx instance rustinx manages; pass an `id` as `?instance=` to the other routes
+pub async fn list_instances_http(req: HttpRequest, session: Session) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    Ok(HttpResponse::Ok().json(instances::list_instances()))
+}
+
+async fn get_system_metrics_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
     // Get metrics from the same function used by Tauri
-    match commands::get_system_metrics() {
+    match commands::get_system_metrics(Some(instance.id)) {
         Ok((cpu, total_mem, used_mem, tasks, worker_count, tx_bytes, rx_bytes)) => {
             Ok(HttpResponse::Ok().json(serde_json::json!({
                 "cpu": cpu,
@@ -121,9 +188,13 @@ async fn get_system_metrics_http() -> Result<HttpResponse, Error> {
     }
 }
 
-async fn start_nginx_http(session: Session) -> Result<HttpResponse, Error> {
-    if session.get::<bool>("logged_in")?.unwrap_or(false) {
-        match start_nginx_browser() {
+async fn start_nginx_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if auth::is_authenticated(&req, &session)? {
+        let instance = match resolve_instance(&instance) {
+            Ok(instance) => instance,
+            Err(response) => return Ok(response),
+        };
+        match start_nginx_browser(&instance, auth::is_controller_peer(&req)) {
             Ok(message) => Ok(HttpResponse::Ok().json(serde_json::json!({
                 "success": true,
                 "message": message
@@ -141,9 +212,13 @@ async fn start_nginx_http(session: Session) -> Result<HttpResponse, Error> {
     }
 }
 
-async fn stop_nginx_http(session: Session) -> Result<HttpResponse, Error> {
-    if session.get::<bool>("logged_in")?.unwrap_or(false) {
-        match stop_nginx_browser() {
+async fn stop_nginx_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if auth::is_authenticated(&req, &session)? {
+        let instance = match resolve_instance(&instance) {
+            Ok(instance) => instance,
+            Err(response) => return Ok(response),
+        };
+        match stop_nginx_browser(&instance, auth::is_controller_peer(&req)) {
             Ok(message) => Ok(HttpResponse::Ok().json(serde_json::json!({
                 "success": true,
                 "message": message
@@ -161,9 +236,13 @@ async fn stop_nginx_http(session: Session) -> Result<HttpResponse, Error> {
     }
 }
 
-async fn restart_nginx_http(session: Session) -> Result<HttpResponse, Error> {
-    if session.get::<bool>("logged_in")?.unwrap_or(false) {
-        match restart_nginx_browser() {
+async fn restart_nginx_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if auth::is_authenticated(&req, &session)? {
+        let instance = match resolve_instance(&instance) {
+            Ok(instance) => instance,
+            Err(response) => return Ok(response),
+        };
+        match restart_nginx_browser(&instance, auth::is_controller_peer(&req)) {
             Ok(message) => Ok(HttpResponse::Ok().json(serde_json::json!({
                 "success": true,
                 "message": message
@@ -181,25 +260,178 @@ async fn restart_nginx_http(session: Session) -> Result<HttpResponse, Error> {
     }
 }
 
-async fn get_nginx_logs_http(
+async fn get_nginx_status_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+    let output = match OS {
+        "linux" => Command::new("systemctl")
+            .arg("is-active")
+            .arg(&instance.unit)
+            .output()
+            .map_err(|e| e.to_string()),
+        "macos" => Command::new("brew")
+            .arg("services")
+            .arg("list")
+            .output()
+            .map_err(|e| e.to_string()),
+        _ => Err("Unsupported OS".into()),
+    };
+
+    match output {
+        Ok(output) if output.status.success() => {
+            let status = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            let is_active = match OS {
+                "linux" => status == "active",
+                "macos" => status
+                    .lines()
+                    .any(|line| line.split_whitespace().next() == Some(instance.service_name()) && line.contains("started")),
+                _ => false,
+            };
+
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "status": if is_active { "active" } else { "inactive" },
+                "raw_output": status
+            })))
+        }
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "status": "inactive",
+                "error": stderr
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "status": "unknown",
+                "error": e
+            })))
+        }
+    }
+}
+
+async fn get_nginx_config_path_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+    match commands::nginx_conf_path(&instance) {
+        Ok(path) => {
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "path": path,
+                "found": true
+            })))
+        }
+        Err(e) if e.starts_with("Failed to execute") => {
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": e
+            })))
+        }
+        Err(_) => {
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "path": instance.config_path().unwrap_or_else(|| "/etc/nginx/nginx.conf".to_string()),
+                "found": false,
+                "message": "Using default path"
+            })))
+        }
+    }
+}
+
+async fn get_nginx_version_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+    let output = instance
+        .command()
+        .arg("-V")
+        .output()
+        .map_err(|e| format!("Failed to execute nginx -V: {}", e));
+
+    match output {
+        Ok(output) => {
+            // nginx -V outputs to stderr, not stdout
+            let version_info = if !output.stderr.is_empty() {
+                String::from_utf8_lossy(&output.stderr).to_string()
+            } else {
+                String::from_utf8_lossy(&output.stdout).to_string()
+            };
+
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "version_info": version_info,
+                "success": true
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": e,
+                "success": false
+            })))
+        }
+    }
+}
+
+pub async fn get_nginx_health_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let snapshot = web::block(move || health::collect_health_snapshot(&instance)).await?;
+    Ok(HttpResponse::Ok().json(snapshot))
+}
+
+pub async fn get_nginx_logs_http(
+    req: HttpRequest,
     session: Session,
+    instance: web::Query<InstanceQuery>,
     query: web::Query<std::collections::HashMap<String, String>>,
 ) -> Result<HttpResponse, Error> {
-    if !session.get::<bool>("logged_in")?.unwrap_or(false) {
+    if !auth::is_authenticated(&req, &session)? {
         return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
             "error": "Authentication required"
         })));
     }
 
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
     let log_type = query.get("type").unwrap_or(&"access".to_string()).clone();
     let lines = query.get("lines")
         .and_then(|s| s.parse::<usize>().ok())
         .unwrap_or(100);
 
     // Find the actual log file path from nginx configuration
-    match find_nginx_log_path(&log_type) {
+    match find_nginx_log_path(&instance, &log_type) {
         Ok(log_path) => {
-            match read_log_tail(&log_path, lines) {
+            match log_search::read_log_tail(&log_path, lines) {
                 Ok(logs) => Ok(HttpResponse::Ok().json(serde_json::json!({
                     "logs": logs,
                     "type": log_type,
@@ -216,53 +448,289 @@ async fn get_nginx_logs_http(
     }
 }
 
-fn find_nginx_log_path(log_type: &str) -> Result<String, String> {
-    // First, check if nginx was compiled with stderr/stdout logging
-    if let Ok(build_info) = get_nginx_build_config() {
-        if build_info.contains("--error-log-path=stderr") && log_type == "error" {
-            return Err("Nginx is configured to log errors to stderr. Error logs are not available as files when using --error-log-path=stderr. You can view nginx error logs using 'journalctl -u nginx' or by checking your process manager logs.".to_string());
-        }
-        if build_info.contains("--error-log-path=/dev/stderr") && log_type == "error" {
-            return Err("Nginx is configured to log errors to stderr. Error logs are not available as files when using --error-log-path=/dev/stderr. You can view nginx error logs using 'journalctl -u nginx' or by checking your process manager logs.".to_string());
-        }
-        if (build_info.contains("--access-log-path=stdout") || build_info.contains("--access-log-path=/dev/stdout")) && log_type == "access" {
-            return Err("Nginx is configured to log access to stdout. Access logs are not available as files when logging to stdout. You can view nginx access logs using 'journalctl -u nginx' or by checking your process manager logs.".to_string());
-        }
+pub async fn search_nginx_logs_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    query: web::Query<LogSearchRequest>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
     }
 
-    // Try common nginx config locations
-    let config_paths = [
-        "/etc/nginx/nginx.conf",
-        "/usr/local/etc/nginx/nginx.conf",
-        "/opt/nginx/nginx.conf",
-        "/usr/local/nginx/conf/nginx.conf",
-    ];
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
 
-    let mut config_path = None;
-    for path in &config_paths {
-        if Path::new(path).exists() {
-            config_path = Some(*path);
-            break;
-        }
+    let request = query.into_inner();
+    match web::block(move || log_search::search_nginx_log(&instance, &request)).await? {
+        Ok(page) => Ok(HttpResponse::Ok().json(page)),
+        Err(LogSearchError::InvalidRequest(e)) => Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e
+        }))),
+        Err(LogSearchError::Failed(e)) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e
+        })))
+    }
+}
+
+/// Requests in the access log since `since` (the last minute by default), by status class
+pub async fn get_nginx_traffic_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    query: web::Query<TrafficQuery>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
     }
 
-    let config_path = config_path.ok_or_else(|| {
-        format!("Could not find nginx.conf in any of these locations: {:?}", config_paths)
-    })?;
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
 
-    // Read and parse nginx.conf to find log directives
-    parse_nginx_config_for_logs(config_path, log_type)
+    let query = query.into_inner();
+    match web::block(move || log_search::traffic_stats(&instance, &query)).await? {
+        Ok(stats) => Ok(HttpResponse::Ok().json(stats)),
+        Err(LogSearchError::InvalidRequest(e)) => Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e
+        }))),
+        Err(LogSearchError::Failed(e)) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e
+        })))
+    }
 }
 
-fn get_nginx_build_config() -> Result<String, String> {
-    let output = Command::new("nginx")
-        .arg("-V")
-        .output()
-        .map_err(|e| format!("Failed to execute nginx -V: {}", e))?;
+pub async fn export_nginx_logs_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    options: web::Query<ExportOptions>,
+    filter: web::Query<LogFilter>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
 
-    // nginx -V outputs build info to stderr
-    let build_info = String::from_utf8_lossy(&output.stderr);
-    Ok(build_info.to_string())
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let options = options.into_inner();
+    let prepared = match log_export::prepare_export(&instance, &options, &filter) {
+        Ok(prepared) => prepared,
+        Err(LogSearchError::InvalidRequest(e)) => return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e
+        }))),
+        Err(LogSearchError::Failed(e)) => return Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e
+        })))
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(options.content_type())
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", options.file_name())))
+        .streaming(log_export::export_stream(options, prepared)))
+}
+
+pub async fn get_systemd_logs_http(
+    req: HttpRequest,
+    session: Session,
+    body: web::Json<SystemdLogOptions>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let options = body.into_inner();
+    if let Err(e) = systemd::validate_options(&options) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e
+        })));
+    }
+    let service_name = options.service_name.clone();
+
+    match web::block(move || systemd::query_systemd_logs(&options)).await? {
+        Ok(page) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "entries": page.entries,
+            "cursor": page.cursor,
+            "service_name": service_name
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e
+        })))
+    }
+}
+
+pub async fn get_allowed_units_http(req: HttpRequest, session: Session) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "units": systemd::allowed_units()
+    })))
+}
+
+/// Latest probe results for every upstream server and `proxy_pass` target
+pub async fn get_upstream_health_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    Ok(HttpResponse::Ok().json(upstreams::upstream_report(&instance)))
+}
+
+pub async fn list_acme_certificates_http(req: HttpRequest, session: Session) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let certificates = web::block(|| acme::list_certificates(&AcmeSettings::from_env())).await?;
+    Ok(HttpResponse::Ok().json(certificates))
+}
+
+/// Issues a certificate over ACME HTTP-01; blocks until the CA has validated every domain
+pub async fn issue_acme_certificate_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    request: web::Json<IssueCertificateRequest>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let domains = request.into_inner().domains;
+    match web::block(move || acme::issue_certificate(&instance, &AcmeSettings::from_env(), &domains)).await? {
+        Ok(certificate) => Ok(HttpResponse::Ok().json(certificate)),
+        Err(e) => Ok(HttpResponse::BadGateway().json(serde_json::json!({
+            "error": e
+        }))),
+    }
+}
+
+/// Every certificate the config references, with expiry, key and SAN checks
+pub async fn get_tls_certificates_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    match web::block(move || tls::tls_inventory(&instance)).await {
+        Ok(Ok(inventory)) => Ok(HttpResponse::Ok().json(inventory)),
+        Ok(Err(e)) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e.to_string()
+        }))),
+    }
+}
+
+/// Streams new journal entries as `journal_entry` server-sent events until the client disconnects
+pub async fn follow_systemd_logs_http(
+    req: HttpRequest,
+    session: Session,
+    query: web::Query<SystemdLogOptions>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    if let Err(e) = systemd::validate_options(&query) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e
+        })));
+    }
+
+    let entries = match systemd::follow_journal(&query) {
+        Ok(entries) => Box::pin(entries),
+        Err(e) => return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e
+        })))
+    };
+
+    let events = stream::unfold(entries, |mut entries| async move {
+        let chunk = match tokio::time::timeout(EVENT_STREAM_KEEPALIVE, entries.next()).await {
+            Ok(Some(entry)) => format_sse_event("journal_entry", &serde_json::json!(entry)),
+            Ok(None) => return None,
+            Err(_) => web::Bytes::from_static(b": keep-alive\n\n"),
+        };
+        Some((Ok::<_, Error>(chunk), entries))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(events))
+}
+
+pub(crate) fn find_nginx_log_path(instance: &NginxInstance, log_type: &str) -> Result<String, String> {
+    // Paths set on the instance win over anything nginx reports
+    let configured = match log_type {
+        "access" => &instance.access_log,
+        "error" => &instance.error_log,
+        _ => return Err("Invalid log type".to_string()),
+    };
+    if let Some(path) = configured {
+        return Ok(path.clone());
+    }
+
+    // Then check if nginx was compiled with stderr/stdout logging
+    if let Ok(build) = nginx_build::nginx_build(instance) {
+        if log_type == "error" && matches!(build.paths.error_log.as_str(), "stderr" | "/dev/stderr") {
+            return Err(format!("Nginx is configured to log errors to stderr. Error logs are not available as files when using --error-log-path={}. You can view nginx error logs using 'journalctl -u {}' or by checking your process manager logs.", build.paths.error_log, instance.unit));
+        }
+        if log_type == "access" && matches!(build.paths.http_log.as_str(), "stdout" | "/dev/stdout") {
+            return Err(format!("Nginx is configured to log access to stdout. Access logs are not available as files when logging to stdout. You can view nginx access logs using 'journalctl -u {}' or by checking your process manager logs.", instance.unit));
+        }
+    }
+
+    // The instance's own config, the one nginx was built with, or a common location
+    let config_path = nginx_config::find_main_config(instance)
+        .ok_or_else(|| format!("Could not find nginx.conf for instance {}", instance.id))?;
+
+    // Read and parse nginx.conf to find log directives
+    parse_nginx_config_for_logs(&config_path, log_type)
 }
 
 fn parse_nginx_config_for_logs(config_path: &str, log_type: &str) -> Result<String, String> {
@@ -382,38 +850,697 @@ fn search_included_configs(base_config: &str, include_pattern: &str, log_type: &
     Err("Log file not found in included configs".to_string())
 }
 
-fn read_log_tail(file_path: &str, lines: usize) -> Result<Vec<String>, String> {
-    if !Path::new(file_path).exists() {
-        return Ok(vec![]);
+/// Best-practice and security findings for the current config, each with its file:line
+pub async fn lint_nginx_config_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
     }
 
-    // Use tail command to get last N lines efficiently
-    let output = Command::new("tail")
-        .arg("-n")
-        .arg(lines.to_string())
-        .arg(file_path)
-        .output()
-        .map_err(|e| format!("Failed to execute tail command: {}", e))?;
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
 
-    if output.status.success() {
-        let content = String::from_utf8_lossy(&output.stdout);
-        let logs: Vec<String> = content
-            .lines()
-            .filter(|line| !line.trim().is_empty())
-            .map(|line| line.to_string())
-            .collect();
-        Ok(logs)
-    } else {
-        let error = String::from_utf8_lossy(&output.stderr);
-        Err(format!("tail command failed: {}", error))
+    match web::block(move || lint::lint_report(&instance)).await? {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e
+        }))),
+    }
+}
+
+/// Formats the config files; `mode` picks between a CI check, a dry-run diff and rewriting them
+pub async fn format_nginx_config_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    request: web::Json<FormatRequest>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let request = request.into_inner();
+    match web::block(move || config_format::format_config(&instance, &request)).await? {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e
+        }))),
+    }
+}
+
+fn dump_error_response(error: DumpError) -> HttpResponse {
+    let body = serde_json::json!({ "error": error.to_string() });
+    match error {
+        DumpError::InvalidRequest(_) => HttpResponse::BadRequest().json(body),
+        DumpError::Failed(_) => HttpResponse::InternalServerError().json(body),
+    }
+}
+
+/// The config nginx actually loads (`nginx -T`), as an include tree with each file's content
+pub async fn get_effective_config_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    match web::block(move || effective_config::effective_config(&instance)).await? {
+        Ok(config) => Ok(HttpResponse::Ok().json(config)),
+        Err(e) => Ok(dump_error_response(e)),
+    }
+}
+
+pub async fn search_effective_config_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    query: web::Query<ConfigSearchRequest>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let request = query.into_inner();
+    match web::block(move || effective_config::search_effective(&instance, &request)).await? {
+        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Err(e) => Ok(dump_error_response(e)),
+    }
+}
+
+/// `nginx -V` parsed into version, TLS library, configure flags, paths and modules
+pub async fn get_nginx_build_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    match web::block(move || nginx_build::nginx_build(&instance)).await? {
+        Ok(build) => Ok(HttpResponse::Ok().json(build)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": e
+        }))),
+    }
+}
+
+/// Which server and location would handle `host`, `port` and `uri`, with the effective root,
+/// proxy_pass and try_files
+pub async fn resolve_route_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    query: web::Query<RouteQuery>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let query = query.into_inner();
+    match web::block(move || route_resolver::resolve_current_route(&instance, &query)).await? {
+        Ok(resolution) => Ok(HttpResponse::Ok().json(resolution)),
+        Err(e) => {
+            let body = serde_json::json!({ "error": e.to_string() });
+            Ok(match e {
+                ResolveError::InvalidRequest(_) => HttpResponse::BadRequest().json(body),
+                ResolveError::NotFound(_) => HttpResponse::NotFound().json(body),
+                ResolveError::Failed(_) => HttpResponse::InternalServerError().json(body),
+            })
+        }
+    }
+}
+
+fn vhost_error_response(error: VhostError) -> HttpResponse {
+    let body = serde_json::json!({ "error": error.to_string() });
+    match error {
+        VhostError::InvalidRequest(_) => HttpResponse::BadRequest().json(body),
+        VhostError::NotFound(_) => HttpResponse::NotFound().json(body),
+        VhostError::Failed(_) => HttpResponse::InternalServerError().json(body),
+    }
+}
+
+pub async fn list_vhosts_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    match web::block(move || vhosts::list_vhosts(&instance)).await? {
+        Ok(vhosts) => Ok(HttpResponse::Ok().json(vhosts)),
+        Err(e) => Ok(vhost_error_response(e)),
+    }
+}
+
+/// Writes a new site from a template; nginx is only reloaded when the site is enabled
+pub async fn create_vhost_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    request: web::Json<VhostRequest>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let request = request.into_inner();
+    match web::block(move || vhosts::create_vhost(&instance, &request)).await? {
+        Ok(site) => Ok(HttpResponse::Created().json(site)),
+        Err(e) => Ok(vhost_error_response(e)),
+    }
+}
+
+pub async fn update_vhost_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    path: web::Path<String>,
+    request: web::Json<VhostRequest>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let mut request = request.into_inner();
+    request.name = path.into_inner();
+    match web::block(move || vhosts::update_vhost(&instance, &request)).await? {
+        Ok(site) => Ok(HttpResponse::Ok().json(site)),
+        Err(e) => Ok(vhost_error_response(e)),
+    }
+}
+
+pub async fn enable_vhost_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let name = path.into_inner();
+    match web::block(move || vhosts::enable_site(&instance, &name)).await? {
+        Ok(site) => Ok(HttpResponse::Ok().json(site)),
+        Err(e) => Ok(vhost_error_response(e)),
+    }
+}
+
+pub async fn disable_vhost_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let name = path.into_inner();
+    match web::block(move || vhosts::disable_site(&instance, &name)).await? {
+        Ok(site) => Ok(HttpResponse::Ok().json(site)),
+        Err(e) => Ok(vhost_error_response(e)),
+    }
+}
+
+pub async fn delete_vhost_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    path: web::Path<String>,
+    query: web::Query<DeleteSiteQuery>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let name = path.into_inner();
+    let force = query.force;
+    match web::block(move || vhosts::delete_site(&instance, &name, force)).await? {
+        Ok(()) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => Ok(vhost_error_response(e)),
+    }
+}
+
+fn template_error_response(error: TemplateError) -> HttpResponse {
+    let body = serde_json::json!({ "error": error.to_string() });
+    match error {
+        TemplateError::InvalidRequest(_) => HttpResponse::BadRequest().json(body),
+        TemplateError::NotFound(_) => HttpResponse::NotFound().json(body),
+        TemplateError::Failed(_) => HttpResponse::InternalServerError().json(body),
+    }
+}
+
+pub async fn list_templates_http(req: HttpRequest, session: Session) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    match web::block(templates::list_templates).await? {
+        Ok(templates) => Ok(HttpResponse::Ok().json(templates)),
+        Err(e) => Ok(template_error_response(e)),
+    }
+}
+
+pub async fn save_template_http(
+    req: HttpRequest,
+    session: Session,
+    path: web::Path<String>,
+    template: web::Json<Template>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let mut template = template.into_inner();
+    template.name = path.into_inner();
+    match web::block(move || templates::save_template(&template)).await? {
+        Ok(()) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => Ok(template_error_response(e)),
+    }
+}
+
+pub async fn delete_template_http(req: HttpRequest, session: Session, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let name = path.into_inner();
+    match web::block(move || templates::delete_template(&name)).await? {
+        Ok(()) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => Ok(template_error_response(e)),
+    }
+}
+
+/// Renders a template and reports what `nginx -t` thinks of it; nothing is written to the live config
+pub async fn preview_template_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    path: web::Path<String>,
+    request: web::Json<PreviewRequest>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let name = path.into_inner();
+    let values = request.into_inner().values;
+    match web::block(move || templates::preview_template(&instance, &name, &values)).await? {
+        Ok(preview) => Ok(HttpResponse::Ok().json(preview)),
+        Err(e) => Ok(template_error_response(e)),
+    }
+}
+
+fn format_sse_event(event_name: &str, data: &serde_json::Value) -> web::Bytes {
+    // JSON-encode the payload so multi-line log entries stay on a single `data:` line
+    let data = serde_json::to_string(data).unwrap_or_else(|_| "null".to_string());
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event_name, data))
+}
+
+/// Server-Sent Events stream of the instance's events and those not tied to any instance
+pub async fn events_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    // The fleet controller has no session, so its stream only ends when it disconnects
+    let session_id = if auth::is_controller_peer(&req) {
+        None
+    } else {
+        match auth::session_id(&session)? {
+            Some(id) => Some(id),
+            None => return Ok(HttpResponse::Unauthorized().finish()),
+        }
+    };
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let receiver = event_bus::subscribe();
+    let state = (receiver, instance.id, session_id);
+    let events = stream::unfold(state, |(mut receiver, instance, session_id)| async move {
+        // End the stream once the client logs out or its session is revoked or expires
+        if let Some(id) = &session_id {
+            if !auth::is_session_active(id) {
+                return None;
+            }
+        }
+
+        // Other instances' events don't count as activity, so the keep-alive is due regardless
+        let deadline = tokio::time::Instant::now() + EVENT_STREAM_KEEPALIVE;
+        let chunk = loop {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Ok(bus_event)) if !bus_event.concerns(&instance) => continue,
+                Ok(Ok(bus_event)) => break format_sse_event(bus_event.event.name(), &bus_event.event.payload()),
+                Ok(Err(RecvError::Lagged(skipped))) => {
+                    break web::Bytes::from(format!(": skipped {} events\n\n", skipped))
+                }
+                Ok(Err(RecvError::Closed)) => return None,
+                Err(_) => break web::Bytes::from_static(b": keep-alive\n\n"),
+            }
+        };
+        Some((Ok::<_, Error>(chunk), (receiver, instance, session_id)))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(events))
+}
+
+fn fleet_error_response(error: FleetError) -> HttpResponse {
+    let body = serde_json::json!({ "error": error.to_string() });
+    match error {
+        FleetError::InvalidRequest(_) => HttpResponse::BadRequest().json(body),
+        FleetError::NotFound(_) => HttpResponse::NotFound().json(body),
+        FleetError::Failed(_) => HttpResponse::BadGateway().json(body),
+    }
+}
+
+/// The agents this controller manages
+pub async fn list_fleet_agents_http(req: HttpRequest, session: Session) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    match web::block(fleet::list_agents).await? {
+        Ok(agents) => Ok(HttpResponse::Ok().json(agents)),
+        Err(e) => Ok(fleet_error_response(e)),
+    }
+}
+
+/// Health, metrics and alerts of every instance on every agent
+pub async fn get_fleet_status_http(req: HttpRequest, session: Session) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    match web::block(fleet::fleet_status).await? {
+        Ok(status) => Ok(HttpResponse::Ok().json(status)),
+        Err(e) => Ok(fleet_error_response(e)),
+    }
+}
+
+/// Forwards `/fleet/agents/{id}/api/...` to the agent's `/api/...`, e.g. a POST to
+/// `/fleet/agents/web-01/api/nginx/restart` restarts nginx on web-01. Streaming endpoints such as
+/// `/events` aren't forwarded.
+pub async fn proxy_fleet_agent_http(
+    session: Session,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let (agent, path) = path.into_inner();
+    let method = req.method().to_string();
+    let query = req.query_string().to_string();
+    let content_type = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let result = web::block(move || {
+        fleet::proxy_request(&agent, &method, &path, &query, content_type.as_deref(), body.to_vec())
+    })
+    .await?;
+    match result {
+        Ok(proxied) => {
+            let status = actix_web::http::StatusCode::from_u16(proxied.status)
+                .unwrap_or(actix_web::http::StatusCode::BAD_GATEWAY);
+            let mut response = HttpResponse::build(status);
+            if let Some(content_type) = proxied.content_type {
+                response.content_type(content_type);
+            }
+            Ok(response.body(proxied.body))
+        }
+        Err(e) => Ok(fleet_error_response(e)),
+    }
+}
+
+fn deploy_error_response(error: DeployError) -> HttpResponse {
+    let body = serde_json::json!({ "error": error.to_string() });
+    match error {
+        DeployError::InvalidRequest(_) => HttpResponse::BadRequest().json(body),
+        DeployError::NotFound(_) => HttpResponse::NotFound().json(body),
+        DeployError::Failed(_) => HttpResponse::InternalServerError().json(body),
+    }
+}
+
+/// Writes config files, then `nginx -t` and a graceful reload; the files are restored if either fails
+pub async fn deploy_config_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    request: web::Json<DeployRequest>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let request = request.into_inner();
+    match web::block(move || deploy::deploy_config(&instance, &request)).await? {
+        Ok(deployment) => Ok(HttpResponse::Ok().json(deployment)),
+        Err(e) => Ok(deploy_error_response(e)),
+    }
+}
+
+/// Deployments of the instance that can still be rolled back
+pub async fn list_deployments_http(req: HttpRequest, session: Session, instance: web::Query<InstanceQuery>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    Ok(HttpResponse::Ok().json(deploy::list_deployments(&instance)))
+}
+
+pub async fn rollback_deployment_http(
+    req: HttpRequest,
+    session: Session,
+    instance: web::Query<InstanceQuery>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let instance = match resolve_instance(&instance) {
+        Ok(instance) => instance,
+        Err(response) => return Ok(response),
+    };
+
+    let id = path.into_inner();
+    match web::block(move || deploy::rollback_deployment(&instance, &id)).await? {
+        Ok(deployment) => Ok(HttpResponse::Ok().json(deployment)),
+        Err(e) => Ok(deploy_error_response(e)),
+    }
+}
+
+/// Starts rolling config files out to the agents in waves; follow it on `/events` as `fleet_rollout`
+pub async fn start_rollout_http(req: HttpRequest, session: Session, request: web::Json<RolloutRequest>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let request = request.into_inner();
+    match web::block(move || rollout::start_rollout(request)).await? {
+        Ok(rollout) => Ok(HttpResponse::Accepted().json(rollout)),
+        Err(e) => Ok(fleet_error_response(e)),
+    }
+}
+
+pub async fn list_rollouts_http(req: HttpRequest, session: Session) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    Ok(HttpResponse::Ok().json(rollout::list_rollouts()))
+}
+
+pub async fn get_rollout_http(req: HttpRequest, session: Session, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    if !auth::is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    match rollout::get_rollout(&path.into_inner()) {
+        Ok(rollout) => Ok(HttpResponse::Ok().json(rollout)),
+        Err(e) => Ok(fleet_error_response(e)),
     }
 }
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.route("/session", web::get().to(check_session))
+    cfg.route("/login", web::post().to(auth::login))
+        .route("/session", web::get().to(check_session))
+        .route("/logout", web::post().to(auth::logout))
+        .route("/sessions", web::get().to(auth::list_sessions))
+        .route("/sessions/{id}", web::delete().to(auth::revoke_session))
+        .route("/instances", web::get().to(list_instances_http))
         .route("/system-metrics", web::get().to(get_system_metrics_http))
         .route("/nginx/start", web::post().to(start_nginx_http))
         .route("/nginx/stop", web::post().to(stop_nginx_http))
         .route("/nginx/restart", web::post().to(restart_nginx_http))
-        .route("/nginx/logs", web::get().to(get_nginx_logs_http));
+        .route("/nginx/status", web::get().to(get_nginx_status_http))
+        .route("/nginx/config-path", web::get().to(get_nginx_config_path_http))
+        .route("/nginx/version", web::get().to(get_nginx_version_http))
+        .route("/nginx/logs", web::get().to(get_nginx_logs_http))
+        .route("/nginx/logs/search", web::get().to(search_nginx_logs_http))
+        .route("/nginx/logs/export", web::get().to(export_nginx_logs_http))
+        .route("/nginx/traffic", web::get().to(get_nginx_traffic_http))
+        .route("/nginx/health", web::get().to(get_nginx_health_http))
+        .route("/nginx/deploy", web::get().to(list_deployments_http))
+        .route("/nginx/deploy", web::post().to(deploy_config_http))
+        .route("/nginx/deploy/{id}/rollback", web::post().to(rollback_deployment_http))
+        .route("/nginx/lint", web::get().to(lint_nginx_config_http))
+        .route("/nginx/format", web::post().to(format_nginx_config_http))
+        .route("/nginx/effective-config", web::get().to(get_effective_config_http))
+        .route("/nginx/effective-config/search", web::get().to(search_effective_config_http))
+        .route("/nginx/resolve", web::get().to(resolve_route_http))
+        .route("/nginx/build", web::get().to(get_nginx_build_http))
+        .route("/systemd/logs", web::post().to(get_systemd_logs_http))
+        .route("/systemd/logs/follow", web::get().to(follow_systemd_logs_http))
+        .route("/systemd/units", web::get().to(get_allowed_units_http))
+        .route("/upstreams", web::get().to(get_upstream_health_http))
+        .route("/tls/certificates", web::get().to(get_tls_certificates_http))
+        .route("/acme/certificates", web::get().to(list_acme_certificates_http))
+        .route("/acme/certificates", web::post().to(issue_acme_certificate_http))
+        .route("/vhosts", web::get().to(list_vhosts_http))
+        .route("/vhosts", web::post().to(create_vhost_http))
+        .route("/vhosts/{name}", web::put().to(update_vhost_http))
+        .route("/vhosts/{name}", web::delete().to(delete_vhost_http))
+        .route("/vhosts/{name}/enable", web::post().to(enable_vhost_http))
+        .route("/vhosts/{name}/disable", web::post().to(disable_vhost_http))
+        .route("/templates", web::get().to(list_templates_http))
+        .route("/templates/{name}", web::put().to(save_template_http))
+        .route("/templates/{name}", web::delete().to(delete_template_http))
+        .route("/templates/{name}/preview", web::post().to(preview_template_http))
+        .route("/fleet/agents", web::get().to(list_fleet_agents_http))
+        .route("/fleet/status", web::get().to(get_fleet_status_http))
+        .route("/fleet/agents/{id}/api/{path:.*}", web::route().to(proxy_fleet_agent_http))
+        .route("/fleet/rollouts", web::get().to(list_rollouts_http))
+        .route("/fleet/rollouts", web::post().to(start_rollout_http))
+        .route("/fleet/rollouts/{id}", web::get().to(get_rollout_http))
+        .route("/events", web::get().to(events_http));
 }
\ No newline at end of file
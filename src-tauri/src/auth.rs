@@ -1,23 +1,237 @@
-
 use actix_session::Session;
-use actix_web::{web, Error, HttpResponse};
-use serde::Deserialize;
+use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
 use std::io::Write;
 use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Deserialize)]
 pub struct LoginRequest {
     password: String,
 }
 
+// Key under which the server-side session id is kept in the session cookie
+const SESSION_ID_KEY: &str = "session_id";
+
+// Defaults used when RUSTINX_SESSION_IDLE_TIMEOUT / RUSTINX_SESSION_ABSOLUTE_TIMEOUT are unset (seconds)
+const DEFAULT_IDLE_TIMEOUT: u64 = 30 * 60;
+const DEFAULT_ABSOLUTE_TIMEOUT: u64 = 12 * 60 * 60;
+
+#[derive(Clone, Debug)]
+struct SessionRecord {
+    user: String,
+    remote_addr: Option<String>,
+    user_agent: Option<String>,
+    created_at: u64,
+    last_seen: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub user: String,
+    pub remote_addr: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: u64,
+    pub last_seen: u64,
+    pub idle_expires_in: u64,
+    pub absolute_expires_in: u64,
+    /// Seconds until the session expires, whichever timeout comes first
+    pub expires_in: u64,
+}
+
 // Global password storage for sudo operations
 lazy_static::lazy_static! {
     static ref PASSWORD_STORE: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(HashMap::new()));
+    static ref SESSION_STORE: Arc<Mutex<HashMap<String, SessionRecord>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn timeout_from_env(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(default)
+}
+
+fn idle_timeout() -> u64 {
+    timeout_from_env("RUSTINX_SESSION_IDLE_TIMEOUT", DEFAULT_IDLE_TIMEOUT)
+}
+
+fn absolute_timeout() -> u64 {
+    timeout_from_env("RUSTINX_SESSION_ABSOLUTE_TIMEOUT", DEFAULT_ABSOLUTE_TIMEOUT)
+}
+
+/// `len` random bytes, hex-encoded; used for session, deployment and rollout ids
+pub(crate) fn generate_id(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn generate_session_id() -> String {
+    generate_id(32)
+}
+
+/// The account whose sudo password was validated; under `sudo` this is the invoking user
+pub fn current_username() -> String {
+    std::env::var("SUDO_USER")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+impl SessionRecord {
+    fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.last_seen) >= idle_timeout()
+            || now.saturating_sub(self.created_at) >= absolute_timeout()
+    }
+
+    fn to_info(&self, id: &str, now: u64) -> SessionInfo {
+        let idle_expires_in = (self.last_seen + idle_timeout()).saturating_sub(now);
+        let absolute_expires_in = (self.created_at + absolute_timeout()).saturating_sub(now);
+        SessionInfo {
+            id: id.to_string(),
+            user: self.user.clone(),
+            remote_addr: self.remote_addr.clone(),
+            user_agent: self.user_agent.clone(),
+            created_at: self.created_at,
+            last_seen: self.last_seen,
+            idle_expires_in,
+            absolute_expires_in,
+            expires_in: idle_expires_in.min(absolute_expires_in),
+        }
+    }
+}
+
+/// Drops sessions that timed out. The sudo password stays: rollouts, certificate renewals and
+/// config reloads still need it while nobody is looking, so only logging out clears it.
+fn prune_expired(store: &mut HashMap<String, SessionRecord>, now: u64) {
+    store.retain(|_, record| !record.is_expired(now));
+}
+
+/// Registers a new server-side session and binds it to the caller's cookie
+pub fn start_session(session: &Session, req: &HttpRequest) -> Result<(), Error> {
+    let now = now_secs();
+    let id = generate_session_id();
+    let record = SessionRecord {
+        user: current_username(),
+        remote_addr: req.connection_info().realip_remote_addr().map(|s| s.to_string()),
+        user_agent: req
+            .headers()
+            .get("User-Agent")
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string()),
+        created_at: now,
+        last_seen: now,
+    };
+
+    if let Ok(mut store) = SESSION_STORE.lock() {
+        store.insert(id.clone(), record);
+    }
+
+    // Issue a fresh cookie so a pre-login session id can't be reused
+    session.renew();
+    session.insert(SESSION_ID_KEY, id)?;
+    Ok(())
+}
+
+/// Request extension marking a request that arrived over an agent's mutual TLS listener. The
+/// client certificate already proved it comes from the fleet controller, so it needs no session.
+#[derive(Clone, Copy, Debug)]
+pub struct ControllerPeer;
+
+pub fn is_controller_peer(req: &HttpRequest) -> bool {
+    req.extensions().contains::<ControllerPeer>()
+}
+
+/// Looks up the caller's session, refreshing its idle timer. Expired or revoked sessions are purged.
+pub fn current_session(session: &Session) -> Result<Option<SessionInfo>, Error> {
+    let id = match session.get::<String>(SESSION_ID_KEY)? {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+
+    let now = now_secs();
+    let info = match SESSION_STORE.lock() {
+        Ok(mut store) => {
+            prune_expired(&mut store, now);
+            store.get_mut(&id).map(|record| {
+                record.last_seen = now;
+                record.to_info(&id, now)
+            })
+        }
+        Err(_) => None,
+    };
+
+    if info.is_none() {
+        session.purge();
+    }
+    Ok(info)
+}
+
+pub fn is_authenticated(req: &HttpRequest, session: &Session) -> Result<bool, Error> {
+    if is_controller_peer(req) {
+        return Ok(true);
+    }
+    Ok(current_session(session)?.is_some())
+}
+
+/// The id of the server-side session the caller's cookie refers to, whether or not it is still live
+pub fn session_id(session: &Session) -> Result<Option<String>, Error> {
+    Ok(session.get::<String>(SESSION_ID_KEY)?)
+}
+
+/// Whether the session is still live. Unlike `current_session` this doesn't refresh its idle timer,
+/// so long-running responses can recheck it without keeping the session alive themselves.
+pub fn is_session_active(id: &str) -> bool {
+    match SESSION_STORE.lock() {
+        Ok(mut store) => {
+            prune_expired(&mut store, now_secs());
+            store.contains_key(id)
+        }
+        Err(_) => false,
+    }
 }
 
-pub async fn login(session: Session, req: web::Json<LoginRequest>) -> Result<HttpResponse, Error> {
+pub fn list_active_sessions() -> Vec<SessionInfo> {
+    let now = now_secs();
+    match SESSION_STORE.lock() {
+        Ok(mut store) => {
+            prune_expired(&mut store, now);
+            let mut sessions: Vec<SessionInfo> = store
+                .iter()
+                .map(|(id, record)| record.to_info(id, now))
+                .collect();
+            sessions.sort_by_key(|s| s.created_at);
+            sessions
+        }
+        Err(_) => vec![],
+    }
+}
+
+/// Removes a session from the store. Returns false if no such session exists.
+pub fn revoke_session_id(id: &str) -> bool {
+    match SESSION_STORE.lock() {
+        Ok(mut store) => {
+            let removed = store.remove(id).is_some();
+            prune_expired(&mut store, now_secs());
+            removed
+        }
+        Err(_) => false,
+    }
+}
+
+pub async fn login(session: Session, http_req: HttpRequest, req: web::Json<LoginRequest>) -> Result<HttpResponse, Error> {
     // Test the sudo password by running a simple command
     let mut child = Command::new("sudo")
         .arg("-S")
@@ -40,27 +254,98 @@ pub async fn login(session: Session, req: web::Json<LoginRequest>) -> Result<Htt
         .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
 
     if output.status.success() {
-        // Store the password for future use
-        if let Ok(mut store) = PASSWORD_STORE.lock() {
-            store.insert("sudo_password".to_string(), req.password.clone());
-        }
-        
-        session.insert("logged_in", true)?;
+        start_session(&session, &http_req)?;
+        // Store the password for future use, once the login can no longer fail
+        store_password(&req.password);
         Ok(HttpResponse::Ok().finish())
     } else {
         Ok(HttpResponse::Unauthorized().finish())
     }
 }
 
-pub async fn authenticated(session: Session) -> Result<HttpResponse, Error> {
-    if let Some(logged_in) = session.get::<bool>("logged_in")? {
-        if logged_in {
-            return Ok(HttpResponse::Ok().finish());
-        }
+pub async fn logout(session: Session) -> Result<HttpResponse, Error> {
+    if let Some(id) = session.get::<String>(SESSION_ID_KEY)? {
+        revoke_session_id(&id);
+    }
+    session.purge();
+    if list_active_sessions().is_empty() {
+        // Nobody is logged in any more, so there is no reason to keep the sudo password around
+        clear_stored_password();
+    }
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true
+    })))
+}
+
+pub async fn authenticated(req: HttpRequest, session: Session) -> Result<HttpResponse, Error> {
+    if is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Ok().finish());
     }
     Ok(HttpResponse::Unauthorized().finish())
 }
 
+pub async fn list_sessions(req: HttpRequest, session: Session) -> Result<HttpResponse, Error> {
+    // The fleet controller has no session of its own, so none of the listed ones is current
+    let current_id = if is_controller_peer(&req) {
+        None
+    } else {
+        match current_session(&session)? {
+            Some(current) => Some(current.id),
+            None => {
+                return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+                    "error": "Authentication required"
+                })))
+            }
+        }
+    };
+
+    let sessions: Vec<serde_json::Value> = list_active_sessions()
+        .into_iter()
+        .map(|info| {
+            let is_current = current_id.as_deref() == Some(info.id.as_str());
+            let mut value = serde_json::to_value(info).unwrap_or_default();
+            value["current"] = serde_json::json!(is_current);
+            value
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "sessions": sessions
+    })))
+}
+
+pub async fn revoke_session(req: HttpRequest, session: Session, path: web::Path<String>) -> Result<HttpResponse, Error> {
+    if !is_authenticated(&req, &session)? {
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Authentication required"
+        })));
+    }
+
+    let id = path.into_inner();
+    if revoke_session_id(&id) {
+        Ok(HttpResponse::Ok().json(serde_json::json!({
+            "success": true
+        })))
+    } else {
+        Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "success": false,
+            "error": "Session not found"
+        })))
+    }
+}
+
+pub fn store_password(password: &str) {
+    if let Ok(mut store) = PASSWORD_STORE.lock() {
+        store.insert("sudo_password".to_string(), password.to_string());
+    }
+}
+
+pub fn clear_stored_password() {
+    if let Ok(mut store) = PASSWORD_STORE.lock() {
+        store.remove("sudo_password");
+    }
+}
+
 pub fn get_stored_password() -> Option<String> {
     if let Ok(store) = PASSWORD_STORE.lock() {
         store.get("sudo_password").cloned()
@@ -68,3 +353,69 @@ pub fn get_stored_password() -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(created_at: u64, last_seen: u64) -> SessionRecord {
+        SessionRecord {
+            user: "admin".to_string(),
+            remote_addr: None,
+            user_agent: None,
+            created_at,
+            last_seen,
+        }
+    }
+
+    #[test]
+    fn sessions_expire_when_idle_or_too_old() {
+        let start = 1_000_000;
+        let fresh = record(start, start);
+        assert!(!fresh.is_expired(start + DEFAULT_IDLE_TIMEOUT - 1));
+        assert!(fresh.is_expired(start + DEFAULT_IDLE_TIMEOUT));
+
+        // Activity keeps a session alive, but only up to the absolute timeout
+        let busy = record(start, start + DEFAULT_ABSOLUTE_TIMEOUT - 1);
+        assert!(!busy.is_expired(start + DEFAULT_ABSOLUTE_TIMEOUT - 1));
+        assert!(busy.is_expired(start + DEFAULT_ABSOLUTE_TIMEOUT));
+
+        let info = fresh.to_info("id", start + 60);
+        assert_eq!(info.idle_expires_in, DEFAULT_IDLE_TIMEOUT - 60);
+        assert_eq!(info.absolute_expires_in, DEFAULT_ABSOLUTE_TIMEOUT - 60);
+        assert_eq!(info.expires_in, DEFAULT_IDLE_TIMEOUT - 60);
+
+        let info = busy.to_info("id", start + DEFAULT_ABSOLUTE_TIMEOUT - 10);
+        assert_eq!(info.expires_in, 10);
+    }
+
+    #[test]
+    fn pruning_drops_only_expired_sessions_and_keeps_the_password() {
+        let now = 1_000_000;
+        let mut store = HashMap::new();
+        store.insert("idle".to_string(), record(now - 2 * DEFAULT_IDLE_TIMEOUT, now - DEFAULT_IDLE_TIMEOUT));
+        store.insert("old".to_string(), record(now - DEFAULT_ABSOLUTE_TIMEOUT, now));
+        store.insert("live".to_string(), record(now - 60, now - 60));
+
+        store_password("secret");
+        prune_expired(&mut store, now);
+        assert_eq!(store.keys().collect::<Vec<_>>(), vec!["live"]);
+
+        prune_expired(&mut store, now + DEFAULT_IDLE_TIMEOUT);
+        assert!(store.is_empty());
+        assert_eq!(get_stored_password().as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn revoked_sessions_are_gone() {
+        let now = now_secs();
+        let id = generate_session_id();
+        SESSION_STORE.lock().unwrap().insert(id.clone(), record(now, now));
+        assert!(is_session_active(&id));
+        assert!(list_active_sessions().iter().any(|s| s.id == id));
+
+        assert!(revoke_session_id(&id));
+        assert!(!is_session_active(&id));
+        assert!(!revoke_session_id(&id));
+    }
+}
@@ -1,9 +1,18 @@
-
-use sysinfo::System;
+use regex::Regex;
 use std::process::{Command, Stdio};
 use std::env::consts::OS;
 use std::io::Write;
+use std::sync::{Mutex, MutexGuard};
+use sysinfo::System;
 use crate::auth::get_stored_password;
+use crate::config_watcher;
+use crate::health;
+use crate::instances::{self, NginxInstance};
+
+lazy_static::lazy_static! {
+    static ref CONF_PATH: Regex = Regex::new(r"/[^ :]+\.conf").unwrap();
+    static ref CONFIG_CHANGES: Mutex<()> = Mutex::new(());
+}
 
 fn execute_sudo_command(args: Vec<&str>) -> Result<std::process::Output, String> {
     // For Tauri (desktop mode), let sudo prompt for password directly
@@ -36,13 +45,14 @@ fn execute_sudo_command_with_stored_password(args: Vec<&str>) -> Result<std::pro
 }
 
 #[tauri::command]
-pub(crate) fn restart_nginx() -> Result<String, String> {
+pub(crate) fn restart_nginx(instance: Option<String>) -> Result<String, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
     let output = match OS {
-        "linux" => execute_sudo_command(vec!["systemctl", "restart", "nginx"])?,
+        "linux" => execute_sudo_command(vec!["systemctl", "restart", &instance.unit])?,
         "macos" => Command::new("brew")
             .arg("services")
             .arg("restart")
-            .arg("nginx")
+            .arg(instance.service_name())
             .output()
             .map_err(|e| e.to_string())?,
         _ => return Err("Unsupported OS".into()),
@@ -59,13 +69,14 @@ pub(crate) fn restart_nginx() -> Result<String, String> {
 }
 
 #[tauri::command]
-pub(crate) fn start_nginx() -> Result<String, String> {
+pub(crate) fn start_nginx(instance: Option<String>) -> Result<String, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
     let output = match OS {
-        "linux" => execute_sudo_command(vec!["systemctl", "start", "nginx"])?,
+        "linux" => execute_sudo_command(vec!["systemctl", "start", &instance.unit])?,
         "macos" => Command::new("brew")
             .arg("services")
             .arg("start")
-            .arg("nginx")
+            .arg(instance.service_name())
             .output()
             .map_err(|e| e.to_string())?,
         _ => return Err("Unsupported OS".into()),
@@ -82,13 +93,14 @@ pub(crate) fn start_nginx() -> Result<String, String> {
 }
 
 #[tauri::command]
-pub(crate) fn stop_nginx() -> Result<String, String> {
+pub(crate) fn stop_nginx(instance: Option<String>) -> Result<String, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
     let output = match OS {
-        "linux" => execute_sudo_command(vec!["systemctl", "stop", "nginx"])?,
+        "linux" => execute_sudo_command(vec!["systemctl", "stop", &instance.unit])?,
         "macos" => Command::new("brew")
             .arg("services")
             .arg("stop")
-            .arg("nginx")
+            .arg(instance.service_name())
             .output()
             .map_err(|e| e.to_string())?,
         _ => return Err("Unsupported OS".into()),
@@ -104,17 +116,54 @@ pub(crate) fn stop_nginx() -> Result<String, String> {
     }
 }
 
+/// Held while a config change is written, validated and reloaded. Site edits, deployments, ACME
+/// challenge includes and formatting all take it, so none of them reloads half of another.
+pub fn lock_config_changes() -> MutexGuard<'static, ()> {
+    CONFIG_CHANGES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Tests the config, then has the running master re-read it. Unlike a restart, in-flight requests
+/// finish on the old workers.
+pub fn reload_nginx(instance: &NginxInstance) -> Result<String, String> {
+    let check = config_watcher::validate_config(instance, None);
+    if !check.valid {
+        return Err(check.message);
+    }
+
+    let output = instance
+        .command()
+        .arg("-s")
+        .arg("reload")
+        .output()
+        .map_err(|e| format!("Failed to execute command: {}", e))?;
+
+    if output.status.success() {
+        Ok("Nginx reloaded successfully".to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(format!("Failed to reload Nginx: {}", stderr))
+    }
+}
+
 #[tauri::command]
-pub(crate) fn get_nginx_conf_path() -> Result<String, String> {
-    // Run the `nginx -t` command to test the configuration and extract the path to the config file
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg("nginx -t 2>&1 | grep -Eo '(/[^ :]+\\.conf)' | head -n 1")
+pub(crate) fn get_nginx_conf_path(instance: Option<String>) -> Result<String, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+    nginx_conf_path(&instance)
+}
+
+/// Runs `nginx -t` for the instance and extracts the path of the config file it tested
+pub fn nginx_conf_path(instance: &NginxInstance) -> Result<String, String> {
+    let output = instance
+        .command()
+        .arg("-t")
         .output()
         .map_err(|e| format!("Failed to execute command: {}", e))?;
 
-    // Convert the output to a string and trim any whitespace
-    let output_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // nginx reports the file it tested on stderr
+    let output_str = CONF_PATH
+        .find(&String::from_utf8_lossy(&output.stderr))
+        .map(|m| m.as_str().to_string())
+        .unwrap_or_default();
 
     // Check if the output is empty, indicating that no configuration file path was found
     if output_str.is_empty() {
@@ -152,46 +201,25 @@ pub(crate) fn open_file(file_path: String) -> Result<(), String> {
 }
 
 
+// CPU %, total and used memory, tasks, workers, transmitted and received bytes
+type SystemMetrics = (f32, u64, u64, usize, usize, u64, u64);
+
 #[tauri::command]
-pub(crate) fn get_system_metrics() -> Result<(f32, u64, u64, usize, usize, u64, u64), String> {
-    let mut sys = System::new_all();
-
-    // Refresh system to get updated information
-    sys.refresh_all();
-
-    // Initialize variables to accumulate nginx-specific metrics
-    let mut cpu_usage = 0.0;
-    let mut used_memory = 0;
-    let mut worker_count = 0;
-
-    // Get the number of nginx processes (tasks/workers) and calculate their CPU and memory usage
-    let num_tasks = sys.processes()
-        .values()
-        .filter(|process| {
-            let name = process.name().to_string_lossy().to_ascii_lowercase();
-            if name.contains("nginx") {
-                cpu_usage += process.cpu_usage();
-                used_memory += process.memory();
-
-                // Check if it's a worker process
-                if name.contains("worker process") {
-                    worker_count += 1;
-                }
-
-                true
-            } else {
-                false
-            }
-        })
-        .count();
+pub(crate) fn get_system_metrics(instance: Option<String>) -> Result<SystemMetrics, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+
+    // Only the processes under this instance's master count towards its usage
+    let metrics = health::process_metrics(&instance);
 
     // Get total memory for the system
+    let mut sys = System::new();
+    sys.refresh_memory();
     let total_memory = sys.total_memory();
 
     // Get bandwidth metrics for nginx by filtering traffic on port 80 and 443
     let (tx_bytes, rx_bytes) = get_nginx_bandwidth().unwrap_or((0, 0));
 
-    Ok((cpu_usage, total_memory, used_memory, num_tasks, worker_count, tx_bytes, rx_bytes))
+    Ok((metrics.cpu_usage, total_memory, metrics.memory, metrics.processes, metrics.workers, tx_bytes, rx_bytes))
 }
 
 fn get_nginx_bandwidth() -> Result<(u64, u64), String> {
@@ -2,9 +2,13 @@ use std::process::Command;
 use std::fs::{File, create_dir_all};
 use std::io::Write;
 
+use crate::instances::{self, NginxInstance};
+
 #[tauri::command]
-pub(crate) fn get_nginx_version() -> Result<String, String> {
-    let output = Command::new("nginx")
+pub(crate) fn get_nginx_version(instance: Option<String>) -> Result<String, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+    let output = instance
+        .command()
         .arg("-V")
         .output()
         .map_err(|e| format!("Failed to execute command: {}", e))?;
@@ -17,15 +21,28 @@ pub(crate) fn get_nginx_version() -> Result<String, String> {
     }
 }
 
+/// The `ExecStart` line for the instance's unit; systemd wants an absolute binary path
+fn exec_start(instance: &NginxInstance, custom_args: &str) -> String {
+    let binary = if instance.binary.starts_with('/') { instance.binary.as_str() } else { "/usr/sbin/nginx" };
+    let pid_file = instance
+        .pid_file
+        .clone()
+        .unwrap_or_else(|| format!("/run/{}.pid", instance.service_name()));
+    let prefix = instance.prefix.as_ref().map(|prefix| format!(" -p {}", prefix)).unwrap_or_default();
+    let config = instance.config_path().unwrap_or_else(|| "/etc/nginx/nginx.conf".to_string());
+    format!(
+        "ExecStart={}{} -g 'pid {}; error_log stderr; worker_processes auto;' -c {} {}",
+        binary, prefix, pid_file, config, custom_args
+    )
+}
+
 #[tauri::command]
-pub(crate) fn modify_nginx_service(custom_args: &str) -> Result<(), String> {
-    let content = format!(
-        "[Service]\nExecStart=\nExecStart=/usr/sbin/nginx -g 'pid /run/nginx.pid; error_log stderr; worker_processes auto;' -c /etc/nginx/nginx.conf {}\n",
-        custom_args
-    );
+pub(crate) fn modify_nginx_service(instance: Option<String>, custom_args: &str) -> Result<(), String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+    let content = format!("[Service]\nExecStart=\n{}\n", exec_start(&instance, custom_args));
 
-    let dir_path = "/etc/systemd/system/nginx.service.d";
-    if let Err(e) = create_dir_all(dir_path) {
+    let dir_path = format!("/etc/systemd/system/{}.d", instance.unit);
+    if let Err(e) = create_dir_all(&dir_path) {
         return Err(format!("Failed to create directory {}: {}", dir_path, e));
     }
 
@@ -43,7 +60,7 @@ pub(crate) fn modify_nginx_service(custom_args: &str) -> Result<(), String> {
 
     std::process::Command::new("systemctl")
         .arg("restart")
-        .arg("nginx")
+        .arg(&instance.unit)
         .status()
         .map_err(|e| format!("Failed to restart nginx: {}", e))?;
 
@@ -51,7 +68,9 @@ pub(crate) fn modify_nginx_service(custom_args: &str) -> Result<(), String> {
 }
 
 #[tauri::command]
-pub(crate) fn reload_and_restart_nginx_service() -> Result<(), String> {
+pub(crate) fn reload_and_restart_nginx_service(instance: Option<String>) -> Result<(), String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+
     // Reload systemd
     Command::new("systemctl")
         .arg("daemon-reload")
@@ -61,9 +80,9 @@ pub(crate) fn reload_and_restart_nginx_service() -> Result<(), String> {
     // Restart Nginx
     Command::new("systemctl")
         .arg("restart")
-        .arg("nginx")
+        .arg(&instance.unit)
         .status()
         .map_err(|e| format!("Failed to restart Nginx: {}", e))?;
 
     Ok(())
-}
\ No newline at end of file
+}
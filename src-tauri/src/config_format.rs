@@ -0,0 +1,444 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::commands;
+use crate::config_watcher;
+use crate::instances::{self, NginxInstance};
+use crate::nginx_config::{self, Directive, Token};
+
+const INDENT: &str = "    ";
+
+// Lines of unchanged context around each diff hunk
+const DIFF_CONTEXT: usize = 3;
+
+// Above this many line pairs the diff falls back to replacing the whole file instead of an exact LCS
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FormatMode {
+    /// Only report which files aren't formatted, for CI
+    Check,
+    /// Return a unified diff of what would change
+    #[default]
+    Diff,
+    /// Rewrite the files, keeping the originals if `nginx -t` rejects the result
+    Apply,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct FormatRequest {
+    #[serde(default)]
+    pub mode: FormatMode,
+    /// Limit to these files; every file in the parsed config when empty
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FormattedFile {
+    pub file: String,
+    pub changed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FormatReport {
+    pub mode: FormatMode,
+    /// True when every file is already formatted (check), or was written (apply)
+    pub formatted: bool,
+    pub files: Vec<FormattedFile>,
+}
+
+/// Whether a word has to be quoted to survive re-tokenizing. Braces in `${var}` don't count.
+fn needs_quotes(word: &str) -> bool {
+    if word.is_empty() || word.starts_with('#') {
+        return true;
+    }
+    let mut chars = word.chars().peekable();
+    let mut previous = None;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '{' if previous == Some('$') => {
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() || matches!(c, ';' | '{' | '}' | '"' | '\'') => return true,
+            _ => {}
+        }
+        previous = Some(c);
+    }
+    false
+}
+
+fn quote(word: &str) -> String {
+    if !needs_quotes(word) {
+        return word.to_string();
+    }
+    // Single quotes save escaping when the word contains double quotes but no single ones
+    let quote_char = if word.contains('"') && !word.contains('\'') { '\'' } else { '"' };
+    // The tokenizer keeps backslash escapes other than the quote itself, so only that needs escaping
+    let mut quoted = String::from(quote_char);
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                quoted.push('\\');
+                if let Some(escaped) = chars.next() {
+                    quoted.push(escaped);
+                }
+            }
+            c if c == quote_char => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push(quote_char);
+    quoted
+}
+
+/// Pretty-prints config text: one directive per line, four-space indentation, minimal quoting,
+/// comments kept where they were and runs of blank lines collapsed to one
+pub fn format_config_str(content: &str, file: &str) -> Result<String, String> {
+    let tokens = nginx_config::tokenize_with_comments(content, file)
+        .map_err(|e| format!("{}:{}: {}", e.file, e.line.unwrap_or(0), e.message))?;
+
+    let mut output: Vec<String> = Vec::new();
+    let mut depth = 0usize;
+    let mut words: Vec<String> = Vec::new();
+    // Comments that appeared in the middle of a directive, attached to the end of its line
+    let mut pending_comments: Vec<String> = Vec::new();
+    // Source line the last emitted output line ended on, to keep blank lines and trailing comments
+    let mut last_line: Option<usize> = None;
+
+    let indent = |depth: usize| INDENT.repeat(depth);
+    let blank_line_before = |output: &mut Vec<String>, line: usize, last_line: Option<usize>| {
+        if last_line.is_some_and(|last| line > last + 1) && output.last().is_some_and(|l| !l.is_empty() && !l.ends_with('{')) {
+            output.push(String::new());
+        }
+    };
+
+    for (token, line) in &tokens {
+        let line = *line;
+        match token {
+            Token::Word(word) => {
+                if words.is_empty() {
+                    blank_line_before(&mut output, line, last_line);
+                    if let Some(name) = word.strip_suffix("_block").filter(|name| name.ends_with("_by_lua")) {
+                        return Err(format!("{}:{}: {}_block contains Lua, which can't be formatted", file, line, name));
+                    }
+                }
+                // The first word is quoted too: `map` and `geo` entries can have empty or quoted keys
+                words.push(quote(word));
+            }
+            Token::Semicolon | Token::OpenBrace => {
+                let mut text = format!("{}{}", indent(depth), words.join(" "));
+                text.push_str(if *token == Token::Semicolon { ";" } else { " {" });
+                for comment in pending_comments.drain(..) {
+                    text.push_str(&format!(" #{}", comment.trim_end()));
+                }
+                output.push(text);
+                words.clear();
+                if *token == Token::OpenBrace {
+                    depth += 1;
+                }
+                last_line = Some(line);
+            }
+            Token::CloseBrace => {
+                if !words.is_empty() {
+                    return Err(format!("{}:{}: directive \"{}\" is not terminated by \";\"", file, line, words[0]));
+                }
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| format!("{}:{}: unexpected \"}}\"", file, line))?;
+                output.push(format!("{}}}", indent(depth)));
+                last_line = Some(line);
+            }
+            Token::Comment(comment) => {
+                let comment = comment.trim_end();
+                if !words.is_empty() {
+                    pending_comments.push(comment.to_string());
+                    continue;
+                }
+                // A comment on the same line as the end of the previous directive stays beside it
+                if last_line == Some(line) {
+                    if let Some(previous) = output.last_mut() {
+                        previous.push_str(&format!(" #{}", comment));
+                        continue;
+                    }
+                }
+                blank_line_before(&mut output, line, last_line);
+                output.push(format!("{}#{}", indent(depth), comment));
+                last_line = Some(line);
+            }
+        }
+    }
+
+    if !words.is_empty() {
+        return Err(format!("{}: unexpected end of file in directive \"{}\"", file, words[0]));
+    }
+    if depth > 0 {
+        return Err(format!("{}: unexpected end of file, expecting \"}}\"", file));
+    }
+
+    let mut formatted = output.join("\n");
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+    Ok(formatted)
+}
+
+fn same_structure(a: &[Directive], b: &[Directive]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| {
+            a.name == b.name
+                && a.args == b.args
+                && a.block.is_some() == b.block.is_some()
+                && same_structure(a.children(), b.children())
+        })
+}
+
+/// Formats and then re-parses, refusing output that doesn't mean exactly what the input did
+pub fn format_checked(content: &str, file: &str) -> Result<String, String> {
+    let formatted = format_config_str(content, file)?;
+    let before = nginx_config::parse_config_str(content, file).map_err(|e| e.message)?;
+    let after = nginx_config::parse_config_str(&formatted, file).map_err(|e| e.message)?;
+    if !same_structure(&before, &after) {
+        return Err(format!("{}: formatting would change the meaning of the config; left untouched", file));
+    }
+    Ok(formatted)
+}
+
+enum Edit<'a> {
+    Keep(&'a str),
+    Remove(&'a str),
+    Add(&'a str),
+}
+
+fn line_edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        return old.iter().map(|line| Edit::Remove(line)).chain(new.iter().map(|line| Edit::Add(line))).collect();
+    }
+
+    // Longest common subsequence lengths of the suffixes
+    let mut lengths = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut edits = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Keep(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            edits.push(Edit::Remove(old[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Add(new[j]));
+            j += 1;
+        }
+    }
+    edits
+}
+
+/// A unified diff between two versions of `file`, empty when they're equal
+pub fn unified_diff(file: &str, old: &str, new: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let edits = line_edits(&old_lines, &new_lines);
+
+    let mut diff = format!("--- {}\n+++ {}\n", file, file);
+    let changed: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Keep(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut start = 0;
+    while start < changed.len() {
+        // Grow the hunk while the next change is within two contexts' reach
+        let mut end = start;
+        while end + 1 < changed.len() && changed[end + 1] - changed[end] <= DIFF_CONTEXT * 2 {
+            end += 1;
+        }
+        let from = changed[start].saturating_sub(DIFF_CONTEXT);
+        let to = (changed[end] + DIFF_CONTEXT + 1).min(edits.len());
+
+        // Line numbers where the hunk starts in each version
+        let (mut old_line, mut new_line) = (1, 1);
+        for edit in &edits[..from] {
+            match edit {
+                Edit::Keep(_) => {
+                    old_line += 1;
+                    new_line += 1;
+                }
+                Edit::Remove(_) => old_line += 1,
+                Edit::Add(_) => new_line += 1,
+            }
+        }
+        let old_count = edits[from..to].iter().filter(|edit| !matches!(edit, Edit::Add(_))).count();
+        let new_count = edits[from..to].iter().filter(|edit| !matches!(edit, Edit::Remove(_))).count();
+        diff.push_str(&format!("@@ -{},{} +{},{} @@\n", old_line, old_count, new_line, new_count));
+        for edit in &edits[from..to] {
+            let (prefix, line) = match edit {
+                Edit::Keep(line) => (' ', line),
+                Edit::Remove(line) => ('-', line),
+                Edit::Add(line) => ('+', line),
+            };
+            diff.push(prefix);
+            diff.push_str(line);
+            diff.push('\n');
+        }
+        start = end + 1;
+    }
+    diff
+}
+
+/// Formats the requested config files (all of them by default) in the given mode
+pub fn format_config(instance: &NginxInstance, request: &FormatRequest) -> Result<FormatReport, String> {
+    // Files are rewritten from what was read here, so nothing else may change them in between
+    let _guard = (request.mode == FormatMode::Apply).then(commands::lock_config_changes);
+    let root = nginx_config::find_main_config(instance).ok_or("Could not find nginx.conf")?;
+    let known = nginx_config::parse_config(&root).file_paths();
+    let files: Vec<String> = if request.files.is_empty() {
+        known
+    } else {
+        // Only files nginx actually loads, so this can't be used to rewrite arbitrary paths
+        if let Some(unknown) = request.files.iter().find(|file| !known.contains(file)) {
+            return Err(format!("{} is not part of the nginx config", unknown));
+        }
+        request.files.clone()
+    };
+
+    let mut results = Vec::new();
+    let mut rewrites: Vec<(String, String, String)> = Vec::new();
+    for file in files {
+        let original = match fs::read_to_string(&file) {
+            Ok(content) => content,
+            Err(e) => {
+                results.push(FormattedFile { file: file.clone(), changed: false, diff: None, error: Some(format!("Failed to read {}: {}", file, e)) });
+                continue;
+            }
+        };
+        match format_checked(&original, &file) {
+            Ok(formatted) => {
+                let changed = formatted != original;
+                let diff = (changed && request.mode == FormatMode::Diff).then(|| unified_diff(&file, &original, &formatted));
+                if changed && request.mode == FormatMode::Apply {
+                    rewrites.push((file.clone(), original, formatted));
+                }
+                results.push(FormattedFile { file, changed, diff, error: None });
+            }
+            Err(e) => results.push(FormattedFile { file, changed: false, diff: None, error: Some(e) }),
+        }
+    }
+
+    if !rewrites.is_empty() {
+        let mut written: Vec<&(String, String, String)> = Vec::new();
+        let mut failure = None;
+        for rewrite in &rewrites {
+            match fs::write(&rewrite.0, &rewrite.2) {
+                Ok(()) => written.push(rewrite),
+                Err(e) => {
+                    failure = Some(format!("Failed to write {}: {}", rewrite.0, e));
+                    break;
+                }
+            }
+        }
+        if failure.is_none() {
+            let check = config_watcher::validate_config(instance, None);
+            if !check.valid {
+                failure = Some(check.message);
+            }
+        }
+        if let Some(e) = failure {
+            for (file, original, _) in written {
+                let _ = fs::write(file, original);
+            }
+            return Err(format!("Formatting rolled back: {}", e));
+        }
+    }
+
+    let formatted = results.iter().all(|result| result.error.is_none() && (!result.changed || request.mode == FormatMode::Apply));
+    Ok(FormatReport { mode: request.mode, formatted, files: results })
+}
+
+#[tauri::command]
+pub(crate) fn format_nginx_config(instance: Option<String>, request: FormatRequest) -> Result<FormatReport, String> {
+    format_config(&instances::get_instance(instance.as_deref())?, &request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Formats, checks the result parses to the same config, and that formatting again changes nothing
+    fn round_trip(content: &str) -> String {
+        let formatted = format_checked(content, "test.conf").unwrap();
+        assert_eq!(format_checked(&formatted, "test.conf").unwrap(), formatted);
+        formatted
+    }
+
+    #[test]
+    fn websocket_map_keeps_its_empty_key() {
+        let formatted = round_trip("map $http_upgrade $connection_upgrade { default upgrade; '' close; }\n");
+        assert_eq!(
+            formatted,
+            "map $http_upgrade $connection_upgrade {\n    default upgrade;\n    \"\" close;\n}\n"
+        );
+    }
+
+    #[test]
+    fn quoted_first_words_stay_quoted() {
+        let formatted = round_trip(
+            r#"map $http_user_agent $is_bot {
+    "~*Googlebot Mobile" 1;
+    '#hash' 1;
+    "semi;colon" 1;
+    default 0;
+}
+geo $trusted { '' 0; 10.0.0.0/8 1; }
+split_clients "${remote_addr}AAA" $variant { 50% "a b"; * b; }
+"#,
+        );
+        assert!(formatted.contains("    \"~*Googlebot Mobile\" 1;\n"), "{}", formatted);
+        assert!(formatted.contains("    \"#hash\" 1;\n"), "{}", formatted);
+        assert!(formatted.contains("    \"semi;colon\" 1;\n"), "{}", formatted);
+        assert!(formatted.contains("    \"\" 0;\n"), "{}", formatted);
+        assert!(formatted.contains("split_clients ${remote_addr}AAA $variant {\n"), "{}", formatted);
+        assert!(formatted.contains("    50% \"a b\";\n"), "{}", formatted);
+    }
+
+    #[test]
+    fn plain_directives_are_left_unquoted() {
+        let formatted = round_trip("http{server{listen 80;location /{return 200 \"ok\";}}}");
+        assert_eq!(
+            formatted,
+            "http {\n    server {\n        listen 80;\n        location / {\n            return 200 ok;\n        }\n    }\n}\n"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::event_bus::{self, RustinxEvent};
+#[cfg(target_os = "linux")]
+use crate::fs_watch::DirWatcher;
+use crate::health;
+use crate::instances::NginxInstance;
+use crate::nginx_config::{self, ParsedConfig};
+
+// Editors and deploy tools touch files several times per save; wait for things to settle
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Longest a pending change waits for quiet, so a busy file in a watched directory can't hold it off
+const MAX_DEBOUNCE: Duration = Duration::from_secs(5);
+
+// How long to wait before looking for nginx.conf again when it can't be found
+const CONFIG_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[cfg(not(target_os = "linux"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Outcome of an `nginx -t` run, published as `nginx_config_check`
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigCheckResult {
+    pub instance: String,
+    pub valid: bool,
+    pub message: String,
+    /// The file whose change triggered this check, `None` for the initial check
+    pub changed_file: Option<String>,
+    pub error_file: Option<String>,
+    pub error_line: Option<usize>,
+    pub checked_at: u64,
+}
+
+lazy_static::lazy_static! {
+    static ref NGINX_ERROR_LOCATION: Regex = Regex::new(r" in (\S+):(\d+)\s*$").unwrap();
+}
+
+/// Extracts the file:line nginx blames in `nginx -t` output
+pub fn parse_error_location(stderr: &str) -> Option<(String, usize)> {
+    stderr
+        .lines()
+        .filter(|line| {
+            line.contains("[emerg]") || line.contains("[alert]") || line.contains("[crit]") || line.contains("[error]")
+        })
+        .find_map(|line| {
+            let captures = NGINX_ERROR_LOCATION.captures(line)?;
+            let line_number = captures[2].parse::<usize>().ok()?;
+            Some((captures[1].to_string(), line_number))
+        })
+}
+
+pub fn validate_config(instance: &NginxInstance, changed_file: Option<String>) -> ConfigCheckResult {
+    let (valid, message, location) = match instance.command().arg("-t").output() {
+        Ok(output) if output.status.success() => (true, "Nginx configuration is valid.".to_string(), None),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            let location = parse_error_location(&stderr);
+            (false, format!("Nginx configuration error: {}", stderr), location)
+        }
+        Err(e) => (false, format!("Nginx configuration error: failed to run nginx -t: {}", e), None),
+    };
+
+    let (error_file, error_line) = match location {
+        Some((file, line)) => (Some(file), Some(line)),
+        None => (None, None),
+    };
+
+    ConfigCheckResult {
+        instance: instance.id.clone(),
+        valid,
+        message,
+        changed_file,
+        error_file,
+        error_line,
+        checked_at: health::now_secs(),
+    }
+}
+
+fn publish_check(instance: &NginxInstance, changed_file: Option<String>) {
+    let result = validate_config(instance, changed_file);
+    health::record_config_check(&instance.id, result.valid, &result.message);
+    event_bus::publish_for(&instance.id, RustinxEvent::ConfigCheck(result));
+}
+
+/// The files of the include tree and the directories holding them, symlinks as well as their targets
+struct WatchTargets {
+    files: HashSet<PathBuf>,
+    directories: HashSet<PathBuf>,
+    patterns: Vec<glob::Pattern>,
+}
+
+impl WatchTargets {
+    fn from_config(parsed: &ParsedConfig) -> Self {
+        let mut files: HashSet<PathBuf> = parsed.file_paths().into_iter().map(PathBuf::from).collect();
+        // sites-enabled/foo -> ../sites-available/foo: editing the target must trigger a check too
+        let targets: Vec<PathBuf> = files
+            .iter()
+            .filter_map(|f| std::fs::canonicalize(f).ok())
+            .filter(|target| !files.contains(target))
+            .collect();
+        files.extend(targets);
+        let mut directories: HashSet<PathBuf> = files
+            .iter()
+            .filter_map(|f| f.parent().map(Path::to_path_buf))
+            .collect();
+
+        // Glob includes such as sites-enabled/* pick up new files, so watch their directories too
+        let mut patterns = Vec::new();
+        for pattern in &parsed.include_patterns {
+            if !nginx_config::is_glob(pattern) {
+                continue;
+            }
+            if let Some(dir) = Path::new(pattern).parent() {
+                directories.insert(dir.to_path_buf());
+            }
+            if let Ok(compiled) = glob::Pattern::new(pattern) {
+                patterns.push(compiled);
+            }
+        }
+
+        WatchTargets { files, directories, patterns }
+    }
+
+    fn is_relevant(&self, path: &Path) -> bool {
+        self.files.contains(path) || self.patterns.iter().any(|p| p.matches_path(path))
+    }
+}
+
+/// Validates the instance's config once, then again every time a file in its include tree changes
+pub fn start_config_watcher(instance: NginxInstance) {
+    thread::spawn(move || {
+        publish_check(&instance, None);
+
+        loop {
+            let root = match nginx_config::find_main_config(&instance) {
+                Some(root) => root,
+                None => {
+                    thread::sleep(CONFIG_RETRY_INTERVAL);
+                    continue;
+                }
+            };
+
+            // Re-parse after every change so new includes are watched as well
+            let targets = WatchTargets::from_config(&nginx_config::parse_config(&root));
+            match wait_for_change(&targets) {
+                Some(changed) => publish_check(&instance, Some(changed.to_string_lossy().to_string())),
+                None => thread::sleep(CONFIG_RETRY_INTERVAL),
+            }
+        }
+    });
+}
+
+/// Blocks until a relevant file changes and stays quiet for `DEBOUNCE`; returns the first file that changed
+#[cfg(target_os = "linux")]
+fn wait_for_change(targets: &WatchTargets) -> Option<PathBuf> {
+    let directories: Vec<PathBuf> = targets.directories.iter().cloned().collect();
+    let watcher = match DirWatcher::new(&directories) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("Failed to watch the nginx config: {}", e);
+            return None;
+        }
+    };
+
+    let mut changed: Option<PathBuf> = None;
+    let mut deadline: Option<Instant> = None;
+    loop {
+        // Block until something happens, or only for the debounce window once a change is pending
+        let timeout = match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return changed;
+                }
+                Some(DEBOUNCE.min(left))
+            }
+            None => None,
+        };
+        let paths = watcher.wait(timeout);
+        if paths.is_empty() && changed.is_some() {
+            return changed;
+        }
+        if changed.is_none() {
+            changed = paths.into_iter().find(|path| targets.is_relevant(path));
+            deadline = changed.as_ref().map(|_| Instant::now() + MAX_DEBOUNCE);
+        }
+    }
+}
+
+/// Fallback for platforms without inotify: compare modification times and directory listings
+#[cfg(not(target_os = "linux"))]
+fn wait_for_change(targets: &WatchTargets) -> Option<PathBuf> {
+    use std::collections::HashMap;
+    use std::time::SystemTime;
+
+    fn snapshot(targets: &WatchTargets) -> HashMap<PathBuf, Option<SystemTime>> {
+        let mut state = HashMap::new();
+        for dir in &targets.directories {
+            if let Ok(entries) = std::fs::read_dir(dir) {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if targets.is_relevant(&path) {
+                        let modified = entry.metadata().and_then(|m| m.modified()).ok();
+                        state.insert(path, modified);
+                    }
+                }
+            }
+        }
+        for file in &targets.files {
+            state.entry(file.clone()).or_insert(None);
+        }
+        state
+    }
+
+    let before = snapshot(targets);
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let after = snapshot(targets);
+        if let Some(path) = after
+            .iter()
+            .find(|(path, modified)| before.get(*path) != Some(*modified))
+            .map(|(path, _)| path.clone())
+            .or_else(|| before.keys().find(|path| !after.contains_key(*path)).cloned())
+        {
+            // Let a burst of writes finish before validating
+            thread::sleep(DEBOUNCE);
+            return Some(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    /// A scratch directory under the system temp dir, removed again when the test ends
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rustinx-watch-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            // Canonical, so paths compare equal to the symlink targets the watcher resolves
+            TempDir(fs::canonicalize(path).unwrap())
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// nginx.conf including sites-enabled/*, with sites-enabled/app linked to sites-available/app
+    fn debian_layout(dir: &Path) -> PathBuf {
+        fs::create_dir_all(dir.join("sites-available")).unwrap();
+        fs::create_dir_all(dir.join("sites-enabled")).unwrap();
+        fs::write(dir.join("sites-available/app"), "server { listen 80; }\n").unwrap();
+        std::os::unix::fs::symlink("../sites-available/app", dir.join("sites-enabled/app")).unwrap();
+        let root = dir.join("nginx.conf");
+        fs::write(&root, format!("http {{\n    include {}/sites-enabled/*;\n}}\n", dir.display())).unwrap();
+        root
+    }
+
+    #[test]
+    fn symlinked_sites_are_watched_at_their_target() {
+        let dir = TempDir::new("symlink");
+        let root = debian_layout(&dir.0);
+
+        let targets = WatchTargets::from_config(&nginx_config::parse_config(&root.to_string_lossy()));
+        assert!(targets.is_relevant(&dir.0.join("sites-enabled/app")));
+        assert!(targets.is_relevant(&dir.0.join("sites-available/app")));
+        assert!(targets.directories.contains(&dir.0.join("sites-available")));
+        assert!(!targets.is_relevant(&dir.0.join("sites-available/other")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn constant_writes_in_a_watched_directory_dont_hold_off_the_check() {
+        let dir = TempDir::new("debounce");
+        let root = debian_layout(&dir.0);
+        let targets = WatchTargets::from_config(&nginx_config::parse_config(&root.to_string_lossy()));
+
+        let busy = dir.0.join("sites-available/access.log");
+        let site = dir.0.join("sites-available/app");
+        let done = Arc::new(AtomicBool::new(false));
+        let writing = done.clone();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            fs::write(&site, "server { listen 8080; }\n").unwrap();
+            // Far more often than DEBOUNCE, for longer than MAX_DEBOUNCE
+            let until = Instant::now() + MAX_DEBOUNCE + Duration::from_secs(3);
+            while Instant::now() < until && !writing.load(Ordering::Relaxed) {
+                fs::write(&busy, "GET /\n").unwrap();
+                thread::sleep(DEBOUNCE / 5);
+            }
+        });
+
+        let started = Instant::now();
+        let changed = wait_for_change(&targets);
+        let waited = started.elapsed();
+        done.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+        assert_eq!(changed, Some(dir.0.join("sites-available/app")));
+        assert!(waited < MAX_DEBOUNCE + Duration::from_secs(1), "waited {:?}", waited);
+    }
+}
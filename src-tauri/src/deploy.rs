@@ -0,0 +1,305 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::auth;
+use crate::commands;
+use crate::health;
+use crate::instances::NginxInstance;
+use crate::nginx_config;
+
+// Deployments kept for rollback per instance; older ones can no longer be undone
+const MAX_KEPT_DEPLOYMENTS: usize = 10;
+
+lazy_static::lazy_static! {
+    static ref DEPLOYMENTS: Mutex<HashMap<String, Vec<StoredDeployment>>> = Mutex::new(HashMap::new());
+}
+
+/// A config file to write, relative to the directory holding the instance's nginx.conf
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeployFile {
+    pub path: String,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeployRequest {
+    pub files: Vec<DeployFile>,
+}
+
+/// A config change that is live and can still be rolled back
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Deployment {
+    pub id: String,
+    pub instance: String,
+    pub files: Vec<String>,
+    pub deployed_at: u64,
+}
+
+// A file's path and what it held, `None` when it didn't exist
+type FileSnapshot = (PathBuf, Option<Vec<u8>>);
+
+struct StoredDeployment {
+    deployment: Deployment,
+    /// What each file held before, `None` for files the deployment created
+    previous: Vec<FileSnapshot>,
+}
+
+#[derive(Debug)]
+pub enum DeployError {
+    /// Empty request or a path outside the config directory
+    InvalidRequest(String),
+    NotFound(String),
+    /// Writing failed, or nginx rejected the config (the previous files are back in place)
+    Failed(String),
+}
+
+impl std::fmt::Display for DeployError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeployError::InvalidRequest(message) | DeployError::NotFound(message) | DeployError::Failed(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+/// Resolves a requested path inside the config directory, refusing anything that could leave it
+fn resolve_target(config_dir: &Path, path: &str) -> Result<PathBuf, DeployError> {
+    let relative = Path::new(path);
+    let contained = !path.is_empty() && relative.components().all(|component| matches!(component, Component::Normal(_)));
+    if !contained {
+        return Err(DeployError::InvalidRequest(format!(
+            "{} must be a path relative to {}",
+            path,
+            config_dir.display()
+        )));
+    }
+    Ok(config_dir.join(relative))
+}
+
+/// The current content of each target, `None` for those that don't exist yet. Anything that can't
+/// be read fails the deployment, since it couldn't be put back afterwards.
+fn snapshot(targets: &[(PathBuf, &String)]) -> Result<Vec<FileSnapshot>, DeployError> {
+    targets
+        .iter()
+        .map(|(target, _)| match fs::read(target) {
+            Ok(content) => Ok((target.clone(), Some(content))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok((target.clone(), None)),
+            Err(e) => Err(DeployError::Failed(format!("Failed to read {}: {}", target.display(), e))),
+        })
+        .collect()
+}
+
+fn restore(previous: &[FileSnapshot]) {
+    for (path, content) in previous.iter().rev() {
+        let result = match content {
+            Some(content) => fs::write(path, content),
+            None => fs::remove_file(path),
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to restore {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Writes every target, returning what they held before. If a write fails the files written so far
+/// are put back.
+fn write_files(targets: &[(PathBuf, &String)]) -> Result<Vec<FileSnapshot>, DeployError> {
+    let previous = snapshot(targets)?;
+    for (index, (target, content)) in targets.iter().enumerate() {
+        let written = target
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(target, content));
+        if let Err(e) = written {
+            restore(&previous[..=index]);
+            return Err(DeployError::Failed(format!("Failed to write {}: {}", target.display(), e)));
+        }
+    }
+    Ok(previous)
+}
+
+/// Records a deployment for rollback, forgetting the oldest beyond `MAX_KEPT_DEPLOYMENTS`
+fn keep(kept: &mut Vec<StoredDeployment>, stored: StoredDeployment) {
+    kept.push(stored);
+    if kept.len() > MAX_KEPT_DEPLOYMENTS {
+        kept.remove(0);
+    }
+}
+
+/// Removes deployment `id` and every later one from `kept`, oldest first
+fn take_since(kept: &mut Vec<StoredDeployment>, id: &str) -> Result<Vec<StoredDeployment>, DeployError> {
+    let index = kept
+        .iter()
+        .position(|stored| stored.deployment.id == id)
+        .ok_or_else(|| DeployError::NotFound(format!("Unknown deployment: {}", id)))?;
+    Ok(kept.split_off(index))
+}
+
+/// Restores the files of `undone` newest first, so every file ends up as it was before the oldest
+fn undo(undone: &[StoredDeployment]) {
+    for stored in undone.iter().rev() {
+        restore(&stored.previous);
+    }
+}
+
+/// Writes the files, checks the result with `nginx -t` and reloads gracefully. When nginx rejects
+/// the config the previous files are put back before returning.
+pub fn deploy_config(instance: &NginxInstance, request: &DeployRequest) -> Result<Deployment, DeployError> {
+    if request.files.is_empty() {
+        return Err(DeployError::InvalidRequest("No files to deploy".to_string()));
+    }
+    let root = nginx_config::find_main_config(instance)
+        .ok_or_else(|| DeployError::Failed("Could not find nginx.conf".to_string()))?;
+    let config_dir = Path::new(&root).parent().unwrap_or(Path::new("/")).to_path_buf();
+    let targets = request
+        .files
+        .iter()
+        .map(|file| resolve_target(&config_dir, &file.path).map(|target| (target, &file.content)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let _guard = commands::lock_config_changes();
+
+    let previous = write_files(&targets)?;
+
+    if let Err(e) = commands::reload_nginx(instance) {
+        restore(&previous);
+        return Err(DeployError::Failed(format!("Deployment rolled back: {}", e)));
+    }
+
+    let deployment = Deployment {
+        id: auth::generate_id(8),
+        instance: instance.id.clone(),
+        files: targets.iter().map(|(target, _)| target.display().to_string()).collect(),
+        deployed_at: health::now_secs(),
+    };
+    if let Ok(mut deployments) = DEPLOYMENTS.lock() {
+        let kept = deployments.entry(instance.id.clone()).or_default();
+        keep(kept, StoredDeployment { deployment: deployment.clone(), previous });
+    }
+    Ok(deployment)
+}
+
+/// Puts back the files a deployment replaced and reloads. Deployments made after it are undone
+/// too, since they were built on top of it.
+pub fn rollback_deployment(instance: &NginxInstance, id: &str) -> Result<Deployment, DeployError> {
+    let _guard = commands::lock_config_changes();
+
+    let undone = {
+        let mut deployments = DEPLOYMENTS.lock().unwrap_or_else(|e| e.into_inner());
+        take_since(deployments.entry(instance.id.clone()).or_default(), id)?
+    };
+
+    undo(&undone);
+    commands::reload_nginx(instance).map_err(|e| DeployError::Failed(format!("Rolled back, but the reload failed: {}", e)))?;
+    Ok(undone[0].deployment.clone())
+}
+
+/// The deployments of the instance that can still be rolled back, oldest first
+pub fn list_deployments(instance: &NginxInstance) -> Vec<Deployment> {
+    DEPLOYMENTS
+        .lock()
+        .map(|deployments| {
+            deployments
+                .get(&instance.id)
+                .map(|kept| kept.iter().map(|stored| stored.deployment.clone()).collect())
+                .unwrap_or_default()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory under the system temp dir, removed again when the test ends
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rustinx-deploy-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn stored(id: &str, previous: Vec<FileSnapshot>) -> StoredDeployment {
+        StoredDeployment {
+            deployment: Deployment { id: id.to_string(), instance: "main".to_string(), files: vec![], deployed_at: 0 },
+            previous,
+        }
+    }
+
+    #[test]
+    fn targets_must_stay_inside_the_config_dir() {
+        let config_dir = Path::new("/etc/nginx");
+        assert_eq!(resolve_target(config_dir, "sites-available/app").unwrap(), config_dir.join("sites-available/app"));
+        for path in ["", "../passwd", "/etc/passwd", "conf.d/../../passwd", "./nginx.conf"] {
+            assert!(matches!(resolve_target(config_dir, path), Err(DeployError::InvalidRequest(_))), "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn unreadable_target_fails_before_anything_is_written() {
+        let dir = TempDir::new("unreadable");
+        let file = dir.0.join("nginx.conf");
+        fs::write(&file, "old").unwrap();
+        let blocked = dir.0.join("conf.d");
+        fs::create_dir(&blocked).unwrap();
+
+        let content = "new".to_string();
+        let result = write_files(&[(file.clone(), &content), (blocked, &content)]);
+        assert!(matches!(result, Err(DeployError::Failed(_))));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "old");
+    }
+
+    #[test]
+    fn rollback_restores_files_as_before_the_oldest_undone_deployment() {
+        let dir = TempDir::new("rollback");
+        let file = dir.0.join("nginx.conf");
+        let created = dir.0.join("conf.d/app.conf");
+        // Not UTF-8, so it only survives if kept as bytes
+        let original = vec![b'#', 0xff, 0xfe, b'\n'];
+        fs::write(&file, &original).unwrap();
+
+        let mut kept = Vec::new();
+        let first = "first".to_string();
+        keep(&mut kept, stored("1", write_files(&[(file.clone(), &first)]).unwrap()));
+        let second = "second".to_string();
+        keep(&mut kept, stored("2", write_files(&[(file.clone(), &second), (created.clone(), &second)]).unwrap()));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "second");
+
+        undo(&take_since(&mut kept, "2").unwrap());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "first");
+        assert!(!created.exists());
+
+        keep(&mut kept, stored("3", write_files(&[(created.clone(), &second)]).unwrap()));
+        undo(&take_since(&mut kept, "1").unwrap());
+        assert_eq!(fs::read(&file).unwrap(), original);
+        assert!(!created.exists());
+        assert!(kept.is_empty());
+        assert!(matches!(take_since(&mut kept, "1"), Err(DeployError::NotFound(_))));
+    }
+
+    #[test]
+    fn only_the_newest_deployments_are_kept() {
+        let mut kept = Vec::new();
+        for id in 0..MAX_KEPT_DEPLOYMENTS + 2 {
+            keep(&mut kept, stored(&id.to_string(), vec![]));
+        }
+        let ids: Vec<&str> = kept.iter().map(|stored| stored.deployment.id.as_str()).collect();
+        assert_eq!(ids.len(), MAX_KEPT_DEPLOYMENTS);
+        assert_eq!(ids[0], "2");
+        assert_eq!(ids[MAX_KEPT_DEPLOYMENTS - 1], (MAX_KEPT_DEPLOYMENTS + 1).to_string());
+    }
+}
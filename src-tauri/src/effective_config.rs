@@ -0,0 +1,397 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use crate::instances::{self, NginxInstance};
+use crate::log_search::{CompiledFilter, LogFilter};
+use crate::nginx_config;
+
+// `nginx -T` opens each file with this line, followed by the file verbatim and a line feed
+const SECTION_PREFIX: &str = "# configuration file ";
+
+// Keeps a search for something like `;` from returning the whole config
+const MAX_SEARCH_MATCHES: usize = 1000;
+
+#[derive(Debug)]
+pub enum DumpError {
+    InvalidRequest(String),
+    Failed(String),
+}
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpError::InvalidRequest(message) | DumpError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+/// One file of the `nginx -T` output
+#[derive(Clone, Debug, Serialize)]
+pub struct DumpSection {
+    pub file: String,
+    pub content: String,
+}
+
+/// The `include` directive that pulled a file in
+#[derive(Clone, Debug, Serialize)]
+pub struct IncludeSite {
+    pub file: String,
+    pub line: usize,
+    pub directive: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct IncludeNode {
+    pub file: String,
+    /// `None` for the main config
+    pub included_by: Option<IncludeSite>,
+    pub content: String,
+    /// Set when the file couldn't be parsed, in which case its includes aren't followed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_error: Option<String>,
+    pub children: Vec<IncludeNode>,
+}
+
+/// The configuration nginx actually loaded, as reported by `nginx -T`
+#[derive(Clone, Debug, Serialize)]
+pub struct EffectiveConfig {
+    pub tree: IncludeNode,
+    /// Dumped files no include in the tree accounts for
+    pub unattached: Vec<DumpSection>,
+    /// Whatever `nginx -T` printed besides the "syntax is ok" lines, e.g. `[warn]` messages
+    pub warnings: Vec<String>,
+}
+
+/// Query accepted by `/api/nginx/effective-config/search` and the `search_effective_config` command
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConfigSearchRequest {
+    /// Substring to look for, or a regular expression when `regex` is set
+    pub query: String,
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigSearchMatch {
+    pub file: String,
+    pub line: usize,
+    pub text: String,
+    /// The includes leading from the main config to `file`, outermost first
+    pub include_chain: Vec<IncludeSite>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigSearchResult {
+    pub matches: Vec<ConfigSearchMatch>,
+    pub truncated: bool,
+}
+
+/// Splits `nginx -T` stdout into its files, in the order nginx read them
+pub fn split_dump(output: &str) -> Vec<DumpSection> {
+    split_dump_with(output, |file| Path::new(file).is_file())
+}
+
+/// `split_dump` with the check for whether a header names a real config file passed in
+fn split_dump_with(output: &str, is_config_file: impl Fn(&str) -> bool) -> Vec<DumpSection> {
+    let mut sections: Vec<DumpSection> = Vec::new();
+    let mut previous_blank = true;
+
+    for line in output.split_inclusive('\n') {
+        let trimmed = line.trim_end_matches(['\r', '\n']);
+        // A file could contain a line that looks like a header. Real ones follow the previous
+        // file's trailing blank line, unless that file didn't end with a newline, so without the
+        // blank line the header has to name an existing file.
+        let header = trimmed
+            .strip_prefix(SECTION_PREFIX)
+            .and_then(|rest| rest.strip_suffix(':'))
+            .filter(|file| previous_blank || is_config_file(file));
+
+        match (header, sections.last_mut()) {
+            (Some(file), _) => sections.push(DumpSection { file: file.to_string(), content: String::new() }),
+            (None, Some(section)) => section.content.push_str(line),
+            (None, None) => {}
+        }
+        previous_blank = trimmed.is_empty();
+    }
+
+    // Drop the line feed nginx adds after each file
+    for section in &mut sections {
+        if section.content.ends_with('\n') {
+            section.content.pop();
+        }
+    }
+    sections
+}
+
+/// Rebuilds the include tree from the dumped files. Relative includes resolve against the
+/// directory of the main config, as they do in nginx.
+pub fn build_include_tree(sections: &[DumpSection]) -> Option<(IncludeNode, Vec<DumpSection>)> {
+    let root = sections.first()?;
+    let base_dir = Path::new(&root.file).parent().unwrap_or(Path::new("/etc/nginx")).to_path_buf();
+    let mut reached = HashSet::new();
+    let mut stack = Vec::new();
+    let tree = build_node(sections, 0, None, &base_dir, &mut stack, &mut reached);
+
+    let unattached = sections
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !reached.contains(index))
+        .map(|(_, section)| section.clone())
+        .collect();
+    Some((tree, unattached))
+}
+
+fn build_node(
+    sections: &[DumpSection],
+    index: usize,
+    included_by: Option<IncludeSite>,
+    base_dir: &Path,
+    stack: &mut Vec<usize>,
+    reached: &mut HashSet<usize>,
+) -> IncludeNode {
+    let section = &sections[index];
+    reached.insert(index);
+    let mut node = IncludeNode {
+        file: section.file.clone(),
+        included_by,
+        content: section.content.clone(),
+        parse_error: None,
+        children: Vec::new(),
+    };
+
+    let directives = match nginx_config::parse_config_str(&section.content, &section.file) {
+        Ok(directives) => directives,
+        Err(e) => {
+            node.parse_error = Some(match e.line {
+                Some(line) => format!("{} at line {}", e.message, line),
+                None => e.message,
+            });
+            return node;
+        }
+    };
+
+    let mut includes = Vec::new();
+    nginx_config::walk(&directives, &mut |directive, _| {
+        if directive.name == "include" {
+            if let Some(pattern) = directive.arg(0) {
+                includes.push((nginx_config::resolve_path(base_dir, pattern), directive));
+            }
+        }
+    });
+
+    stack.push(index);
+    for (pattern, directive) in includes {
+        let site = IncludeSite {
+            file: directive.file.clone(),
+            line: directive.line,
+            directive: format!("include {};", directive.args.join(" ")),
+        };
+        for child in matching_sections(sections, &pattern) {
+            // nginx refuses include cycles, but a hand-edited dump shouldn't hang us
+            if stack.contains(&child) {
+                continue;
+            }
+            node.children
+                .push(build_node(sections, child, Some(site.clone()), base_dir, stack, reached));
+        }
+    }
+    stack.pop();
+    node
+}
+
+fn matching_sections(sections: &[DumpSection], pattern: &str) -> Vec<usize> {
+    if !nginx_config::is_glob(pattern) {
+        return sections.iter().position(|s| s.file == pattern).into_iter().collect();
+    }
+
+    let compiled = match glob::Pattern::new(pattern) {
+        Ok(compiled) => compiled,
+        Err(_) => return vec![],
+    };
+    let options = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+    let mut matches: Vec<usize> = (0..sections.len())
+        .filter(|i| compiled.matches_with(&sections[*i].file, options))
+        .collect();
+    // nginx reads glob matches in name order
+    matches.sort_by(|a, b| sections[*a].file.cmp(&sections[*b].file));
+    matches
+}
+
+/// Runs `nginx -T` for the instance and returns the effective configuration as an include tree
+pub fn effective_config(instance: &NginxInstance) -> Result<EffectiveConfig, DumpError> {
+    let output = instance
+        .command()
+        .arg("-T")
+        .output()
+        .map_err(|e| DumpError::Failed(format!("Failed to run nginx -T: {}", e)))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(DumpError::Failed(format!("nginx -T failed: {}", stderr.trim())));
+    }
+
+    let sections = split_dump(&String::from_utf8_lossy(&output.stdout));
+    let (tree, unattached) = build_include_tree(&sections)
+        .ok_or_else(|| DumpError::Failed("nginx -T printed no configuration files".to_string()))?;
+    let warnings = stderr
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter(|line| !line.contains("syntax is ok") && !line.contains("test is successful"))
+        .map(str::to_string)
+        .collect();
+
+    Ok(EffectiveConfig { tree, unattached, warnings })
+}
+
+/// Searches every line of the effective configuration, reporting each file once even when it is
+/// included from several places
+pub fn search_config(config: &EffectiveConfig, request: &ConfigSearchRequest) -> Result<ConfigSearchResult, DumpError> {
+    if request.query.is_empty() {
+        return Err(DumpError::InvalidRequest("query is required".to_string()));
+    }
+    let filter = LogFilter {
+        query: Some(request.query.clone()),
+        regex: request.regex,
+        case_sensitive: request.case_sensitive,
+        ..Default::default()
+    }
+    .compile()
+    .map_err(DumpError::InvalidRequest)?;
+
+    let mut result = ConfigSearchResult { matches: Vec::new(), truncated: false };
+    let mut searched = HashSet::new();
+    let mut chain = Vec::new();
+    search_node(&config.tree, &filter, &mut chain, &mut searched, &mut result);
+
+    for section in &config.unattached {
+        if searched.insert(section.file.clone()) {
+            search_lines(&section.file, &section.content, &[], &filter, &mut result);
+        }
+    }
+    Ok(result)
+}
+
+fn search_node(
+    node: &IncludeNode,
+    filter: &CompiledFilter,
+    chain: &mut Vec<IncludeSite>,
+    searched: &mut HashSet<String>,
+    result: &mut ConfigSearchResult,
+) {
+    if let Some(site) = &node.included_by {
+        chain.push(site.clone());
+    }
+    if searched.insert(node.file.clone()) {
+        search_lines(&node.file, &node.content, chain, filter, result);
+    }
+    for child in &node.children {
+        search_node(child, filter, chain, searched, result);
+    }
+    if node.included_by.is_some() {
+        chain.pop();
+    }
+}
+
+fn search_lines(
+    file: &str,
+    content: &str,
+    chain: &[IncludeSite],
+    filter: &CompiledFilter,
+    result: &mut ConfigSearchResult,
+) {
+    for (number, text) in content.lines().enumerate() {
+        if !filter.matches_text(text) {
+            continue;
+        }
+        if result.matches.len() >= MAX_SEARCH_MATCHES {
+            result.truncated = true;
+            return;
+        }
+        result.matches.push(ConfigSearchMatch {
+            file: file.to_string(),
+            line: number + 1,
+            text: text.to_string(),
+            include_chain: chain.to_vec(),
+        });
+    }
+}
+
+/// Dumps the config and searches it; nginx -T is cheap enough to run for every query
+pub fn search_effective(instance: &NginxInstance, request: &ConfigSearchRequest) -> Result<ConfigSearchResult, DumpError> {
+    search_config(&effective_config(instance)?, request)
+}
+
+#[tauri::command]
+pub(crate) fn get_effective_config(instance: Option<String>) -> Result<EffectiveConfig, String> {
+    effective_config(&instances::get_instance(instance.as_deref())?).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn search_effective_config(
+    instance: Option<String>,
+    request: ConfigSearchRequest,
+) -> Result<ConfigSearchResult, String> {
+    search_effective(&instances::get_instance(instance.as_deref())?, &request).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(sections: &[DumpSection]) -> Vec<(&str, &str)> {
+        sections.iter().map(|section| (section.file.as_str(), section.content.as_str())).collect()
+    }
+
+    #[test]
+    fn split_dump_separates_files_ending_in_a_newline() {
+        let output = "# configuration file /etc/nginx/nginx.conf:\n\
+                      events {}\n\
+                      include mime.types;\n\
+                      \n\
+                      # configuration file /etc/nginx/mime.types:\n\
+                      types { text/html html; }\n\
+                      \n";
+        let sections = split_dump_with(output, |_| false);
+        assert_eq!(
+            files(&sections),
+            vec![
+                ("/etc/nginx/nginx.conf", "events {}\ninclude mime.types;\n"),
+                ("/etc/nginx/mime.types", "types { text/html html; }\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_dump_separates_a_file_without_a_final_newline() {
+        let output = "# configuration file /etc/nginx/nginx.conf:\n\
+                      events {}\n\
+                      include conf.d/*.conf;\n\
+                      # configuration file /etc/nginx/conf.d/app.conf:\n\
+                      server { listen 80; }\n\
+                      \n";
+        let sections = split_dump_with(output, |file| file == "/etc/nginx/conf.d/app.conf");
+        assert_eq!(
+            files(&sections),
+            vec![
+                ("/etc/nginx/nginx.conf", "events {}\ninclude conf.d/*.conf;"),
+                ("/etc/nginx/conf.d/app.conf", "server { listen 80; }\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_dump_keeps_header_lookalikes_inside_a_file() {
+        let output = "# configuration file /etc/nginx/nginx.conf:\n\
+                      events {}\n\
+                      # configuration file /nowhere.conf:\n\
+                      \n";
+        let sections = split_dump_with(output, |_| false);
+        assert_eq!(
+            files(&sections),
+            vec![("/etc/nginx/nginx.conf", "events {}\n# configuration file /nowhere.conf:\n")]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::config_watcher::ConfigCheckResult;
+use crate::health::HealthSnapshot;
+use crate::logging::DroppedLines;
+use crate::rollout::Rollout;
+use crate::systemd::JournalEntry;
+use crate::upstreams::UpstreamReport;
+
+// Slow subscribers that fall further behind than this start missing events
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+/// Everything the backend monitors report. Monitors publish these to the bus and never
+/// deal with the transport; Tauri, the SSE endpoint and anything else subscribe to it.
+#[derive(Clone, Debug)]
+pub enum RustinxEvent {
+    AccessLog(String),
+    ErrorLog(String),
+    LogLinesDropped(DroppedLines),
+    ConfigCheck(ConfigCheckResult),
+    StatusCheck(String),
+    Heartbeat(HealthSnapshot),
+    JournalEntry(JournalEntry),
+    UpstreamHealth(UpstreamReport),
+    RolloutProgress(Rollout),
+}
+
+impl RustinxEvent {
+    /// The event name the frontend listens for
+    pub fn name(&self) -> &'static str {
+        match self {
+            RustinxEvent::AccessLog(_) => "access_event",
+            RustinxEvent::ErrorLog(_) => "error_event",
+            RustinxEvent::LogLinesDropped(_) => "log_lines_dropped",
+            RustinxEvent::ConfigCheck(_) => "nginx_config_check",
+            RustinxEvent::StatusCheck(_) => "nginx_status_check",
+            RustinxEvent::Heartbeat(_) => "nginx_health",
+            RustinxEvent::JournalEntry(_) => "journal_entry",
+            RustinxEvent::UpstreamHealth(_) => "upstream_health",
+            RustinxEvent::RolloutProgress(_) => "fleet_rollout",
+        }
+    }
+
+    pub fn payload(&self) -> Value {
+        match self {
+            RustinxEvent::AccessLog(line)
+            | RustinxEvent::ErrorLog(line)
+            | RustinxEvent::StatusCheck(line) => Value::String(line.clone()),
+            RustinxEvent::LogLinesDropped(dropped) => serde_json::to_value(dropped).unwrap_or(Value::Null),
+            RustinxEvent::ConfigCheck(result) => serde_json::to_value(result).unwrap_or(Value::Null),
+            RustinxEvent::Heartbeat(snapshot) => serde_json::to_value(snapshot).unwrap_or(Value::Null),
+            RustinxEvent::JournalEntry(entry) => serde_json::to_value(entry).unwrap_or(Value::Null),
+            RustinxEvent::UpstreamHealth(report) => serde_json::to_value(report).unwrap_or(Value::Null),
+            RustinxEvent::RolloutProgress(rollout) => serde_json::to_value(rollout).unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// An event on the bus, tagged with the nginx instance it is about
+#[derive(Clone, Debug)]
+pub struct BusEvent {
+    /// `None` for events that don't belong to one instance, such as journal entries
+    pub instance: Option<String>,
+    pub event: RustinxEvent,
+}
+
+impl BusEvent {
+    /// Whether a subscriber following `instance` should see this event
+    pub fn concerns(&self, instance: &str) -> bool {
+        self.instance.as_deref().unwrap_or(instance) == instance
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref EVENT_BUS: broadcast::Sender<BusEvent> = broadcast::channel(EVENT_BUS_CAPACITY).0;
+}
+
+pub fn publish(event: RustinxEvent) {
+    // Sending only fails when nobody is subscribed yet, which is fine
+    let _ = EVENT_BUS.send(BusEvent { instance: None, event });
+}
+
+pub fn publish_for(instance: &str, event: RustinxEvent) {
+    let _ = EVENT_BUS.send(BusEvent { instance: Some(instance.to_string()), event });
+}
+
+pub fn subscribe() -> broadcast::Receiver<BusEvent> {
+    EVENT_BUS.subscribe()
+}
@@ -1,21 +1,78 @@
+use std::sync::Mutex;
 use std::time::Duration;
 
+use tokio::sync::broadcast::error::RecvError;
 use tokio::time::interval;
 
 use tauri;
 
 use tauri::{AppHandle, Manager};
 
-pub(crate) fn emit_event(app: AppHandle, data: &str, event_name: &str) {
-    app.emit_all(event_name, data.to_string())
-        .expect("failed to emit event");
+use crate::event_bus::{self, RustinxEvent};
+use crate::health;
+use crate::instances;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+lazy_static::lazy_static! {
+    // The instance the desktop window is showing; `None` until it picks one, meaning the first
+    static ref SELECTED_INSTANCE: Mutex<Option<String>> = Mutex::new(None);
 }
 
+fn selected_instance() -> String {
+    let selected = SELECTED_INSTANCE.lock().ok().and_then(|selected| selected.clone());
+    instances::get_instance(selected.as_deref())
+        .map(|instance| instance.id)
+        .unwrap_or_else(|_| instances::DEFAULT_INSTANCE_ID.to_string())
+}
+
+/// Relays the events of the selected instance, and those not tied to one, to the Tauri frontend
+pub(crate) fn forward_to_tauri(app: AppHandle) {
+    let mut receiver = event_bus::subscribe();
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(bus_event) => {
+                    if !bus_event.concerns(&selected_instance()) {
+                        continue;
+                    }
+                    let event = bus_event.event;
+                    if let Err(e) = app.emit_all(event.name(), event.payload()) {
+                        eprintln!("Failed to emit {}: {}", event.name(), e);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("Tauri event forwarder lagged, skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Switches the events the desktop window receives to another instance
 #[tauri::command]
-pub(crate) async fn start_emitting_events(app: AppHandle) {
-    let mut interval = interval(Duration::from_secs(5));
+pub(crate) fn select_event_instance(instance: Option<String>) -> Result<(), String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+    if let Ok(mut selected) = SELECTED_INSTANCE.lock() {
+        *selected = Some(instance.id);
+    }
+    Ok(())
+}
+
+/// Publishes a composite health snapshot of every instance every few seconds as the `nginx_health`
+/// heartbeat
+pub async fn start_emitting_events() {
+    let mut interval = interval(HEARTBEAT_INTERVAL);
     loop {
         interval.tick().await;
-        emit_event(app.clone(), "This is a test event", "test_event");
+        for instance in instances::list_instances() {
+            let id = instance.id.clone();
+            // Collecting the snapshot scans processes and shells out, so keep it off the async workers
+            match tokio::task::spawn_blocking(move || health::collect_health_snapshot(&instance)).await {
+                Ok(snapshot) => event_bus::publish_for(&id, RustinxEvent::Heartbeat(snapshot)),
+                Err(e) => eprintln!("Failed to collect health snapshot: {}", e),
+            }
+        }
     }
 }
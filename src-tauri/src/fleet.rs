@@ -0,0 +1,692 @@
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod, SslVerifyMode};
+use openssl::x509::X509Ref;
+use reqwest::blocking::{Client, Response};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+use crate::health::{AlertSeverity, HealthSnapshot};
+use crate::instances::{self, NginxInstance};
+
+// Defaults for RUSTINX_AGENTS and RUSTINX_AGENT_LISTEN
+const DEFAULT_AGENTS_FILE: &str = "/etc/rustinx/agents.json";
+const DEFAULT_AGENT_LISTEN: &str = "0.0.0.0:8443";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How `web-server` takes part in a fleet, from RUSTINX_MODE
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FleetMode {
+    /// Manages only the machine it runs on
+    Standalone,
+    /// Additionally serves the API to a controller over mutual TLS
+    Agent,
+    /// Aggregates the agents listed in the agents file and forwards actions to them
+    Controller,
+}
+
+impl FleetMode {
+    pub fn from_env() -> Self {
+        match std::env::var("RUSTINX_MODE").as_deref() {
+            Ok("agent") => FleetMode::Agent,
+            Ok("controller") => FleetMode::Controller,
+            _ => FleetMode::Standalone,
+        }
+    }
+}
+
+/// Certificate, key and CA both sides of the fleet connection use. Agents only accept
+/// controllers whose certificate the CA signed, and controllers only trust agents signed by it.
+#[derive(Clone, Debug)]
+pub struct FleetTls {
+    pub certificate: String,
+    pub key: String,
+    pub ca: String,
+}
+
+impl FleetTls {
+    pub fn from_env() -> Result<Self, FleetError> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| FleetError::InvalidRequest(format!("{} must be set in agent and controller mode", name)))
+        };
+        Ok(FleetTls {
+            certificate: var("RUSTINX_TLS_CERT")?,
+            key: var("RUSTINX_TLS_KEY")?,
+            ca: var("RUSTINX_TLS_CA")?,
+        })
+    }
+}
+
+/// A host running `web-server` in agent mode
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Agent {
+    pub id: String,
+    /// Base URL of the agent's mutual TLS listener, e.g. `https://10.0.0.5:8443`
+    pub url: String,
+}
+
+#[derive(Debug)]
+pub enum FleetError {
+    /// Not running as a controller, or the request names something that can't be forwarded
+    InvalidRequest(String),
+    NotFound(String),
+    /// The agent couldn't be reached or the TLS material couldn't be loaded
+    Failed(String),
+}
+
+impl std::fmt::Display for FleetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FleetError::InvalidRequest(message) | FleetError::NotFound(message) | FleetError::Failed(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+/// CPU, memory and process counts as the agent's `/api/system-metrics` reports them
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMetrics {
+    pub cpu: f32,
+    pub total_memory: u64,
+    pub used_memory: u64,
+    pub tasks: usize,
+    pub worker_count: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AgentInstanceStatus {
+    pub instance: String,
+    pub health: Option<HealthSnapshot>,
+    pub metrics: Option<AgentMetrics>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AgentStatus {
+    pub id: String,
+    pub url: String,
+    pub reachable: bool,
+    /// Why the agent, or part of what it was asked, couldn't be fetched
+    pub error: Option<String>,
+    pub instances: Vec<AgentInstanceStatus>,
+}
+
+/// An alert of one agent, or the controller's own alert about an agent it can't reach
+#[derive(Clone, Debug, Serialize)]
+pub struct FleetAlert {
+    pub agent: String,
+    pub instance: Option<String>,
+    pub key: String,
+    pub severity: AlertSeverity,
+    pub message: String,
+    pub since: Option<u64>,
+}
+
+/// Every agent's status plus the totals the dashboard shows first
+#[derive(Clone, Debug, Serialize)]
+pub struct FleetStatus {
+    pub agents: Vec<AgentStatus>,
+    pub reachable: usize,
+    pub unreachable: usize,
+    /// Instances reporting `active`, across all agents
+    pub active_instances: usize,
+    pub inactive_instances: usize,
+    pub workers: usize,
+    pub alerts: Vec<FleetAlert>,
+}
+
+/// What an agent answered to a forwarded request
+pub struct ProxiedResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+fn agents_file() -> String {
+    std::env::var("RUSTINX_AGENTS").unwrap_or_else(|_| DEFAULT_AGENTS_FILE.to_string())
+}
+
+/// Where the agent's mutual TLS listener binds
+pub fn agent_listen_address() -> String {
+    std::env::var("RUSTINX_AGENT_LISTEN").unwrap_or_else(|_| DEFAULT_AGENT_LISTEN.to_string())
+}
+
+/// Whether an agent also serves the plain HTTP API and UI, from RUSTINX_AGENT_HTTP=1. Off by
+/// default: nothing but the controller's certificate should reach an agent's API.
+pub fn agent_serves_http() -> bool {
+    matches!(std::env::var("RUSTINX_AGENT_HTTP").as_deref(), Ok("1") | Ok("true"))
+}
+
+fn require_controller() -> Result<(), FleetError> {
+    match FleetMode::from_env() {
+        FleetMode::Controller => Ok(()),
+        _ => Err(FleetError::InvalidRequest("Not running as a fleet controller (RUSTINX_MODE=controller)".to_string())),
+    }
+}
+
+/// The registered agents, in file order
+pub fn list_agents() -> Result<Vec<Agent>, FleetError> {
+    require_controller()?;
+
+    let path = agents_file();
+    let content = fs::read_to_string(&path).map_err(|e| FleetError::Failed(format!("Failed to read {}: {}", path, e)))?;
+    let agents: Vec<Agent> =
+        serde_json::from_str(&content).map_err(|e| FleetError::Failed(format!("Failed to parse {}: {}", path, e)))?;
+
+    let mut seen = HashSet::new();
+    for agent in &agents {
+        if !instances::is_valid_id(&agent.id) {
+            return Err(FleetError::Failed(format!("{}: invalid agent id \"{}\"", path, agent.id)));
+        }
+        if !seen.insert(agent.id.as_str()) {
+            return Err(FleetError::Failed(format!("{}: agent id \"{}\" is used twice", path, agent.id)));
+        }
+        if !agent.url.starts_with("https://") {
+            return Err(FleetError::Failed(format!("{}: agent \"{}\" must use an https:// URL", path, agent.id)));
+        }
+    }
+    Ok(agents)
+}
+
+pub fn get_agent(id: &str) -> Result<Agent, FleetError> {
+    list_agents()?
+        .into_iter()
+        .find(|agent| agent.id == id)
+        .ok_or_else(|| FleetError::NotFound(format!("Unknown agent: {}", id)))
+}
+
+/// The name the controller's certificate must carry, from RUSTINX_CONTROLLER_NAME. Every agent's
+/// certificate is signed by the same CA, so the CA alone can't tell the controller apart.
+pub fn controller_name() -> Result<String, FleetError> {
+    std::env::var("RUSTINX_CONTROLLER_NAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| FleetError::InvalidRequest("RUSTINX_CONTROLLER_NAME must be set in agent mode".to_string()))
+}
+
+/// Common names and DNS subject alternative names of a certificate
+fn certificate_names(certificate: &X509Ref) -> Vec<String> {
+    let mut names: Vec<String> = certificate
+        .subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .filter_map(|entry| entry.data().to_string().ok())
+        .collect();
+    if let Some(alt_names) = certificate.subject_alt_names() {
+        names.extend(alt_names.iter().filter_map(|name| name.dnsname().map(str::to_string)));
+    }
+    names
+}
+
+/// The acceptor for the agent's listener: our certificate, and a client certificate signed by
+/// the fleet CA and issued to `controller` is required before any request is read
+pub fn agent_acceptor(tls: &FleetTls, controller: &str) -> Result<SslAcceptorBuilder, FleetError> {
+    let failed = |what: &str, e: openssl::error::ErrorStack| FleetError::Failed(format!("Failed to load {}: {}", what, e));
+
+    let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).map_err(|e| failed("TLS", e))?;
+    builder
+        .set_certificate_chain_file(&tls.certificate)
+        .map_err(|e| failed(&tls.certificate, e))?;
+    builder
+        .set_private_key_file(&tls.key, SslFiletype::PEM)
+        .map_err(|e| failed(&tls.key, e))?;
+    builder.check_private_key().map_err(|e| failed(&tls.key, e))?;
+    builder.set_ca_file(&tls.ca).map_err(|e| failed(&tls.ca, e))?;
+    let controller = controller.to_string();
+    builder.set_verify_callback(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT, move |verified, context| {
+        // The chain is checked against the CA as usual; the client's own certificate must also name the controller
+        if !verified || context.error_depth() > 0 {
+            return verified;
+        }
+        context
+            .current_cert()
+            .is_some_and(|certificate| certificate_names(certificate).contains(&controller))
+    });
+    Ok(builder)
+}
+
+/// An HTTP client presenting the controller's certificate and trusting only the fleet CA
+pub(crate) fn controller_client(tls: &FleetTls) -> Result<Client, FleetError> {
+    let read = |path: &str| fs::read(path).map_err(|e| FleetError::Failed(format!("Failed to read {}: {}", path, e)));
+
+    // native-tls only takes PKCS#8 keys, so convert whatever `openssl req` produced
+    let key = PKey::private_key_from_pem(&read(&tls.key)?)
+        .and_then(|key| key.private_key_to_pem_pkcs8())
+        .map_err(|e| FleetError::Failed(format!("Failed to parse {}: {}", tls.key, e)))?;
+    let identity = reqwest::Identity::from_pkcs8_pem(&read(&tls.certificate)?, &key)
+        .map_err(|e| FleetError::Failed(format!("Failed to load {}: {}", tls.certificate, e)))?;
+    let ca = reqwest::Certificate::from_pem(&read(&tls.ca)?)
+        .map_err(|e| FleetError::Failed(format!("Failed to parse {}: {}", tls.ca, e)))?;
+
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent("rustinx-controller")
+        .identity(identity)
+        .add_root_certificate(ca)
+        .tls_built_in_root_certs(false)
+        .build()
+        .map_err(|e| FleetError::Failed(e.to_string()))
+}
+
+fn agent_url(agent: &Agent, path: &str) -> String {
+    format!("{}/api/{}", agent.url.trim_end_matches('/'), path.trim_start_matches('/'))
+}
+
+pub(crate) fn get_json<T: DeserializeOwned>(client: &Client, agent: &Agent, path: &str) -> Result<T, String> {
+    read_json(agent, client.get(agent_url(agent, path)).send())
+}
+
+pub(crate) fn post_json<T: DeserializeOwned>(client: &Client, agent: &Agent, path: &str, body: &impl Serialize) -> Result<T, String> {
+    read_json(agent, client.post(agent_url(agent, path)).json(body).send())
+}
+
+/// The decoded answer, or the agent's own `error` message when it refused
+fn read_json<T: DeserializeOwned>(agent: &Agent, sent: reqwest::Result<Response>) -> Result<T, String> {
+    let response = sent.map_err(|e| format!("{}: {}", agent.id, e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let message = response
+            .json::<serde_json::Value>()
+            .ok()
+            .and_then(|body| body.get("error").and_then(|e| e.as_str()).map(str::to_string))
+            .unwrap_or_else(|| status.to_string());
+        return Err(format!("{}: {}", agent.id, message));
+    }
+    response.json().map_err(|e| format!("{}: {}", agent.id, e))
+}
+
+fn fetch_agent_status(client: &Client, agent: &Agent) -> AgentStatus {
+    let mut status = AgentStatus {
+        id: agent.id.clone(),
+        url: agent.url.clone(),
+        reachable: false,
+        error: None,
+        instances: vec![],
+    };
+
+    let instances: Vec<NginxInstance> = match get_json(client, agent, "instances") {
+        Ok(instances) => instances,
+        Err(e) => {
+            status.error = Some(e);
+            return status;
+        }
+    };
+    status.reachable = true;
+
+    for instance in instances {
+        let query = format!("?instance={}", instance.id);
+        let health = get_json::<HealthSnapshot>(client, agent, &format!("nginx/health{}", query));
+        let metrics = get_json::<AgentMetrics>(client, agent, &format!("system-metrics{}", query));
+        // Keep what did arrive; the first failure is reported for the agent
+        if let Some(e) = health.as_ref().err().or(metrics.as_ref().err()) {
+            status.error.get_or_insert_with(|| e.clone());
+        }
+        status.instances.push(AgentInstanceStatus {
+            instance: instance.id,
+            health: health.ok(),
+            metrics: metrics.ok(),
+        });
+    }
+    status
+}
+
+/// Asks every agent for its instances' health and metrics, all agents at once
+pub fn fleet_status() -> Result<FleetStatus, FleetError> {
+    let agents = list_agents()?;
+    let client = controller_client(&FleetTls::from_env()?)?;
+
+    let statuses: Vec<AgentStatus> = thread::scope(|scope| {
+        let handles: Vec<_> = agents
+            .iter()
+            .map(|agent| {
+                let client = &client;
+                scope.spawn(move || fetch_agent_status(client, agent))
+            })
+            .collect();
+        handles.into_iter().filter_map(|handle| handle.join().ok()).collect()
+    });
+
+    let mut alerts = Vec::new();
+    let mut active_instances = 0;
+    let mut inactive_instances = 0;
+    let mut workers = 0;
+    for agent in &statuses {
+        if !agent.reachable {
+            alerts.push(FleetAlert {
+                agent: agent.id.clone(),
+                instance: None,
+                key: "agent_unreachable".to_string(),
+                severity: AlertSeverity::Critical,
+                message: agent.error.clone().unwrap_or_else(|| "agent is unreachable".to_string()),
+                since: None,
+            });
+            continue;
+        }
+        for instance in &agent.instances {
+            if let Some(health) = &instance.health {
+                if health.status == "active" {
+                    active_instances += 1;
+                } else {
+                    inactive_instances += 1;
+                }
+                workers += health.worker_count;
+                alerts.extend(health.alerts.iter().map(|alert| FleetAlert {
+                    agent: agent.id.clone(),
+                    instance: alert.instance.clone(),
+                    key: alert.key.clone(),
+                    severity: alert.severity.clone(),
+                    message: alert.message.clone(),
+                    since: Some(alert.since),
+                }));
+            }
+        }
+    }
+    // The same rustinx-wide alert comes back once per instance
+    let mut seen = HashSet::new();
+    alerts.retain(|alert| seen.insert((alert.agent.clone(), alert.instance.clone(), alert.key.clone())));
+    alerts.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.agent.cmp(&b.agent)).then(a.key.cmp(&b.key)));
+
+    Ok(FleetStatus {
+        reachable: statuses.iter().filter(|agent| agent.reachable).count(),
+        unreachable: statuses.iter().filter(|agent| !agent.reachable).count(),
+        agents: statuses,
+        active_instances,
+        inactive_instances,
+        workers,
+        alerts,
+    })
+}
+
+/// Sends a request to the agent's API as the controller and hands back whatever it answers,
+/// errors included. `path` is relative to `/api/`; `query` is passed on as is.
+pub fn proxy_request(
+    agent_id: &str,
+    method: &str,
+    path: &str,
+    query: &str,
+    content_type: Option<&str>,
+    body: Vec<u8>,
+) -> Result<ProxiedResponse, FleetError> {
+    let agent = get_agent(agent_id)?;
+    // Logging in belongs to each host's own UI; the controller's certificate stands in for it
+    if path.is_empty() || path.split('/').any(|segment| segment == "..") || path.starts_with("login") {
+        return Err(FleetError::InvalidRequest(format!("Can't forward /api/{} to an agent", path)));
+    }
+    let method = Method::from_bytes(method.as_bytes()).map_err(|e| FleetError::InvalidRequest(e.to_string()))?;
+
+    let client = controller_client(&FleetTls::from_env()?)?;
+    let mut url = agent_url(&agent, path);
+    if !query.is_empty() {
+        url.push('?');
+        url.push_str(query);
+    }
+    let mut request = client.request(method, &url).body(body);
+    if let Some(content_type) = content_type {
+        request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+    }
+
+    let response = request
+        .send()
+        .map_err(|e| FleetError::Failed(format!("Failed to reach agent {}: {}", agent.id, e)))?;
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = response
+        .bytes()
+        .map_err(|e| FleetError::Failed(format!("Failed to read the answer of agent {}: {}", agent.id, e)))?
+        .to_vec();
+
+    Ok(ProxiedResponse { status, content_type, body })
+}
+
+#[tauri::command]
+pub(crate) fn get_fleet_status() -> Result<FleetStatus, String> {
+    fleet_status().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth;
+    use actix_session::{storage::CookieSessionStore, Session, SessionMiddleware};
+    use actix_web::dev::Service;
+    use actix_web::{cookie::Key, web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer};
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::Private;
+    use openssl::x509::extension::{BasicConstraints, ExtendedKeyUsage, SubjectAlternativeName};
+    use openssl::x509::{X509NameBuilder, X509};
+    use serde_json::json;
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc;
+
+    /// A scratch directory under the system temp dir, removed again when the test ends
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rustinx-fleet-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes `{name}.pem` and `{name}.key` for 127.0.0.1, signed by `issuer` or, without one, a CA
+    fn issue(dir: &Path, name: &str, serial: u32, issuer: Option<&(X509, PKey<Private>)>) -> (X509, PKey<Private>) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_nid(Nid::COMMONNAME, name).unwrap();
+        let subject = subject.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(serial).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        match issuer {
+            Some((ca, ca_key)) => {
+                builder.set_issuer_name(ca.subject_name()).unwrap();
+                let san = SubjectAlternativeName::new()
+                    .ip("127.0.0.1")
+                    .build(&builder.x509v3_context(Some(ca), None))
+                    .unwrap();
+                builder.append_extension(san).unwrap();
+                builder
+                    .append_extension(ExtendedKeyUsage::new().server_auth().client_auth().build().unwrap())
+                    .unwrap();
+                builder.sign(ca_key, MessageDigest::sha256()).unwrap();
+            }
+            None => {
+                builder.set_issuer_name(&subject).unwrap();
+                builder
+                    .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                    .unwrap();
+                builder.sign(&key, MessageDigest::sha256()).unwrap();
+            }
+        }
+
+        let certificate = builder.build();
+        fs::write(dir.join(format!("{}.pem", name)), certificate.to_pem().unwrap()).unwrap();
+        fs::write(dir.join(format!("{}.key", name)), key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        (certificate, key)
+    }
+
+    fn tls(dir: &Path, name: &str) -> FleetTls {
+        let path = |file: String| dir.join(file).to_string_lossy().to_string();
+        FleetTls {
+            certificate: path(format!("{}.pem", name)),
+            key: path(format!("{}.key", name)),
+            ca: path("ca.pem".to_string()),
+        }
+    }
+
+    fn unauthorized() -> HttpResponse {
+        HttpResponse::Unauthorized().json(json!({ "error": "Authentication required" }))
+    }
+
+    async fn instances(req: HttpRequest, session: Session) -> actix_web::Result<HttpResponse> {
+        if !auth::is_authenticated(&req, &session)? {
+            return Ok(unauthorized());
+        }
+        Ok(HttpResponse::Ok().json(json!([{ "id": "main" }])))
+    }
+
+    async fn health(req: HttpRequest, session: Session) -> actix_web::Result<HttpResponse> {
+        if !auth::is_authenticated(&req, &session)? {
+            return Ok(unauthorized());
+        }
+        Ok(HttpResponse::Ok().json(json!({
+            "instance": "main",
+            "timestamp": 1,
+            "status": "active",
+            "config_valid": true,
+            "config_message": null,
+            "master_pid": 100,
+            "uptime_secs": 60,
+            "worker_count": 2,
+            "last_reload": null,
+            "alerts": [{
+                "key": "error_rate",
+                "instance": "main",
+                "severity": "warning",
+                "message": "5xx rate above threshold",
+                "since": 1
+            }]
+        })))
+    }
+
+    async fn metrics(req: HttpRequest, session: Session) -> actix_web::Result<HttpResponse> {
+        if !auth::is_authenticated(&req, &session)? {
+            return Ok(unauthorized());
+        }
+        Ok(HttpResponse::Ok().json(json!({
+            "cpu": 1.5,
+            "totalMemory": 2048,
+            "usedMemory": 512,
+            "tasks": 3,
+            "workerCount": 2
+        })))
+    }
+
+    async fn restart(req: HttpRequest, session: Session, body: String) -> actix_web::Result<HttpResponse> {
+        if !auth::is_authenticated(&req, &session)? {
+            return Ok(unauthorized());
+        }
+        Ok(HttpResponse::Ok().json(json!({ "query": req.query_string(), "body": body })))
+    }
+
+    /// Serves a stand-in agent API on the mutual TLS listener, marking requests the way
+    /// `web-server` does, and returns its address and handle
+    fn start_agent(tls: FleetTls) -> (String, actix_web::dev::ServerHandle) {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                let key = Key::generate();
+                let server = HttpServer::new(move || {
+                    App::new()
+                        .wrap_fn(|req, srv| {
+                            if req.app_config().secure() {
+                                req.extensions_mut().insert(auth::ControllerPeer);
+                            }
+                            srv.call(req)
+                        })
+                        .wrap(SessionMiddleware::new(CookieSessionStore::default(), key.clone()))
+                        .route("/api/instances", web::get().to(instances))
+                        .route("/api/nginx/health", web::get().to(health))
+                        .route("/api/system-metrics", web::get().to(metrics))
+                        .route("/api/nginx/restart", web::post().to(restart))
+                })
+                .workers(1)
+                .bind_openssl("127.0.0.1:0", agent_acceptor(&tls, "controller").unwrap())
+                .unwrap();
+                let address = server.addrs()[0];
+                let server = server.run();
+                sender.send((format!("https://{}", address), server.handle())).unwrap();
+                server.await.unwrap();
+            });
+        });
+        receiver.recv().unwrap()
+    }
+
+    // Controller mode is read from the environment, so everything that needs it runs in one test
+    #[test]
+    fn controller_reaches_agents_over_mutual_tls() {
+        let dir = TempDir::new("mtls");
+        let ca = issue(&dir.0, "ca", 1, None);
+        issue(&dir.0, "agent", 2, Some(&ca));
+        issue(&dir.0, "controller", 3, Some(&ca));
+
+        let (url, server) = start_agent(tls(&dir.0, "agent"));
+        let agents = dir.0.join("agents.json");
+        let agents_json = json!([
+            { "id": "web-01", "url": url },
+            { "id": "web-02", "url": "https://127.0.0.1:1" }
+        ]);
+        fs::write(&agents, agents_json.to_string()).unwrap();
+
+        let controller = tls(&dir.0, "controller");
+        std::env::set_var("RUSTINX_MODE", "controller");
+        std::env::set_var("RUSTINX_AGENTS", &agents);
+        std::env::set_var("RUSTINX_TLS_CERT", &controller.certificate);
+        std::env::set_var("RUSTINX_TLS_KEY", &controller.key);
+        std::env::set_var("RUSTINX_TLS_CA", &controller.ca);
+
+        let status = fleet_status().unwrap();
+        assert_eq!((status.reachable, status.unreachable), (1, 1));
+        assert_eq!((status.active_instances, status.workers), (1, 2));
+        let web_01 = &status.agents[0];
+        assert_eq!(web_01.error, None);
+        assert_eq!(web_01.instances[0].metrics.as_ref().map(|m| m.used_memory), Some(512));
+        let alerts: Vec<(&str, &str)> = status.alerts.iter().map(|a| (a.agent.as_str(), a.key.as_str())).collect();
+        assert_eq!(alerts, vec![("web-02", "agent_unreachable"), ("web-01", "error_rate")]);
+
+        let response = proxy_request("web-01", "POST", "nginx/restart", "instance=main", Some("text/plain"), b"now".to_vec()).unwrap();
+        assert_eq!(response.status, 200);
+        let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(body, json!({ "query": "instance=main", "body": "now" }));
+        assert!(matches!(proxy_request("web-01", "POST", "login", "", None, vec![]), Err(FleetError::InvalidRequest(_))));
+
+        // Trusting the CA isn't enough; without a client certificate the agent refuses the connection
+        let ca_pem = fs::read(&controller.ca).unwrap();
+        let anonymous = Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(&ca_pem).unwrap())
+            .tls_built_in_root_certs(false)
+            .build()
+            .unwrap();
+        assert!(anonymous.get(format!("{}/api/instances", url)).send().is_err());
+
+        // Another agent's certificate is signed by the same CA but isn't the controller's
+        let impostor = controller_client(&tls(&dir.0, "agent")).unwrap();
+        assert!(impostor.get(format!("{}/api/instances", url)).send().is_err());
+
+        actix_web::rt::System::new().block_on(server.stop(false));
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[cfg(not(target_os = "linux"))]
+const FALLBACK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches directories for changes to the files in them. Directories are watched instead of
+/// files so renames, rotations and editors that replace files are all noticed.
+/// On platforms without inotify, `wait` simply sleeps and callers re-check on their own.
+pub(crate) struct DirWatcher {
+    #[cfg(target_os = "linux")]
+    inotify: nix::sys::inotify::Inotify,
+    #[cfg(target_os = "linux")]
+    watches: std::collections::HashMap<nix::sys::inotify::WatchDescriptor, PathBuf>,
+}
+
+#[cfg(target_os = "linux")]
+impl DirWatcher {
+    pub(crate) fn new(directories: &[PathBuf]) -> std::io::Result<Self> {
+        use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+
+        let inotify = Inotify::init(InitFlags::IN_CLOEXEC | InitFlags::IN_NONBLOCK)?;
+        let flags = AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_MODIFY
+            | AddWatchFlags::IN_ATTRIB
+            | AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_MOVED_TO;
+
+        let mut watches = std::collections::HashMap::new();
+        for dir in directories {
+            match inotify.add_watch(dir.as_path(), flags) {
+                Ok(wd) => {
+                    watches.insert(wd, dir.clone());
+                }
+                Err(e) => eprintln!("Failed to watch {}: {}", dir.display(), e),
+            }
+        }
+
+        if watches.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "none of the directories could be watched",
+            ));
+        }
+        Ok(DirWatcher { inotify, watches })
+    }
+
+    /// Waits up to `timeout` (forever when `None`) and returns the paths that changed.
+    /// An empty result means the timeout passed without activity.
+    pub(crate) fn wait(&self, timeout: Option<Duration>) -> Vec<PathBuf> {
+        use nix::errno::Errno;
+        use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+        use std::os::fd::AsFd;
+
+        let timeout = match timeout {
+            Some(timeout) => PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX),
+            None => PollTimeout::NONE,
+        };
+        let mut fds = [PollFd::new(self.inotify.as_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, timeout) {
+            Ok(0) | Err(Errno::EINTR) => return vec![],
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Failed to poll inotify: {}", e);
+                std::thread::sleep(Duration::from_secs(1));
+                return vec![];
+            }
+        }
+
+        match self.inotify.read_events() {
+            Ok(events) => events
+                .into_iter()
+                .filter_map(|event| {
+                    let dir = self.watches.get(&event.wd)?;
+                    Some(dir.join(event.name?))
+                })
+                .collect(),
+            Err(Errno::EAGAIN) => vec![],
+            Err(e) => {
+                eprintln!("Failed to read inotify events: {}", e);
+                vec![]
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl DirWatcher {
+    pub(crate) fn new(_directories: &[PathBuf]) -> std::io::Result<Self> {
+        Ok(DirWatcher {})
+    }
+
+    pub(crate) fn wait(&self, timeout: Option<Duration>) -> Vec<PathBuf> {
+        let interval = timeout.map_or(FALLBACK_POLL_INTERVAL, |t| t.min(FALLBACK_POLL_INTERVAL));
+        std::thread::sleep(interval);
+        vec![]
+    }
+}
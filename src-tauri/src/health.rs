@@ -0,0 +1,410 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use sysinfo::{Pid, Process, System};
+
+use crate::instances::{self, NginxInstance};
+use crate::logging::nginx_status;
+use crate::nginx_build;
+use crate::nginx_config;
+
+// Where nginx usually writes its master pid, checked in order
+const PID_FILE_CANDIDATES: [&str; 5] = [
+    "/run/nginx.pid",
+    "/var/run/nginx.pid",
+    "/usr/local/var/run/nginx.pid",
+    "/opt/homebrew/var/run/nginx.pid",
+    "/usr/local/nginx/logs/nginx.pid",
+];
+
+// Workers started this long after the master are treated as the result of a reload
+const RELOAD_DETECTION_SLACK_SECS: u64 = 2;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Alert {
+    pub key: String,
+    /// The nginx instance the alert is about; `None` for rustinx-wide alerts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    pub severity: AlertSeverity,
+    pub message: String,
+    pub since: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigState {
+    pub valid: bool,
+    pub message: String,
+    pub checked_at: u64,
+}
+
+/// Composite health of the nginx instance, emitted as the `nginx_health` heartbeat
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HealthSnapshot {
+    pub instance: String,
+    pub timestamp: u64,
+    pub status: String,
+    pub config_valid: Option<bool>,
+    pub config_message: Option<String>,
+    pub master_pid: Option<u32>,
+    pub uptime_secs: Option<u64>,
+    pub worker_count: usize,
+    pub last_reload: Option<u64>,
+    pub alerts: Vec<Alert>,
+}
+
+/// CPU, memory and process counts of one instance's master and everything it spawned
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ProcessMetrics {
+    pub master_pid: Option<u32>,
+    pub cpu_usage: f32,
+    pub memory: u64,
+    pub processes: usize,
+    pub workers: usize,
+    pub cache_managers: usize,
+    pub cache_loaders: usize,
+}
+
+lazy_static::lazy_static! {
+    // Keyed by instance (None for rustinx-wide alerts) and alert key
+    static ref ALERTS: Mutex<HashMap<(Option<String>, String), Alert>> = Mutex::new(HashMap::new());
+    static ref LAST_CONFIG_CHECK: Mutex<HashMap<String, ConfigState>> = Mutex::new(HashMap::new());
+}
+
+pub(crate) fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn store_alert(instance: Option<&str>, key: &str, severity: AlertSeverity, message: &str) {
+    if let Ok(mut alerts) = ALERTS.lock() {
+        let slot = (instance.map(str::to_string), key.to_string());
+        let since = alerts.get(&slot).map(|a| a.since).unwrap_or_else(now_secs);
+        alerts.insert(
+            slot,
+            Alert {
+                key: key.to_string(),
+                instance: instance.map(str::to_string),
+                severity,
+                message: message.to_string(),
+                since,
+            },
+        );
+    }
+}
+
+/// Raises (or updates) an alert that stays active until `clear_alert` is called with the same key
+pub fn raise_alert(key: &str, severity: AlertSeverity, message: &str) {
+    store_alert(None, key, severity, message);
+}
+
+pub fn clear_alert(key: &str) {
+    if let Ok(mut alerts) = ALERTS.lock() {
+        alerts.remove(&(None, key.to_string()));
+    }
+}
+
+/// Like `raise_alert`, for a problem with one instance; other instances can raise the same key
+pub fn raise_instance_alert(instance: &str, key: &str, severity: AlertSeverity, message: &str) {
+    store_alert(Some(instance), key, severity, message);
+}
+
+pub fn clear_instance_alert(instance: &str, key: &str) {
+    if let Ok(mut alerts) = ALERTS.lock() {
+        alerts.remove(&(Some(instance.to_string()), key.to_string()));
+    }
+}
+
+fn sorted_alerts(filter: impl Fn(&Alert) -> bool) -> Vec<Alert> {
+    let mut alerts: Vec<Alert> = match ALERTS.lock() {
+        Ok(alerts) => alerts.values().filter(|alert| filter(alert)).cloned().collect(),
+        Err(_) => vec![],
+    };
+    alerts.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.key.cmp(&b.key)));
+    alerts
+}
+
+pub fn active_alerts() -> Vec<Alert> {
+    sorted_alerts(|_| true)
+}
+
+/// The rustinx-wide alerts plus the ones raised for `instance`
+pub fn alerts_for(instance: &str) -> Vec<Alert> {
+    sorted_alerts(|alert| alert.instance.as_deref().unwrap_or(instance) == instance)
+}
+
+/// Remembers the outcome of the latest `nginx -t` so the heartbeat doesn't have to run it again
+pub(crate) fn record_config_check(instance: &str, valid: bool, message: &str) {
+    if let Ok(mut state) = LAST_CONFIG_CHECK.lock() {
+        state.insert(
+            instance.to_string(),
+            ConfigState {
+                valid,
+                message: message.to_string(),
+                checked_at: now_secs(),
+            },
+        );
+    }
+
+    if valid {
+        clear_instance_alert(instance, "config_invalid");
+    } else {
+        raise_instance_alert(instance, "config_invalid", AlertSeverity::Critical, message);
+    }
+}
+
+pub fn last_config_check(instance: &str) -> Option<ConfigState> {
+    LAST_CONFIG_CHECK.lock().ok().and_then(|state| state.get(instance).cloned())
+}
+
+/// Where the instance keeps its master pid: its own setting, the `pid` directive of its config,
+/// then the path it was built with
+fn pid_files(instance: &NginxInstance) -> Vec<String> {
+    if let Some(pid_file) = &instance.pid_file {
+        return vec![pid_file.clone()];
+    }
+
+    let mut candidates = Vec::new();
+    let build = nginx_build::nginx_build(instance).ok();
+    if let Some(root) = nginx_config::find_main_config(instance) {
+        let directives = fs::read_to_string(&root)
+            .ok()
+            .and_then(|content| nginx_config::parse_config_str(&content, &root).ok())
+            .unwrap_or_default();
+        if let Some(pid) = directives.iter().rev().find(|d| d.name == "pid").and_then(|d| d.arg(0)) {
+            // nginx resolves a relative pid path against its prefix
+            let prefix = instance.prefix.clone().or_else(|| build.as_ref().map(|b| b.paths.prefix.clone()));
+            let prefix = prefix.unwrap_or_else(|| "/".to_string());
+            candidates.push(nginx_config::resolve_path(Path::new(&prefix), pid));
+        }
+    }
+    if let Some(build) = build {
+        candidates.push(build.paths.pid);
+    }
+    // Packaged installs only; a prefix install keeps its pid under the prefix
+    if instance.prefix.is_none() {
+        candidates.extend(PID_FILE_CANDIDATES.iter().map(|path| path.to_string()));
+    }
+    candidates
+}
+
+pub fn read_master_pid(instance: &NginxInstance) -> Option<u32> {
+    pid_files(instance)
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .find_map(|content| content.trim().parse::<u32>().ok())
+}
+
+/// What an nginx process does, as told by the title nginx gives it (`nginx: worker process`)
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessRole {
+    Master,
+    Worker,
+    CacheManager,
+    CacheLoader,
+}
+
+/// One process of an instance's tree
+#[derive(Clone, Debug, Serialize)]
+pub struct NginxProcess {
+    pub pid: u32,
+    pub role: ProcessRole,
+    pub cpu_usage: f32,
+    pub memory: u64,
+    pub started_at: u64,
+}
+
+fn process_title(process: &Process) -> String {
+    // nginx overwrites argv with its title, padding the rest of the buffer
+    process
+        .cmd()
+        .iter()
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string()
+}
+
+/// Classifies a process below the master. `None` for anything that isn't an nginx process, such as
+/// programs started by piped logging.
+fn classify(process: &Process, master: &Process) -> Option<ProcessRole> {
+    let title = process_title(process);
+    match title.strip_prefix("nginx: ").and_then(|rest| rest.strip_suffix(" process")) {
+        Some("master") => Some(ProcessRole::Master),
+        Some("worker") => Some(ProcessRole::Worker),
+        Some("cache manager") => Some(ProcessRole::CacheManager),
+        Some("cache loader") => Some(ProcessRole::CacheLoader),
+        Some(_) => None,
+        // Where nginx can't set titles (macOS, the BSDs) its children keep the master's command
+        // line, so the only thing left to tell them apart is being forked from the master binary
+        None if !process_title(master).starts_with("nginx: ")
+            && process.exe().is_some()
+            && process.exe() == master.exe() =>
+        {
+            Some(ProcessRole::Worker)
+        }
+        None => None,
+    }
+}
+
+/// The master and the nginx processes below it. Other nginx installations and tools with nginx in
+/// their name, such as exporters, are not part of the tree.
+pub fn process_tree(sys: &System, master: Pid) -> Vec<NginxProcess> {
+    let master = match sys.process(master) {
+        Some(master) => master,
+        None => return vec![],
+    };
+
+    let mut tree = vec![(master, ProcessRole::Master)];
+    let mut index = 0;
+    while index < tree.len() {
+        let parent = tree[index].0.pid();
+        // On Linux threads show up as processes too; they are already counted with their process
+        tree.extend(
+            sys.processes()
+                .values()
+                .filter(|process| process.parent() == Some(parent) && process.thread_kind().is_none())
+                .filter_map(|process| classify(process, master).map(|role| (process, role))),
+        );
+        index += 1;
+    }
+
+    tree.into_iter()
+        .map(|(process, role)| NginxProcess {
+            pid: process.pid().as_u32(),
+            role,
+            cpu_usage: process.cpu_usage(),
+            memory: process.memory(),
+            started_at: process.start_time(),
+        })
+        .collect()
+}
+
+/// Whether the process runs the instance's nginx binary. A bare name such as the default `nginx`
+/// only has to match the executable's file name.
+fn runs_instance_binary(process: &Process, instance: &NginxInstance) -> bool {
+    let exe = match process.exe() {
+        Some(exe) => exe,
+        None => return false,
+    };
+    let binary = Path::new(&instance.binary);
+    if binary.components().count() > 1 {
+        fs::canonicalize(binary).is_ok_and(|binary| binary == exe)
+    } else {
+        exe.file_name() == Some(binary.as_os_str())
+    }
+}
+
+/// The running master of the instance. A stale pid file can outlive nginx, and its pid can be
+/// reused by something else, so the process has to still be titled as an nginx master, or run the
+/// instance's binary where nginx can't set titles.
+pub fn find_master(sys: &System, instance: &NginxInstance) -> Option<Pid> {
+    let pid = Pid::from_u32(read_master_pid(instance)?);
+    let process = sys.process(pid)?;
+    let title = process_title(process);
+    let is_master = match title.strip_prefix("nginx: ") {
+        Some(rest) => rest.starts_with("master process"),
+        None => runs_instance_binary(process, instance),
+    };
+    is_master.then_some(pid)
+}
+
+/// Resource usage of the instance's process tree, found through its pid file
+pub fn process_metrics(instance: &NginxInstance) -> ProcessMetrics {
+    let mut sys = System::new_all();
+    sys.refresh_all();
+
+    let master = match find_master(&sys, instance) {
+        Some(master) => master,
+        None => return ProcessMetrics::default(),
+    };
+
+    let tree = process_tree(&sys, master);
+    let count = |role: ProcessRole| tree.iter().filter(|process| process.role == role).count();
+    ProcessMetrics {
+        master_pid: Some(master.as_u32()),
+        cpu_usage: tree.iter().map(|process| process.cpu_usage).sum(),
+        memory: tree.iter().map(|process| process.memory).sum(),
+        processes: tree.len(),
+        workers: count(ProcessRole::Worker),
+        cache_managers: count(ProcessRole::CacheManager),
+        cache_loaders: count(ProcessRole::CacheLoader),
+    }
+}
+
+pub fn collect_health_snapshot(instance: &NginxInstance) -> HealthSnapshot {
+    let status = nginx_status(instance);
+    if status == "active" {
+        clear_instance_alert(&instance.id, "nginx_inactive");
+    } else {
+        raise_instance_alert(
+            &instance.id,
+            "nginx_inactive",
+            AlertSeverity::Critical,
+            &format!("nginx is {}", status),
+        );
+    }
+
+    let mut sys = System::new_all();
+    sys.refresh_all();
+
+    let master = find_master(&sys, instance).and_then(|pid| sys.process(pid));
+
+    let mut master_pid = None;
+    let mut uptime_secs = None;
+    let mut worker_count = 0;
+    let mut last_reload = None;
+
+    if let Some(master) = master {
+        master_pid = Some(master.pid().as_u32());
+        uptime_secs = Some(master.run_time());
+
+        let worker_start_times: Vec<u64> = process_tree(&sys, master.pid())
+            .into_iter()
+            .filter(|process| process.role == ProcessRole::Worker)
+            .map(|process| process.started_at)
+            .collect();
+        worker_count = worker_start_times.len();
+
+        // Workers are replaced on every reload, so the newest one tells us when it happened
+        last_reload = worker_start_times
+            .into_iter()
+            .max()
+            .filter(|started| *started > master.start_time() + RELOAD_DETECTION_SLACK_SECS);
+    }
+
+    let config = last_config_check(&instance.id);
+
+    HealthSnapshot {
+        instance: instance.id.clone(),
+        timestamp: now_secs(),
+        status,
+        config_valid: config.as_ref().map(|c| c.valid),
+        config_message: config.map(|c| c.message),
+        master_pid,
+        uptime_secs,
+        worker_count,
+        last_reload,
+        alerts: alerts_for(&instance.id),
+    }
+}
+
+#[tauri::command]
+pub(crate) fn get_nginx_health(instance: Option<String>) -> Result<HealthSnapshot, String> {
+    Ok(collect_health_snapshot(&instances::get_instance(instance.as_deref())?))
+}
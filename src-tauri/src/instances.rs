@@ -0,0 +1,160 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// JSON list of instances; without it rustinx manages the single `nginx` on PATH
+const DEFAULT_INSTANCES_FILE: &str = "/etc/rustinx/instances.json";
+
+pub const DEFAULT_INSTANCE_ID: &str = "default";
+
+lazy_static::lazy_static! {
+    static ref INSTANCE_ID: Regex = Regex::new(r"^[A-Za-z0-9][A-Za-z0-9_-]*$").unwrap();
+}
+
+/// One nginx installation: its binary, where it runs from and the service that manages it.
+/// Anything left unset is taken from what the binary reports in `nginx -V`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NginxInstance {
+    pub id: String,
+    #[serde(default = "default_binary")]
+    pub binary: String,
+    /// Passed as `-p`; relative paths in the config resolve against it
+    pub prefix: Option<String>,
+    /// Passed as `-c`
+    pub config: Option<String>,
+    /// systemd unit on Linux; on macOS the same name (without `.service`) is the brew service
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    pub pid_file: Option<String>,
+    pub access_log: Option<String>,
+    pub error_log: Option<String>,
+}
+
+/// The `?instance=` parameter every route accepts; the first configured instance when omitted
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct InstanceQuery {
+    pub instance: Option<String>,
+}
+
+fn default_binary() -> String {
+    "nginx".to_string()
+}
+
+fn default_unit() -> String {
+    "nginx.service".to_string()
+}
+
+impl Default for NginxInstance {
+    fn default() -> Self {
+        NginxInstance {
+            id: DEFAULT_INSTANCE_ID.to_string(),
+            binary: default_binary(),
+            prefix: None,
+            config: None,
+            unit: default_unit(),
+            pid_file: None,
+            access_log: None,
+            error_log: None,
+        }
+    }
+}
+
+impl NginxInstance {
+    /// The binary with this instance's `-p` and `-c`, ready for `-t`, `-T`, `-V` or `-s`
+    pub fn command(&self) -> Command {
+        let mut command = self.command_without_config();
+        if let Some(config) = self.config_path() {
+            command.arg("-c").arg(config);
+        }
+        command
+    }
+
+    /// The binary with this instance's `-p` only, for checking a config other than its own
+    pub fn command_without_config(&self) -> Command {
+        let mut command = Command::new(&self.binary);
+        if let Some(prefix) = &self.prefix {
+            command.arg("-p").arg(prefix);
+        }
+        command
+    }
+
+    /// The configured `-c` path, made absolute against the prefix like nginx does
+    pub fn config_path(&self) -> Option<String> {
+        let config = self.config.as_ref()?;
+        match &self.prefix {
+            Some(prefix) if !config.starts_with('/') => Some(Path::new(prefix).join(config).to_string_lossy().to_string()),
+            _ => Some(config.clone()),
+        }
+    }
+
+    /// The name `systemctl` and `brew services` know the instance by
+    pub fn service_name(&self) -> &str {
+        self.unit.strip_suffix(".service").unwrap_or(&self.unit)
+    }
+}
+
+/// Whether `id` can name an instance, or anything else addressed the same way in URLs
+pub fn is_valid_id(id: &str) -> bool {
+    INSTANCE_ID.is_match(id)
+}
+
+fn instances_file() -> String {
+    std::env::var("RUSTINX_INSTANCES").unwrap_or_else(|_| DEFAULT_INSTANCES_FILE.to_string())
+}
+
+fn validate_instances(instances: &[NginxInstance]) -> Result<(), String> {
+    if instances.is_empty() {
+        return Err("no instances are defined".to_string());
+    }
+    let mut seen = HashSet::new();
+    for instance in instances {
+        if !is_valid_id(&instance.id) {
+            return Err(format!("invalid instance id \"{}\"", instance.id));
+        }
+        if !seen.insert(instance.id.as_str()) {
+            return Err(format!("instance id \"{}\" is used twice", instance.id));
+        }
+    }
+    Ok(())
+}
+
+/// Every configured instance, in file order. Falls back to the single default instance when the
+/// instances file is missing or unusable.
+pub fn list_instances() -> Vec<NginxInstance> {
+    let path = instances_file();
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return vec![NginxInstance::default()],
+    };
+
+    match serde_json::from_str::<Vec<NginxInstance>>(&content)
+        .map_err(|e| e.to_string())
+        .and_then(|instances| validate_instances(&instances).map(|_| instances))
+    {
+        Ok(instances) => instances,
+        Err(e) => {
+            eprintln!("Ignoring {}: {}", path, e);
+            vec![NginxInstance::default()]
+        }
+    }
+}
+
+/// Looks an instance up by id; `None` or an empty id picks the first one
+pub fn get_instance(id: Option<&str>) -> Result<NginxInstance, String> {
+    let instances = list_instances();
+    match id.filter(|id| !id.is_empty()) {
+        None => Ok(instances.into_iter().next().unwrap_or_default()),
+        Some(id) => instances
+            .into_iter()
+            .find(|instance| instance.id == id)
+            .ok_or_else(|| format!("Unknown nginx instance: {}", id)),
+    }
+}
+
+#[tauri::command]
+pub(crate) fn get_instances() -> Vec<NginxInstance> {
+    list_instances()
+}
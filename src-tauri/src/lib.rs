@@ -1,8 +1,29 @@
+pub mod acme;
 pub mod auth;
 pub mod actix_routes;
 pub mod commands;
 pub mod config;
+pub mod config_format;
+pub mod config_watcher;
+pub mod deploy;
+pub mod effective_config;
+pub mod event_bus;
 pub mod events_service;
+pub mod fleet;
+pub mod fs_watch;
+pub mod health;
+pub mod instances;
+pub mod lint;
+pub mod log_export;
+pub mod log_search;
 pub mod logging;
+pub mod nginx_build;
+pub mod nginx_config;
+pub mod rollout;
+pub mod route_resolver;
 pub mod systemd;
-pub mod util;
\ No newline at end of file
+pub mod templates;
+pub mod tls;
+pub mod upstreams;
+pub mod util;
+pub mod vhosts;
\ No newline at end of file
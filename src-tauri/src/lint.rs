@@ -0,0 +1,477 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::instances::{self, NginxInstance};
+use crate::nginx_config::{self, Directive, ParsedConfig};
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A lint rule; `check` sees the whole expanded config and reports findings at file:line
+pub struct LintRule {
+    pub id: &'static str,
+    pub severity: LintSeverity,
+    pub description: &'static str,
+    check: fn(&[Directive], &mut Vec<Finding>),
+}
+
+/// What a rule found, before the rule id and severity are attached
+struct Finding {
+    file: String,
+    line: usize,
+    message: String,
+}
+
+impl Finding {
+    fn at(directive: &Directive, message: String) -> Self {
+        Finding { file: directive.file.clone(), line: directive.line, message }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LintFinding {
+    pub rule: &'static str,
+    pub severity: LintSeverity,
+    pub message: String,
+    pub file: String,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RuleInfo {
+    pub id: &'static str,
+    pub severity: LintSeverity,
+    pub description: &'static str,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LintReport {
+    pub root: String,
+    pub findings: Vec<LintFinding>,
+    pub rules: Vec<RuleInfo>,
+}
+
+pub const RULES: &[LintRule] = &[
+    LintRule {
+        id: "server-tokens",
+        severity: LintSeverity::Warning,
+        description: "server_tokens is on, so error pages and the Server header reveal the nginx version",
+        check: check_server_tokens,
+    },
+    LintRule {
+        id: "missing-hsts",
+        severity: LintSeverity::Warning,
+        description: "TLS server without a Strict-Transport-Security header",
+        check: check_hsts,
+    },
+    LintRule {
+        id: "weak-tls-protocols",
+        severity: LintSeverity::Error,
+        description: "ssl_protocols allows SSLv3, TLSv1 or TLSv1.1",
+        check: check_tls_protocols,
+    },
+    LintRule {
+        id: "autoindex",
+        severity: LintSeverity::Warning,
+        description: "autoindex on lists directory contents to anyone",
+        check: check_autoindex,
+    },
+    LintRule {
+        id: "proxy-host-header",
+        severity: LintSeverity::Warning,
+        description: "proxy_pass without proxy_set_header Host, so the backend sees the upstream name",
+        check: check_proxy_host_header,
+    },
+    LintRule {
+        id: "duplicate-server-name",
+        severity: LintSeverity::Error,
+        description: "Two servers share a listen address and server_name; nginx ignores the second",
+        check: check_duplicate_server_names,
+    },
+    LintRule {
+        id: "root-in-location",
+        severity: LintSeverity::Info,
+        description: "root set inside a location; set it on the server so every location shares it",
+        check: check_root_in_location,
+    },
+    LintRule {
+        id: "if-in-location",
+        severity: LintSeverity::Warning,
+        description: "if inside a location only reliably supports return and rewrite ... last",
+        check: check_if_misuse,
+    },
+];
+
+/// Servers in `http` together with the `http` block they inherit from
+fn servers(tree: &[Directive]) -> Vec<(&Directive, &Directive)> {
+    let mut servers = Vec::new();
+    nginx_config::walk(tree, &mut |directive, parents| {
+        if directive.name == "server" {
+            if let Some(http) = parents.last().filter(|parent| parent.name == "http") {
+                servers.push((directive, *http));
+            }
+        }
+    });
+    servers
+}
+
+fn children_named<'a>(block: &'a Directive, name: &'a str) -> impl Iterator<Item = &'a Directive> + 'a {
+    block.children().iter().filter(move |child| child.name == name)
+}
+
+/// For array directives like `add_header` and `proxy_set_header`: the innermost level that sets
+/// any of them wins outright, the outer ones are not merged in
+fn inherited<'a>(chain: &[&'a Directive], name: &str) -> Vec<&'a Directive> {
+    for block in chain.iter().rev() {
+        let found: Vec<&Directive> = block.children().iter().filter(|child| child.name == name).collect();
+        if !found.is_empty() {
+            return found;
+        }
+    }
+    Vec::new()
+}
+
+/// Whether any `listen` of the server has the `ssl` or `quic` parameter
+pub(crate) fn is_tls_server(server: &Directive) -> bool {
+    children_named(server, "listen").any(|listen| listen.args.iter().skip(1).any(|arg| arg == "ssl" || arg == "quic"))
+}
+
+fn check_server_tokens(tree: &[Directive], findings: &mut Vec<Finding>) {
+    nginx_config::walk(tree, &mut |directive, _| {
+        if directive.name == "server_tokens" && directive.arg(0) == Some("on") {
+            findings.push(Finding::at(directive, "server_tokens on exposes the nginx version".to_string()));
+        }
+    });
+
+    // It also defaults to on, so an http block that never turns it off leaks the version too
+    for http in tree.iter().filter(|directive| directive.name == "http") {
+        if children_named(http, "server_tokens").next().is_some() {
+            continue;
+        }
+        let exposed = servers(std::slice::from_ref(http))
+            .iter()
+            .any(|(server, _)| children_named(server, "server_tokens").next().is_none());
+        if exposed {
+            findings.push(Finding::at(http, "server_tokens is not set and defaults to on; add server_tokens off".to_string()));
+        }
+    }
+}
+
+fn check_hsts(tree: &[Directive], findings: &mut Vec<Finding>) {
+    for (server, http) in servers(tree) {
+        if !is_tls_server(server) {
+            continue;
+        }
+        let headers = inherited(&[http, server], "add_header");
+        let has_hsts = headers
+            .iter()
+            .any(|header| header.arg(0).is_some_and(|name| name.eq_ignore_ascii_case("Strict-Transport-Security")));
+        if !has_hsts {
+            let names = children_named(server, "server_name").flat_map(|d| d.args.clone()).collect::<Vec<_>>().join(" ");
+            findings.push(Finding::at(
+                server,
+                format!("TLS server {} sends no Strict-Transport-Security header", if names.is_empty() { "_" } else { &names }),
+            ));
+        }
+    }
+}
+
+fn check_tls_protocols(tree: &[Directive], findings: &mut Vec<Finding>) {
+    nginx_config::walk(tree, &mut |directive, _| {
+        if directive.name != "ssl_protocols" && directive.name != "proxy_ssl_protocols" {
+            return;
+        }
+        let weak: Vec<&str> = directive
+            .args
+            .iter()
+            .map(|arg| arg.as_str())
+            .filter(|arg| matches!(*arg, "SSLv2" | "SSLv3" | "TLSv1" | "TLSv1.1"))
+            .collect();
+        if !weak.is_empty() {
+            findings.push(Finding::at(directive, format!("{} allows {}", directive.name, weak.join(", "))));
+        }
+    });
+}
+
+fn check_autoindex(tree: &[Directive], findings: &mut Vec<Finding>) {
+    nginx_config::walk(tree, &mut |directive, _| {
+        if directive.name == "autoindex" && directive.arg(0) == Some("on") {
+            findings.push(Finding::at(directive, "autoindex on exposes directory listings".to_string()));
+        }
+    });
+}
+
+fn check_proxy_host_header(tree: &[Directive], findings: &mut Vec<Finding>) {
+    nginx_config::walk(tree, &mut |directive, parents| {
+        if directive.name != "proxy_pass" {
+            return;
+        }
+        // proxy_set_header can sit at any level from http down to the location holding proxy_pass
+        let chain: Vec<&Directive> = parents
+            .iter()
+            .copied()
+            .skip_while(|parent| parent.name != "http")
+            .collect();
+        let sets_host = inherited(&chain, "proxy_set_header")
+            .iter()
+            .any(|header| header.arg(0).is_some_and(|name| name.eq_ignore_ascii_case("Host")));
+        if !sets_host {
+            findings.push(Finding::at(
+                directive,
+                format!(
+                    "proxy_pass {} without proxy_set_header Host; the backend sees the proxy_pass host instead of the client's",
+                    directive.arg(0).unwrap_or_default()
+                ),
+            ));
+        }
+    });
+}
+
+/// `80`, `*:80` and `0.0.0.0:80` are the same socket to nginx
+fn normalize_listen(address: &str) -> String {
+    if address.starts_with("unix:") {
+        return address.to_string();
+    }
+    if address.chars().all(|c| c.is_ascii_digit()) {
+        return format!("*:{}", address);
+    }
+    if let Some(port) = address.strip_prefix("0.0.0.0:") {
+        return format!("*:{}", port);
+    }
+    if address.starts_with('[') || address.contains(':') {
+        return address.to_string();
+    }
+    // A bare host or IP listens on port 80
+    format!("{}:80", address)
+}
+
+fn check_duplicate_server_names(tree: &[Directive], findings: &mut Vec<Finding>) {
+    let mut seen: HashMap<(String, String), &Directive> = HashMap::new();
+    for (server, _) in servers(tree) {
+        let mut listens: Vec<String> = children_named(server, "listen").filter_map(|d| d.arg(0)).map(normalize_listen).collect();
+        if listens.is_empty() {
+            listens.push("*:80".to_string());
+        }
+        listens.dedup();
+        let names: Vec<String> = children_named(server, "server_name")
+            .flat_map(|d| d.args.iter())
+            .map(|name| name.to_ascii_lowercase())
+            .collect();
+
+        for listen in &listens {
+            for name in &names {
+                // "" is the default empty name, not a real host
+                if name.is_empty() || name == "\"\"" {
+                    continue;
+                }
+                match seen.get(&(listen.clone(), name.clone())) {
+                    Some(first) => findings.push(Finding::at(
+                        server,
+                        format!("server_name {} on {} is already used by the server at {}", name, listen, first.location()),
+                    )),
+                    None => {
+                        seen.insert((listen.clone(), name.clone()), server);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn check_root_in_location(tree: &[Directive], findings: &mut Vec<Finding>) {
+    nginx_config::walk(tree, &mut |directive, parents| {
+        if directive.name != "root" || parents.last().map(|parent| parent.name.as_str()) != Some("location") {
+            return;
+        }
+        let server = parents.iter().rev().find(|parent| parent.name == "server");
+        // Only worth flagging when the server has no root of its own for other locations to fall back on
+        if server.is_some_and(|server| children_named(server, "root").next().is_none()) {
+            findings.push(Finding::at(
+                directive,
+                "root inside location; locations without their own root fall back to the compiled-in html directory".to_string(),
+            ));
+        }
+    });
+}
+
+fn check_if_misuse(tree: &[Directive], findings: &mut Vec<Finding>) {
+    nginx_config::walk(tree, &mut |directive, parents| {
+        if directive.name != "if" {
+            return;
+        }
+
+        // File checks are what try_files is for, in any context
+        let file_test = directive
+            .args
+            .iter()
+            .any(|arg| matches!(arg.trim_start_matches('('), "-f" | "!-f" | "-e" | "!-e" | "-d" | "!-d"));
+        if file_test {
+            findings.push(Finding::at(directive, "if with a file test; use try_files instead".to_string()));
+            return;
+        }
+
+        if !parents.iter().any(|parent| parent.name == "location") {
+            return;
+        }
+        let unsafe_directives: Vec<&str> = directive
+            .children()
+            .iter()
+            .filter(|child| match child.name.as_str() {
+                "return" => false,
+                "rewrite" => !child.args.iter().any(|arg| arg == "last"),
+                _ => true,
+            })
+            .map(|child| child.name.as_str())
+            .collect();
+        if !unsafe_directives.is_empty() {
+            findings.push(Finding::at(
+                directive,
+                format!(
+                    "if inside location with {}; only return and rewrite ... last are safe there",
+                    unsafe_directives.join(", ")
+                ),
+            ));
+        }
+    });
+}
+
+/// Runs every rule over the expanded config
+pub fn lint_config(parsed: &ParsedConfig) -> Vec<LintFinding> {
+    let tree = parsed.expanded();
+    let mut findings: Vec<LintFinding> = Vec::new();
+    for rule in RULES {
+        let mut found = Vec::new();
+        (rule.check)(&tree, &mut found);
+        findings.extend(found.into_iter().map(|finding| LintFinding {
+            rule: rule.id,
+            severity: rule.severity,
+            message: finding.message,
+            file: finding.file,
+            line: finding.line,
+        }));
+    }
+    findings.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)).then(b.severity.cmp(&a.severity)));
+    findings
+}
+
+pub fn lint_report(instance: &NginxInstance) -> Result<LintReport, String> {
+    let root = nginx_config::find_main_config(instance).ok_or("Could not find nginx.conf")?;
+    let parsed = nginx_config::parse_config(&root);
+    Ok(LintReport {
+        findings: lint_config(&parsed),
+        rules: RULES
+            .iter()
+            .map(|rule| RuleInfo { id: rule.id, severity: rule.severity, description: rule.description })
+            .collect(),
+        root,
+    })
+}
+
+#[tauri::command]
+pub(crate) fn lint_nginx_config(instance: Option<String>) -> Result<LintReport, String> {
+    lint_report(&instances::get_instance(instance.as_deref())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nginx_config::ConfigFile;
+
+    fn parsed(content: &str) -> ParsedConfig {
+        ParsedConfig {
+            root: "nginx.conf".to_string(),
+            files: vec![ConfigFile {
+                path: "nginx.conf".to_string(),
+                directives: nginx_config::parse_config_str(content, "nginx.conf").unwrap(),
+            }],
+            errors: Vec::new(),
+            include_patterns: Vec::new(),
+        }
+    }
+
+    /// Lines `rule` flags in `content`
+    fn flagged(rule: &str, content: &str) -> Vec<usize> {
+        lint_config(&parsed(content))
+            .into_iter()
+            .filter(|finding| finding.rule == rule)
+            .map(|finding| finding.line)
+            .collect()
+    }
+
+    #[test]
+    fn server_tokens() {
+        assert_eq!(flagged("server-tokens", "http {\n    server_tokens on;\n    server { listen 80; }\n}\n"), vec![2]);
+        // Never set at all defaults to on
+        assert_eq!(flagged("server-tokens", "http {\n    server { listen 80; }\n}\n"), vec![1]);
+        assert!(flagged("server-tokens", "http {\n    server_tokens off;\n    server { listen 80; }\n}\n").is_empty());
+    }
+
+    #[test]
+    fn missing_hsts() {
+        let config = "http {\n    server {\n        listen 443 ssl;\n        server_name example.com;\n    }\n}\n";
+        assert_eq!(flagged("missing-hsts", config), vec![2]);
+
+        let config = "http {\n    add_header Strict-Transport-Security \"max-age=31536000\" always;\n    server {\n        listen 443 ssl;\n    }\n    server {\n        listen 80;\n    }\n}\n";
+        assert!(flagged("missing-hsts", config).is_empty());
+
+        // A server-level add_header replaces the inherited ones, HSTS included
+        let config = "http {\n    add_header Strict-Transport-Security \"max-age=31536000\";\n    server {\n        listen 443 ssl;\n        add_header X-Frame-Options DENY;\n    }\n}\n";
+        assert_eq!(flagged("missing-hsts", config), vec![3]);
+    }
+
+    #[test]
+    fn weak_tls_protocols() {
+        assert_eq!(flagged("weak-tls-protocols", "http {\n    ssl_protocols TLSv1 TLSv1.1 TLSv1.2;\n}\n"), vec![2]);
+        assert!(flagged("weak-tls-protocols", "http {\n    ssl_protocols TLSv1.2 TLSv1.3;\n}\n").is_empty());
+    }
+
+    #[test]
+    fn autoindex() {
+        assert_eq!(flagged("autoindex", "http {\n    server {\n        location /files/ {\n            autoindex on;\n        }\n    }\n}\n"), vec![4]);
+        assert!(flagged("autoindex", "http {\n    server {\n        autoindex off;\n    }\n}\n").is_empty());
+    }
+
+    #[test]
+    fn proxy_host_header() {
+        let config = "http {\n    server {\n        location / {\n            proxy_pass http://backend;\n        }\n    }\n}\n";
+        assert_eq!(flagged("proxy-host-header", config), vec![4]);
+
+        let config = "http {\n    proxy_set_header Host $host;\n    server {\n        location / {\n            proxy_pass http://backend;\n        }\n    }\n}\n";
+        assert!(flagged("proxy-host-header", config).is_empty());
+    }
+
+    #[test]
+    fn duplicate_server_name() {
+        let config = "http {\n    server {\n        listen 80;\n        server_name example.com;\n    }\n    server {\n        listen 0.0.0.0:80;\n        server_name Example.com;\n    }\n}\n";
+        assert_eq!(flagged("duplicate-server-name", config), vec![6]);
+
+        let config = "http {\n    server {\n        listen 80;\n        server_name example.com;\n    }\n    server {\n        listen 443 ssl;\n        server_name example.com;\n    }\n}\n";
+        assert!(flagged("duplicate-server-name", config).is_empty());
+    }
+
+    #[test]
+    fn root_in_location() {
+        let config = "http {\n    server {\n        location /static/ {\n            root /srv/static;\n        }\n    }\n}\n";
+        assert_eq!(flagged("root-in-location", config), vec![4]);
+
+        let config = "http {\n    server {\n        root /srv/www;\n        location /static/ {\n            root /srv/static;\n        }\n    }\n}\n";
+        assert!(flagged("root-in-location", config).is_empty());
+    }
+
+    #[test]
+    fn if_in_location() {
+        let config = "http {\n    server {\n        location / {\n            if ($http_user_agent ~ bot) {\n                add_header X-Bot 1;\n            }\n        }\n    }\n}\n";
+        assert_eq!(flagged("if-in-location", config), vec![4]);
+
+        let config = "http {\n    server {\n        if (!-f $request_filename) {\n            return 404;\n        }\n    }\n}\n";
+        assert_eq!(flagged("if-in-location", config), vec![3]);
+
+        let config = "http {\n    server {\n        location / {\n            if ($request_method = POST) {\n                return 405;\n            }\n        }\n    }\n}\n";
+        assert!(flagged("if-in-location", config).is_empty());
+    }
+}
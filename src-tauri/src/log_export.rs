@@ -0,0 +1,410 @@
+use actix_web::web;
+use chrono::{TimeZone, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures_util::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use tokio::sync::mpsc;
+
+use crate::actix_routes::find_nginx_log_path;
+use crate::instances::{self, NginxInstance};
+use crate::log_search::{self, CompiledFilter, Direction, LogEntry, LogFilter, LogSearchError};
+use crate::systemd::{self, JournalEntry, JournalQuery};
+
+// Entries fetched per search call while walking a log from start to end
+const EXPORT_PAGE_SIZE: usize = 5_000;
+
+// Bytes buffered before a chunk is handed to the HTTP response
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+// Chunks queued ahead of a slow client before the exporter blocks
+const STREAM_QUEUE_DEPTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportSource {
+    #[default]
+    Access,
+    Error,
+    Journal,
+}
+
+impl ExportSource {
+    fn name(&self) -> &'static str {
+        match self {
+            ExportSource::Access => "access",
+            ExportSource::Error => "error",
+            ExportSource::Journal => "journal",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    /// A single JSON array
+    #[default]
+    Json,
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+/// What to export and how; the records themselves are selected with a `LogFilter`
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ExportOptions {
+    #[serde(rename = "type", default)]
+    pub source: ExportSource,
+    #[serde(default)]
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub gzip: bool,
+}
+
+impl ExportOptions {
+    pub fn content_type(&self) -> &'static str {
+        if self.gzip {
+            "application/gzip"
+        } else {
+            self.format.content_type()
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        let stamp = Utc::now().format("%Y%m%d-%H%M%S");
+        let mut name = format!("nginx-{}-{}.{}", self.source.name(), stamp, self.format.extension());
+        if self.gzip {
+            name.push_str(".gz");
+        }
+        name
+    }
+}
+
+/// One exported log record, the same shape for every source
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportRecord {
+    pub source: &'static str,
+    /// Unix seconds
+    pub timestamp: Option<i64>,
+    /// The same instant as RFC 3339 in UTC, for spreadsheets and humans
+    pub time: Option<String>,
+    pub status: Option<u16>,
+    pub level: Option<String>,
+    pub file: Option<String>,
+    pub message: String,
+}
+
+impl ExportRecord {
+    fn from_entry(source: ExportSource, entry: LogEntry) -> Self {
+        ExportRecord {
+            source: source.name(),
+            timestamp: entry.timestamp,
+            time: entry
+                .timestamp
+                .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+                .map(|time| time.to_rfc3339()),
+            status: entry.status,
+            level: entry.level,
+            file: Some(entry.file),
+            message: entry.line,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportSummary {
+    pub records: usize,
+    pub path: String,
+}
+
+enum Output<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Plain(out) => out.write(buf),
+            Output::Gzip(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Plain(out) => out.flush(),
+            Output::Gzip(out) => out.flush(),
+        }
+    }
+}
+
+impl<W: Write> Output<W> {
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Output::Plain(mut out) => {
+                out.flush()?;
+                Ok(out)
+            }
+            Output::Gzip(out) => {
+                let mut out = out.finish()?;
+                out.flush()?;
+                Ok(out)
+            }
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Serializes records one at a time in the requested format
+struct RecordWriter<W: Write> {
+    out: Output<W>,
+    format: ExportFormat,
+    records: usize,
+}
+
+impl<W: Write> RecordWriter<W> {
+    fn new(out: W, format: ExportFormat, gzip: bool) -> io::Result<Self> {
+        let out = if gzip {
+            Output::Gzip(GzEncoder::new(out, Compression::default()))
+        } else {
+            Output::Plain(out)
+        };
+        let mut writer = RecordWriter { out, format, records: 0 };
+        match format {
+            ExportFormat::Csv => writer.out.write_all(b"time,timestamp,source,status,level,file,message\n")?,
+            ExportFormat::Json => writer.out.write_all(b"[")?,
+            ExportFormat::Ndjson => {}
+        }
+        Ok(writer)
+    }
+
+    fn write_record(&mut self, record: &ExportRecord) -> io::Result<()> {
+        match self.format {
+            ExportFormat::Csv => {
+                let row = [
+                    csv_field(record.time.as_deref().unwrap_or("")),
+                    record.timestamp.map(|t| t.to_string()).unwrap_or_default(),
+                    record.source.to_string(),
+                    record.status.map(|s| s.to_string()).unwrap_or_default(),
+                    csv_field(record.level.as_deref().unwrap_or("")),
+                    csv_field(record.file.as_deref().unwrap_or("")),
+                    csv_field(&record.message),
+                ];
+                writeln!(self.out, "{}", row.join(","))?;
+            }
+            ExportFormat::Json => {
+                if self.records > 0 {
+                    self.out.write_all(b",")?;
+                }
+                self.out.write_all(b"\n")?;
+                serde_json::to_writer(&mut self.out, record)?;
+            }
+            ExportFormat::Ndjson => {
+                serde_json::to_writer(&mut self.out, record)?;
+                self.out.write_all(b"\n")?;
+            }
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<(W, usize)> {
+        if self.format == ExportFormat::Json {
+            self.out.write_all(b"\n]\n")?;
+        }
+        let records = self.records;
+        Ok((self.out.finish()?, records))
+    }
+}
+
+fn export_log_file<W: Write>(
+    source: ExportSource,
+    path: &Path,
+    filter: &CompiledFilter,
+    writer: &mut RecordWriter<W>,
+) -> io::Result<()> {
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = log_search::search_log_file(path, filter, cursor.as_deref(), Direction::Forward, EXPORT_PAGE_SIZE)
+            .map_err(io::Error::other)?;
+        for entry in page.entries {
+            writer.write_record(&ExportRecord::from_entry(source, entry))?;
+        }
+        // `after` stops moving at the end of the live file and disappears past the `until` bound
+        if page.after.is_none() || page.after == cursor {
+            return Ok(());
+        }
+        cursor = page.after;
+    }
+}
+
+/// Journal priorities under the level names nginx uses, so one level filter works for both
+fn journal_level(entry: &JournalEntry) -> Option<String> {
+    let name = match entry.priority_name()? {
+        "err" => "error",
+        "warning" => "warn",
+        other => other,
+    };
+    Some(name.to_string())
+}
+
+fn export_journal<W: Write>(unit: &str, filter: &CompiledFilter, writer: &mut RecordWriter<W>) -> io::Result<()> {
+    let (since, until) = filter.time_range();
+    let query = JournalQuery {
+        unit: unit.to_string(),
+        since,
+        until,
+        ..Default::default()
+    };
+
+    let mut result = Ok(());
+    systemd::read_journal(&query, None, |journal_entry| {
+        let entry = LogEntry {
+            level: journal_level(&journal_entry),
+            timestamp: journal_entry.timestamp_secs(),
+            status: None,
+            file: journal_entry.unit.clone().unwrap_or_else(|| unit.to_string()),
+            line: journal_entry.message,
+        };
+        if !filter.matches(&entry) {
+            return true;
+        }
+        result = writer.write_record(&ExportRecord::from_entry(ExportSource::Journal, entry));
+        result.is_ok()
+    })
+    .map_err(io::Error::other)?;
+    result
+}
+
+/// A validated export: the compiled filter and the log file to read (`None` for the journal of `unit`)
+pub struct PreparedExport {
+    filter: CompiledFilter,
+    path: Option<String>,
+    unit: String,
+}
+
+/// Checks the request before anything is streamed, so problems can still become a proper error response
+pub fn prepare_export(
+    instance: &NginxInstance,
+    options: &ExportOptions,
+    filter: &LogFilter,
+) -> Result<PreparedExport, LogSearchError> {
+    let filter = filter.compile().map_err(LogSearchError::InvalidRequest)?;
+    let path = match options.source {
+        ExportSource::Access | ExportSource::Error => {
+            Some(find_nginx_log_path(instance, options.source.name()).map_err(LogSearchError::Failed)?)
+        }
+        ExportSource::Journal if cfg!(target_os = "linux") => None,
+        ExportSource::Journal => {
+            return Err(LogSearchError::InvalidRequest("Journal export is only available on Linux".to_string()))
+        }
+    };
+    Ok(PreparedExport { filter, path, unit: instance.unit.clone() })
+}
+
+/// Writes every matching record to `out`; returns the output and the number of records written
+pub fn export_logs<W: Write>(options: &ExportOptions, prepared: &PreparedExport, out: W) -> io::Result<(W, usize)> {
+    let mut writer = RecordWriter::new(out, options.format, options.gzip)?;
+    match &prepared.path {
+        Some(path) => export_log_file(options.source, Path::new(path), &prepared.filter, &mut writer)?,
+        None => export_journal(&prepared.unit, &prepared.filter, &mut writer)?,
+    }
+    writer.finish()
+}
+
+/// Hands fixed-size chunks to the response stream; fails once the client has gone away
+struct ChannelWriter {
+    sender: mpsc::Sender<io::Result<web::Bytes>>,
+    buffer: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn send_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = web::Bytes::from(std::mem::take(&mut self.buffer));
+        self.sender
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "export client disconnected"))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= STREAM_CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffer()
+    }
+}
+
+/// Runs the export on a blocking thread and yields it as response body chunks. A slow client
+/// holds the exporter back instead of letting chunks pile up in memory.
+pub fn export_stream(
+    options: ExportOptions,
+    prepared: PreparedExport,
+) -> impl Stream<Item = io::Result<web::Bytes>> {
+    let (sender, receiver) = mpsc::channel(STREAM_QUEUE_DEPTH);
+    actix_web::rt::task::spawn_blocking(move || {
+        let out = ChannelWriter { sender: sender.clone(), buffer: Vec::new() };
+        if let Err(e) = export_logs(&options, &prepared, out) {
+            eprintln!("Log export failed: {}", e);
+            // Abort the response rather than end it cleanly, so a truncated export isn't mistaken for a full one
+            let _ = sender.blocking_send(Err(e));
+        }
+    });
+
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    })
+}
+
+/// Desktop counterpart of `/api/nginx/logs/export`: writes the export to `destination`
+#[tauri::command]
+pub(crate) fn export_nginx_logs(
+    instance: Option<String>,
+    options: ExportOptions,
+    filter: LogFilter,
+    destination: String,
+) -> Result<ExportSummary, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+    let prepared = prepare_export(&instance, &options, &filter).map_err(|e| e.to_string())?;
+    let file = File::create(&destination).map_err(|e| format!("Failed to create {}: {}", destination, e))?;
+    let (_, records) = export_logs(&options, &prepared, BufWriter::new(file))
+        .map_err(|e| format!("Failed to export logs: {}", e))?;
+    Ok(ExportSummary { records, path: destination })
+}
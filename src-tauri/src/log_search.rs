@@ -0,0 +1,745 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use flate2::read::GzDecoder;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::actix_routes::find_nginx_log_path;
+use crate::instances::{self, NginxInstance};
+
+// Bytes read per step when walking a file backwards
+const REVERSE_CHUNK_SIZE: u64 = 64 * 1024;
+
+// Rotated siblings (`access.log.1`, `access.log.2.gz`, ...) looked at beyond the live file
+const MAX_ROTATIONS: usize = 30;
+
+// Lines examined per request; a search that hits this returns a cursor to continue from
+const MAX_SCANNED_LINES: usize = 200_000;
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 5_000;
+
+// Most to least severe, as used by `error_log`
+const ERROR_LEVELS: [&str; 8] = ["emerg", "alert", "crit", "error", "warn", "notice", "info", "debug"];
+
+lazy_static::lazy_static! {
+    // `[10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.1" 200` as written by the combined and common formats
+    static ref ACCESS_LINE: Regex =
+        Regex::new(r#"\[(\d{2}/\w{3}/\d{4}:\d{2}:\d{2}:\d{2} [+-]\d{4})\] "(?:[^"\\]|\\.)*" (\d{3}) "#).unwrap();
+    // `2024/01/15 10:30:00 [error] 1234#0: ...`
+    static ref ERROR_LINE: Regex =
+        Regex::new(r"^(\d{4}/\d{2}/\d{2} \d{2}:\d{2}:\d{2}) \[(\w+)\]").unwrap();
+    static ref RELATIVE_TIME: Regex = Regex::new(r"^-(\d{1,9})([smhd])$").unwrap();
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// From the cursor (or the end of the live file) towards older entries
+    #[default]
+    Backward,
+    /// From the cursor (or the start of the oldest rotation) towards newer entries
+    Forward,
+}
+
+/// Filters shared by log search and export. Everything is optional; an empty filter matches every line.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct LogFilter {
+    /// Substring to look for, or a regular expression when `regex` is set
+    pub query: Option<String>,
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// RFC 3339 timestamp or unix seconds
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Access logs: comma separated codes, classes and ranges such as `404,5xx,300-399`
+    pub status: Option<String>,
+    /// Error logs: comma separated levels; a trailing `+` includes everything more severe, e.g. `warn+`
+    pub level: Option<String>,
+}
+
+/// Query accepted by `/api/nginx/logs/search` and the `search_nginx_logs` command.
+/// The filter fields are spelled out rather than flattened so they parse from a query string.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct LogSearchRequest {
+    /// `access` or `error`
+    #[serde(rename = "type", default = "default_log_type")]
+    pub log_type: String,
+    pub query: Option<String>,
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub status: Option<String>,
+    pub level: Option<String>,
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub direction: Direction,
+    pub limit: Option<usize>,
+}
+
+impl LogSearchRequest {
+    pub fn filter(&self) -> LogFilter {
+        LogFilter {
+            query: self.query.clone(),
+            regex: self.regex,
+            case_sensitive: self.case_sensitive,
+            since: self.since.clone(),
+            until: self.until.clone(),
+            status: self.status.clone(),
+            level: self.level.clone(),
+        }
+    }
+}
+
+fn default_log_type() -> String {
+    "access".to_string()
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LogEntry {
+    pub line: String,
+    /// Unix seconds parsed from the line, if it has a recognizable timestamp
+    pub timestamp: Option<i64>,
+    pub status: Option<u16>,
+    pub level: Option<String>,
+    pub file: String,
+}
+
+/// One page of results in file order (oldest first), whichever direction was searched
+#[derive(Clone, Debug, Serialize)]
+pub struct LogPage {
+    pub entries: Vec<LogEntry>,
+    /// Continue with `direction=backward` from here for older entries; `None` once there are none
+    pub before: Option<String>,
+    /// Continue with `direction=forward` from here for newer entries; at the end of the live file this
+    /// stays put so it can be polled, and it is `None` once entries are past the `until` bound
+    pub after: Option<String>,
+    pub scanned: usize,
+    pub files: Vec<String>,
+}
+
+/// A line boundary: byte offset within a rotation (0 is the live file, 1 is `.1`, ...).
+/// Cursors are only meaningful until the next rotation shifts the files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct LogCursor {
+    rotation: usize,
+    offset: u64,
+}
+
+impl LogCursor {
+    fn parse(value: &str) -> Result<Self, String> {
+        let (rotation, offset) = value
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid cursor '{}'", value))?;
+        Ok(LogCursor {
+            rotation: rotation.parse().map_err(|_| format!("Invalid cursor '{}'", value))?,
+            offset: offset.parse().map_err(|_| format!("Invalid cursor '{}'", value))?,
+        })
+    }
+
+    fn encode(&self) -> String {
+        format!("{}:{}", self.rotation, self.offset)
+    }
+}
+
+enum StatusRule {
+    Exact(u16),
+    Range(u16, u16),
+}
+
+enum TextMatcher {
+    Substring { needle: String, case_sensitive: bool },
+    Pattern(Regex),
+}
+
+/// A validated `LogFilter`, ready to be applied to lines
+pub struct CompiledFilter {
+    text: Option<TextMatcher>,
+    since: Option<i64>,
+    until: Option<i64>,
+    statuses: Vec<StatusRule>,
+    levels: Vec<String>,
+}
+
+/// Accepts unix seconds, RFC 3339, a local `YYYY-MM-DD[ HH:MM[:SS]]`, `now`, `today`, `yesterday`
+/// and relative offsets such as `-15m` or `-2d`
+pub(crate) fn parse_time_bound(value: &str) -> Result<i64, String> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<i64>() {
+        return Ok(secs);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.timestamp());
+    }
+
+    let now = Local::now();
+    let midnight = |days_back: i64| {
+        let date = now.date_naive() - chrono::Duration::days(days_back);
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+            .map(|time| time.timestamp())
+    };
+    match value {
+        "now" => return Ok(now.timestamp()),
+        "today" => return midnight(0).ok_or_else(|| format!("Invalid time '{}'", value)),
+        "yesterday" => return midnight(1).ok_or_else(|| format!("Invalid time '{}'", value)),
+        _ => {}
+    }
+    if let Some(captures) = RELATIVE_TIME.captures(value) {
+        let amount: i64 = captures[1].parse().map_err(|_| format!("Invalid time '{}'", value))?;
+        let unit = match &captures[2] {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            _ => 86400,
+        };
+        return Ok(now.timestamp() - amount.saturating_mul(unit));
+    }
+
+    // `datetime-local` inputs send local time without an offset
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, format) {
+            if let Some(time) = Local.from_local_datetime(&naive).earliest() {
+                return Ok(time.timestamp());
+            }
+        }
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        if let Some(time) = date.and_hms_opt(0, 0, 0).and_then(|naive| Local.from_local_datetime(&naive).earliest()) {
+            return Ok(time.timestamp());
+        }
+    }
+    Err(format!(
+        "Invalid time '{}': expected unix seconds, RFC 3339, YYYY-MM-DD[ HH:MM[:SS]], now, today, yesterday or an offset like -15m",
+        value
+    ))
+}
+
+fn parse_status_rule(rule: &str) -> Result<StatusRule, String> {
+    let invalid = || format!("Invalid status filter '{}'", rule);
+    let lower = rule.to_ascii_lowercase();
+    if let Some(class) = lower.strip_suffix("xx") {
+        let class: u16 = class.parse().map_err(|_| invalid())?;
+        if !(1..=5).contains(&class) {
+            return Err(invalid());
+        }
+        return Ok(StatusRule::Range(class * 100, class * 100 + 99));
+    }
+    if let Some((low, high)) = lower.split_once('-') {
+        let low: u16 = low.trim().parse().map_err(|_| invalid())?;
+        let high: u16 = high.trim().parse().map_err(|_| invalid())?;
+        if low > high {
+            return Err(invalid());
+        }
+        return Ok(StatusRule::Range(low, high));
+    }
+    lower.parse().map(StatusRule::Exact).map_err(|_| invalid())
+}
+
+fn parse_level_rule(rule: &str) -> Result<Vec<String>, String> {
+    let lower = rule.to_ascii_lowercase();
+    let (name, and_above) = match lower.strip_suffix('+') {
+        Some(name) => (name, true),
+        None => (lower.as_str(), false),
+    };
+    let name = if name == "warning" { "warn" } else { name };
+    let index = ERROR_LEVELS
+        .iter()
+        .position(|level| *level == name)
+        .ok_or_else(|| format!("Invalid level '{}': expected one of {}", rule, ERROR_LEVELS.join(", ")))?;
+
+    if and_above {
+        Ok(ERROR_LEVELS[..=index].iter().map(|l| l.to_string()).collect())
+    } else {
+        Ok(vec![name.to_string()])
+    }
+}
+
+fn split_list(value: &Option<String>) -> Vec<&str> {
+    value
+        .as_deref()
+        .map(|v| v.split(',').map(str::trim).filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+impl LogFilter {
+    /// Validates the filter; the error is meant for the caller (a 400, not a 500)
+    pub fn compile(&self) -> Result<CompiledFilter, String> {
+        let text = match self.query.as_deref().filter(|q| !q.is_empty()) {
+            None => None,
+            Some(query) if self.regex => Some(TextMatcher::Pattern(
+                RegexBuilder::new(query)
+                    .case_insensitive(!self.case_sensitive)
+                    .size_limit(1 << 20)
+                    .build()
+                    .map_err(|e| format!("Invalid regex: {}", e))?,
+            )),
+            Some(query) => Some(TextMatcher::Substring {
+                needle: if self.case_sensitive { query.to_string() } else { query.to_lowercase() },
+                case_sensitive: self.case_sensitive,
+            }),
+        };
+
+        let since = self.since.as_deref().filter(|s| !s.trim().is_empty()).map(parse_time_bound).transpose()?;
+        let until = self.until.as_deref().filter(|s| !s.trim().is_empty()).map(parse_time_bound).transpose()?;
+
+        let statuses = split_list(&self.status)
+            .into_iter()
+            .map(parse_status_rule)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut levels = Vec::new();
+        for rule in split_list(&self.level) {
+            levels.extend(parse_level_rule(rule)?);
+        }
+
+        Ok(CompiledFilter { text, since, until, statuses, levels })
+    }
+}
+
+impl CompiledFilter {
+    pub(crate) fn matches_text(&self, line: &str) -> bool {
+        match &self.text {
+            None => true,
+            Some(TextMatcher::Pattern(pattern)) => pattern.is_match(line),
+            Some(TextMatcher::Substring { needle, case_sensitive: true }) => line.contains(needle.as_str()),
+            Some(TextMatcher::Substring { needle, case_sensitive: false }) => {
+                line.to_lowercase().contains(needle.as_str())
+            }
+        }
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if self.since.is_some() || self.until.is_some() {
+            // Lines without a timestamp (continuations, startup noise) can't be placed in the range
+            let timestamp = match entry.timestamp {
+                Some(timestamp) => timestamp,
+                None => return false,
+            };
+            if self.since.is_some_and(|since| timestamp < since) || self.until.is_some_and(|until| timestamp > until) {
+                return false;
+            }
+        }
+        if !self.statuses.is_empty() {
+            let status = match entry.status {
+                Some(status) => status,
+                None => return false,
+            };
+            let matched = self.statuses.iter().any(|rule| match rule {
+                StatusRule::Exact(code) => status == *code,
+                StatusRule::Range(low, high) => (*low..=*high).contains(&status),
+            });
+            if !matched {
+                return false;
+            }
+        }
+        if !self.levels.is_empty() && entry.level.as_ref().filter(|level| self.levels.contains(level)).is_none() {
+            return false;
+        }
+        self.matches_text(&entry.line)
+    }
+
+    /// The `since`/`until` bounds in unix seconds, for sources that can narrow the range themselves
+    pub fn time_range(&self) -> (Option<i64>, Option<i64>) {
+        (self.since, self.until)
+    }
+
+    /// Searching backwards, everything past this entry is older than `since`
+    fn is_before_range(&self, entry: &LogEntry) -> bool {
+        matches!((self.since, entry.timestamp), (Some(since), Some(timestamp)) if timestamp < since)
+    }
+
+    /// Searching forwards, everything past this entry is newer than `until`
+    fn is_after_range(&self, entry: &LogEntry) -> bool {
+        matches!((self.until, entry.timestamp), (Some(until), Some(timestamp)) if timestamp > until)
+    }
+}
+
+/// Extracts timestamp, status and level from an access or error log line
+pub fn parse_log_line(line: &str, file: &str) -> LogEntry {
+    let mut entry = LogEntry {
+        line: line.to_string(),
+        timestamp: None,
+        status: None,
+        level: None,
+        file: file.to_string(),
+    };
+
+    if let Some(captures) = ERROR_LINE.captures(line) {
+        entry.timestamp = NaiveDateTime::parse_from_str(&captures[1], "%Y/%m/%d %H:%M:%S")
+            .ok()
+            .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+            .map(|time| time.timestamp());
+        entry.level = Some(captures[2].to_string());
+    } else if let Some(captures) = ACCESS_LINE.captures(line) {
+        entry.timestamp = DateTime::parse_from_str(&captures[1], "%d/%b/%Y:%H:%M:%S %z")
+            .ok()
+            .map(|time| time.timestamp());
+        entry.status = captures[2].parse().ok();
+    }
+
+    entry
+}
+
+/// The live log and its rotated siblings that exist on disk, newest first, keyed by rotation number
+pub fn log_rotations(path: &Path) -> Vec<(usize, PathBuf)> {
+    let mut rotations = Vec::new();
+    if path.exists() {
+        rotations.push((0, path.to_path_buf()));
+    }
+    for rotation in 1..=MAX_ROTATIONS {
+        let plain = PathBuf::from(format!("{}.{}", path.display(), rotation));
+        let compressed = PathBuf::from(format!("{}.{}.gz", path.display(), rotation));
+        if plain.exists() {
+            rotations.push((rotation, plain));
+        } else if compressed.exists() {
+            rotations.push((rotation, compressed));
+        } else {
+            break;
+        }
+    }
+    rotations
+}
+
+trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Opens a rotation for random access; gzip members are decompressed into memory first
+fn open_rotation(path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+    let file = File::open(path)?;
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        let mut content = Vec::new();
+        GzDecoder::new(file).read_to_end(&mut content)?;
+        Ok(Box::new(Cursor::new(content)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Yields lines from a byte offset towards the start of the file, with each line's start offset
+struct ReverseLineReader {
+    source: Box<dyn ReadSeek>,
+    /// Unread bytes held in memory, `[buffer_start, end)`
+    buffer: Vec<u8>,
+    buffer_start: u64,
+}
+
+impl ReverseLineReader {
+    fn new(source: Box<dyn ReadSeek>, end: u64) -> Self {
+        ReverseLineReader { source, buffer: Vec::new(), buffer_start: end }
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        loop {
+            // The buffer always ends where the next line to yield ends, terminator included
+            let content_end = if self.buffer.last() == Some(&b'\n') {
+                self.buffer.len() - 1
+            } else {
+                self.buffer.len()
+            };
+
+            if let Some(newline) = self.buffer[..content_end].iter().rposition(|b| *b == b'\n') {
+                let line = self.buffer[newline + 1..content_end].to_vec();
+                self.buffer.truncate(newline + 1);
+                return Ok(Some((self.buffer_start + newline as u64 + 1, line)));
+            }
+
+            if self.buffer_start == 0 {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                let line = self.buffer[..content_end].to_vec();
+                self.buffer.clear();
+                return Ok(Some((0, line)));
+            }
+
+            let read_start = self.buffer_start.saturating_sub(REVERSE_CHUNK_SIZE);
+            let mut chunk = vec![0; (self.buffer_start - read_start) as usize];
+            self.source.seek(SeekFrom::Start(read_start))?;
+            self.source.read_exact(&mut chunk)?;
+            chunk.extend_from_slice(&self.buffer);
+            self.buffer = chunk;
+            self.buffer_start = read_start;
+        }
+    }
+}
+
+fn ends_with_newline(source: &mut Box<dyn ReadSeek>, length: u64) -> io::Result<bool> {
+    if length == 0 {
+        return Ok(true);
+    }
+    let mut last = [0u8; 1];
+    source.seek(SeekFrom::Start(length - 1))?;
+    source.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+/// Reads `lines` most recent lines of a log, oldest first, without shelling out to `tail`
+pub fn read_log_tail(path: &str, lines: usize) -> Result<Vec<String>, String> {
+    let page = search_log_file(Path::new(path), &LogFilter::default().compile()?, None, Direction::Backward, lines)?;
+    Ok(page.entries.into_iter().map(|entry| entry.line).collect())
+}
+
+/// Searches a log and its rotations, one page at a time
+pub fn search_log_file(
+    path: &Path,
+    filter: &CompiledFilter,
+    cursor: Option<&str>,
+    direction: Direction,
+    limit: usize,
+) -> Result<LogPage, String> {
+    let cursor = cursor.filter(|c| !c.is_empty()).map(LogCursor::parse).transpose()?;
+    let limit = limit.clamp(1, MAX_LIMIT);
+    let rotations = log_rotations(path);
+    let files = rotations.iter().map(|(_, p)| p.display().to_string()).collect();
+
+    let mut page = match direction {
+        Direction::Backward => search_backward(&rotations, filter, cursor, limit),
+        Direction::Forward => search_forward(&rotations, filter, cursor, limit),
+    }
+    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    page.files = files;
+    Ok(page)
+}
+
+fn search_backward(
+    rotations: &[(usize, PathBuf)],
+    filter: &CompiledFilter,
+    cursor: Option<LogCursor>,
+    limit: usize,
+) -> io::Result<LogPage> {
+    let mut entries = Vec::new();
+    let mut scanned = 0;
+    let mut after = cursor;
+    let mut before = None;
+
+    let start_rotation = cursor.map_or(0, |c| c.rotation);
+    'rotations: for (rotation, path) in rotations.iter().filter(|(r, _)| *r >= start_rotation) {
+        let mut source = open_rotation(path)?;
+        let length = source.seek(SeekFrom::End(0))?;
+        let end = match cursor {
+            Some(c) if c.rotation == *rotation => c.offset.min(length),
+            _ => length,
+        };
+
+        let file = path.display().to_string();
+        let still_writing = *rotation == 0 && end == length && !ends_with_newline(&mut source, length)?;
+        let mut reader = ReverseLineReader::new(source, end);
+        if still_writing {
+            // Same as forward paging: a final line without its newline is left for later
+            if let Some((start, _)) = reader.next_line()? {
+                after = after.or(Some(LogCursor { rotation: 0, offset: start }));
+            }
+        }
+        if after.is_none() {
+            after = Some(LogCursor { rotation: *rotation, offset: end });
+        }
+
+        while let Some((start, bytes)) = reader.next_line()? {
+            let position = LogCursor { rotation: *rotation, offset: start };
+            let line = String::from_utf8_lossy(&bytes);
+            if line.trim().is_empty() {
+                continue;
+            }
+            scanned += 1;
+
+            let entry = parse_log_line(&line, &file);
+            if filter.is_before_range(&entry) {
+                break 'rotations;
+            }
+            if filter.matches(&entry) {
+                entries.push(entry);
+            }
+            if entries.len() >= limit || scanned >= MAX_SCANNED_LINES {
+                before = Some(position);
+                break 'rotations;
+            }
+        }
+    }
+
+    // Collected newest first; pages are always returned in file order
+    entries.reverse();
+    Ok(LogPage {
+        entries,
+        before: before.map(|c| c.encode()),
+        after: after.map(|c| c.encode()),
+        scanned,
+        files: Vec::new(),
+    })
+}
+
+fn search_forward(
+    rotations: &[(usize, PathBuf)],
+    filter: &CompiledFilter,
+    cursor: Option<LogCursor>,
+    limit: usize,
+) -> io::Result<LogPage> {
+    let mut entries = Vec::new();
+    let mut scanned = 0;
+    let mut before = cursor;
+    let mut after = None;
+    let mut past_range = false;
+
+    let start_rotation = cursor.map_or(usize::MAX, |c| c.rotation);
+    'rotations: for (rotation, path) in rotations.iter().rev().filter(|(r, _)| *r <= start_rotation) {
+        let mut source = open_rotation(path)?;
+        let length = source.seek(SeekFrom::End(0))?;
+        let mut offset = match cursor {
+            Some(c) if c.rotation == *rotation => c.offset.min(length),
+            _ => 0,
+        };
+        if before.is_none() {
+            before = Some(LogCursor { rotation: *rotation, offset });
+        }
+        source.seek(SeekFrom::Start(offset))?;
+
+        let file = path.display().to_string();
+        let mut reader = BufReader::new(source);
+        let mut bytes = Vec::new();
+        loop {
+            bytes.clear();
+            let read = reader.read_until(b'\n', &mut bytes)?;
+            // A final line without its newline is still being written; leave it for the next page
+            if read == 0 || (*rotation == 0 && !bytes.ends_with(b"\n")) {
+                break;
+            }
+            offset += read as u64;
+            after = Some(LogCursor { rotation: *rotation, offset });
+
+            let line = String::from_utf8_lossy(&bytes);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.trim().is_empty() {
+                continue;
+            }
+            scanned += 1;
+
+            let entry = parse_log_line(line, &file);
+            if filter.is_after_range(&entry) {
+                past_range = true;
+                break 'rotations;
+            }
+            if filter.matches(&entry) {
+                entries.push(entry);
+            }
+            if entries.len() >= limit || scanned >= MAX_SCANNED_LINES {
+                break 'rotations;
+            }
+        }
+    }
+
+    Ok(LogPage {
+        entries,
+        before: before.map(|c| c.encode()),
+        after: if past_range { None } else { after.or(cursor).map(|c| c.encode()) },
+        scanned,
+        files: Vec::new(),
+    })
+}
+
+/// Resolves the instance's log for `request.log_type` and runs the search
+pub fn search_nginx_log(instance: &NginxInstance, request: &LogSearchRequest) -> Result<LogPage, LogSearchError> {
+    let filter = request.filter().compile().map_err(LogSearchError::InvalidRequest)?;
+    if let Some(cursor) = request.cursor.as_deref().filter(|c| !c.is_empty()) {
+        LogCursor::parse(cursor).map_err(LogSearchError::InvalidRequest)?;
+    }
+    let path = find_nginx_log_path(instance, &request.log_type).map_err(LogSearchError::Failed)?;
+    search_log_file(
+        Path::new(&path),
+        &filter,
+        request.cursor.as_deref(),
+        request.direction,
+        request.limit.unwrap_or(DEFAULT_LIMIT),
+    )
+    .map_err(LogSearchError::Failed)
+}
+
+// Traffic stats look this far back when no `since` is given
+const DEFAULT_TRAFFIC_WINDOW: &str = "-60s";
+
+/// Query accepted by `/api/nginx/traffic`
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TrafficQuery {
+    /// Same forms as the search `since`; the last minute by default
+    pub since: Option<String>,
+}
+
+/// Requests in the access log since a point in time, by status class
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TrafficStats {
+    pub since: i64,
+    pub requests: u64,
+    pub status_2xx: u64,
+    pub status_3xx: u64,
+    pub status_4xx: u64,
+    pub status_5xx: u64,
+    /// Share of requests answered with a 5xx, 0 when there were none
+    pub error_rate: f64,
+    pub client_error_rate: f64,
+}
+
+/// Counts the access log entries written since `query.since`, following rotations
+pub fn traffic_stats(instance: &NginxInstance, query: &TrafficQuery) -> Result<TrafficStats, LogSearchError> {
+    let filter = LogFilter {
+        since: Some(query.since.clone().unwrap_or_else(|| DEFAULT_TRAFFIC_WINDOW.to_string())),
+        ..LogFilter::default()
+    };
+    let compiled = filter.compile().map_err(LogSearchError::InvalidRequest)?;
+    let path = find_nginx_log_path(instance, "access").map_err(LogSearchError::Failed)?;
+
+    let mut stats = TrafficStats {
+        since: compiled.time_range().0.unwrap_or(0),
+        ..TrafficStats::default()
+    };
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = search_log_file(Path::new(&path), &compiled, cursor.as_deref(), Direction::Backward, MAX_LIMIT)
+            .map_err(LogSearchError::Failed)?;
+        for status in page.entries.iter().filter_map(|entry| entry.status) {
+            stats.requests += 1;
+            match status / 100 {
+                2 => stats.status_2xx += 1,
+                3 => stats.status_3xx += 1,
+                4 => stats.status_4xx += 1,
+                5 => stats.status_5xx += 1,
+                _ => {}
+            }
+        }
+        match page.before {
+            Some(before) => cursor = Some(before),
+            None => break,
+        }
+    }
+
+    if stats.requests > 0 {
+        stats.error_rate = stats.status_5xx as f64 / stats.requests as f64;
+        stats.client_error_rate = stats.status_4xx as f64 / stats.requests as f64;
+    }
+    Ok(stats)
+}
+
+#[derive(Debug)]
+pub enum LogSearchError {
+    /// Bad filter or cursor supplied by the caller
+    InvalidRequest(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for LogSearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogSearchError::InvalidRequest(message) | LogSearchError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) fn search_nginx_logs(instance: Option<String>, request: LogSearchRequest) -> Result<LogPage, String> {
+    search_nginx_log(&instances::get_instance(instance.as_deref())?, &request).map_err(|e| e.to_string())
+}
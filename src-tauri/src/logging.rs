@@ -1,68 +1,257 @@
-use std::io::BufRead;
+use std::io::{BufRead, Seek, SeekFrom};
 use std::process::Command;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use std::thread;
 
 use std::io::BufReader;
 
-use std::fs::File;
+use std::fs::{self, File};
+use std::path::PathBuf;
 
 use std;
-use tauri::{AppHandle, Manager};
 use std::env::consts::OS;
+use serde::Serialize;
+use sysinfo::{ProcessesToUpdate, System};
 
+use crate::acme;
+use crate::actix_routes::find_nginx_log_path;
+use crate::config_watcher;
+use crate::event_bus::{self, RustinxEvent};
+use crate::fs_watch::DirWatcher;
+use crate::health;
+use crate::instances::{self, NginxInstance};
+use crate::tls;
+use crate::upstreams;
 
-pub(crate) fn monitor_nginx_log(path: &str, label: &str, app: AppHandle) -> std::io::Result<()> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
-    let mut line = String::new();
+// Most lines forwarded per log per second; anything beyond is counted and reported as dropped
+const MAX_LINES_PER_SECOND: usize = 200;
 
-    loop {
-        match reader.read_line(&mut line) {
-            Ok(0) => {
-                thread::sleep(Duration::from_millis(500)); // No new line, wait before trying again
+// Longer lines are cut so a single runaway entry can't balloon an event
+const MAX_LINE_LENGTH: usize = 16 * 1024;
+
+// Upper bound between rotation checks when no file activity wakes the tailer
+const TAIL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Published when a log produced lines faster than the frontend is allowed to receive them
+#[derive(Clone, Debug, Serialize)]
+pub struct DroppedLines {
+    pub log: String,
+    pub dropped: u64,
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Follows a log file across rotation (rename + recreate) and truncation (copytruncate)
+struct LogTailer {
+    instance: String,
+    path: PathBuf,
+    to_event: fn(String) -> RustinxEvent,
+    reader: Option<BufReader<File>>,
+    file_id: u64,
+    position: u64,
+    pending: Vec<u8>,
+    window_start: Instant,
+    sent_in_window: usize,
+    dropped: u64,
+}
+
+impl LogTailer {
+    fn new(instance: &str, path: PathBuf, to_event: fn(String) -> RustinxEvent) -> Self {
+        LogTailer {
+            instance: instance.to_string(),
+            path,
+            to_event,
+            reader: None,
+            file_id: 0,
+            position: 0,
+            pending: Vec::new(),
+            window_start: Instant::now(),
+            sent_in_window: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Opens the file, skipping its current content when `from_end` is set
+    fn open(&mut self, from_end: bool) {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(_) => return,
+        };
+        let id = file.metadata().map(|m| file_id(&m)).unwrap_or(0);
+        let mut reader = BufReader::new(file);
+        let position = if from_end {
+            reader.seek(SeekFrom::End(0)).unwrap_or(0)
+        } else {
+            0
+        };
+
+        self.reader = Some(reader);
+        self.file_id = id;
+        self.position = position;
+        self.pending.clear();
+    }
+
+    /// Reopens the file after a failed read and carries on at `position`, unless it was replaced
+    /// or truncated meanwhile; only then is everything in it new
+    fn reopen(&mut self, metadata: &fs::Metadata) {
+        if file_id(metadata) != self.file_id || metadata.len() < self.position {
+            self.open(false);
+            return;
+        }
+
+        let resumed = File::open(&self.path).and_then(|file| {
+            let mut reader = BufReader::new(file);
+            reader.seek(SeekFrom::Start(self.position))?;
+            Ok(reader)
+        });
+        match resumed {
+            Ok(reader) => self.reader = Some(reader),
+            Err(e) => eprintln!("Failed to reopen {}: {}", self.path.display(), e),
+        }
+    }
+
+    fn read_available(&mut self) {
+        let mut lines = Vec::new();
+        if let Some(reader) = self.reader.as_mut() {
+            loop {
+                let kept = self.pending.len();
+                match reader.read_until(b'\n', &mut self.pending) {
+                    Ok(0) => break,
+                    Ok(read) => {
+                        self.position += read as u64;
+                        // A line without its newline yet is still being written; finish it next time
+                        if self.pending.ends_with(b"\n") {
+                            lines.push(String::from_utf8_lossy(&self.pending).to_string());
+                            self.pending.clear();
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to read {}: {}", self.path.display(), e);
+                        // `position` doesn't count what this read got, so drop it and retry from there
+                        self.pending.truncate(kept);
+                        self.reader = None;
+                        break;
+                    }
+                }
+            }
+        }
+
+        for line in lines {
+            self.forward(line);
+        }
+    }
+
+    fn check_rotation(&mut self) {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // Moved away and not recreated yet: keep draining the old handle until it is
+            Err(_) => return,
+        };
+
+        if self.reader.is_none() {
+            // Either the file appeared after we started or a read failed
+            self.reopen(&metadata);
+        } else if file_id(&metadata) != self.file_id {
+            // Rotated: finish what was written to the old file, then follow the new one from its start
+            self.read_available();
+            if !self.pending.is_empty() {
+                let line = String::from_utf8_lossy(&self.pending).to_string();
+                self.pending.clear();
+                self.forward(line);
+            }
+            self.open(false);
+        } else if metadata.len() < self.position {
+            // Truncated in place
+            if let Some(reader) = self.reader.as_mut() {
+                if reader.seek(SeekFrom::Start(0)).is_ok() {
+                    self.position = 0;
+                    self.pending.clear();
+                }
             }
-            Ok(_) => {
-                // Here, instead of sending through a channel, emit the event directly
-                app.emit_all(label, &line)
-                    .expect("Failed to emit log event");
-                line.clear(); // Clear the line buffer for the next read
+        }
+    }
+
+    fn forward(&mut self, mut line: String) {
+        self.roll_window();
+        if self.sent_in_window >= MAX_LINES_PER_SECOND {
+            self.dropped += 1;
+            return;
+        }
+
+        if line.len() > MAX_LINE_LENGTH {
+            let mut cut = MAX_LINE_LENGTH;
+            while !line.is_char_boundary(cut) {
+                cut -= 1;
             }
-            Err(e) => return Err(e),
+            line.truncate(cut);
+            line.push('\n');
         }
+        self.sent_in_window += 1;
+        event_bus::publish_for(&self.instance, (self.to_event)(line));
+    }
+
+    /// Starts a new rate-limit window once a second has passed, reporting what the last one dropped
+    fn roll_window(&mut self) {
+        if self.window_start.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+        if self.dropped > 0 {
+            event_bus::publish_for(&self.instance, RustinxEvent::LogLinesDropped(DroppedLines {
+                log: (self.to_event)(String::new()).name().to_string(),
+                dropped: self.dropped,
+            }));
+        }
+        self.window_start = Instant::now();
+        self.sent_in_window = 0;
+        self.dropped = 0;
     }
 }
 
-fn check_nginx_config(app: AppHandle) {
-    thread::spawn(move || loop {
-        // Execute `nginx -t` to check the configuration
-        let output = Command::new("nginx")
-            .arg("-t")
-            .output()
-            .expect("Failed to execute command");
-
-        // Prepare the message based on the command's success or failure
-        let message = if output.status.success() {
-            "Nginx configuration is valid.".to_string()
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            format!("Nginx configuration error: {}", stderr)
-        };
+/// Streams new lines of `path` to the event bus. Waits for the file if it doesn't exist yet and
+/// survives logrotate; never returns.
+pub(crate) fn monitor_nginx_log(instance: &str, path: &str, to_event: fn(String) -> RustinxEvent) {
+    let path = PathBuf::from(path);
+    let directory = path.parent().map(|p| p.to_path_buf()).unwrap_or_else(|| PathBuf::from("/"));
+    let mut tailer = LogTailer::new(instance, path, to_event);
+    let mut watcher = None;
 
-        // Emit the result to the frontend
-        app.emit_all("nginx_config_check", &message)
-            .expect("Failed to emit nginx config check event");
+    // Only lines written from now on; history is served by the logs endpoint
+    tailer.open(true);
 
-        // Wait for a few seconds before checking again
-        thread::sleep(Duration::from_secs(5));
-    });
+    loop {
+        if watcher.is_none() {
+            // The log directory itself may not exist until nginx is installed or started
+            watcher = DirWatcher::new(std::slice::from_ref(&directory)).ok();
+        }
+
+        tailer.read_available();
+        tailer.check_rotation();
+        tailer.read_available();
+        tailer.roll_window();
+
+        match &watcher {
+            Some(watcher) => {
+                watcher.wait(Some(TAIL_CHECK_INTERVAL));
+            }
+            None => thread::sleep(TAIL_CHECK_INTERVAL),
+        }
+    }
 }
 
-pub(crate) fn start_log_monitoring(app_handle: AppHandle) {
-    // Determine the correct log paths based on the OS
-    let (access_log_path, error_log_path) = match OS {
+pub fn start_log_monitoring() {
+    // Fall back to the usual locations when the nginx config doesn't tell us
+    let (default_access_log, default_error_log) = match OS {
         "macos" => (
             "/usr/local/var/log/nginx/access.log", // Common path on macOS with Homebrew Nginx
             "/usr/local/var/log/nginx/error.log",
@@ -77,67 +266,152 @@ pub(crate) fn start_log_monitoring(app_handle: AppHandle) {
         }
     };
 
-    let app_handle_for_access_log = app_handle.clone();
-    let app_handle_for_config_check = app_handle.clone();
-    let app_handle_for_status_check = app_handle.clone();
-    
-    // Spawn a thread for monitoring access logs
-    std::thread::spawn(move || {
-        monitor_nginx_log(access_log_path, "access_event", app_handle_for_access_log)
-            .unwrap_or_else(|e| eprintln!("Log monitoring error: {}", e));
-    });
+    for instance in instances::list_instances() {
+        let access_log_path = find_nginx_log_path(&instance, "access").unwrap_or_else(|_| default_access_log.to_string());
+        let error_log_path = find_nginx_log_path(&instance, "error").unwrap_or_else(|_| default_error_log.to_string());
 
-    // Spawn a thread for monitoring error logs
-    let app_handle_for_error_log = app_handle;
-    std::thread::spawn(move || {
-        monitor_nginx_log(error_log_path, "error_event", app_handle_for_error_log)
-            .unwrap_or_else(|e| eprintln!("Log monitoring error: {}", e));
-    });
+        // Spawn a thread for monitoring access logs
+        let id = instance.id.clone();
+        std::thread::spawn(move || {
+            monitor_nginx_log(&id, &access_log_path, RustinxEvent::AccessLog);
+        });
+
+        // Spawn a thread for monitoring error logs
+        let id = instance.id.clone();
+        std::thread::spawn(move || {
+            monitor_nginx_log(&id, &error_log_path, RustinxEvent::ErrorLog);
+        });
 
-    // Start checking Nginx configuration and status
-    check_nginx_config(app_handle_for_config_check);
-    check_nginx_status(app_handle_for_status_check);
+        // Validate the configuration whenever it changes and keep checking the status
+        config_watcher::start_config_watcher(instance.clone());
+        check_nginx_status(instance.clone());
+        upstreams::start_upstream_monitor(instance.clone());
+        tls::start_certificate_monitor(instance);
+    }
+    acme::start_renewal_monitor();
 }
 
 
-pub(crate) fn check_nginx_status(app: AppHandle) {
+pub(crate) fn check_nginx_status(instance: NginxInstance) {
     thread::spawn(move || loop {
-        let status = match OS {
-            "linux" => {
-                let output = Command::new("systemctl")
-                    .arg("is-active")
-                    .arg("nginx")
-                    .output();
-
-                match output {
-                    Ok(output) => String::from_utf8_lossy(&output.stdout).trim().to_string(),
-                    Err(_) => "unknown".to_string(),
-                }
+        event_bus::publish_for(&instance.id, RustinxEvent::StatusCheck(nginx_status(&instance)));
+
+        thread::sleep(Duration::from_secs(5));
+    });
+}
+
+pub(crate) fn nginx_status(instance: &NginxInstance) -> String {
+    match OS {
+        "linux" => {
+            let output = Command::new("systemctl")
+                .arg("is-active")
+                .arg(&instance.unit)
+                .output();
+
+            match output {
+                Ok(output) => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+                Err(_) => "unknown".to_string(),
             }
-            "macos" => {
-                let output = Command::new("sh")
-                    .arg("-c")
-                    .arg("ps aux | grep nginx | grep -v grep")
-                    .output();
-
-                match output {
-                    Ok(output) => {
-                        if output.stdout.is_empty() {
-                            "inactive".to_string()
-                        } else {
-                            "active".to_string()
-                        }
-                    }
-                    Err(_) => "unknown".to_string(),
+        }
+        "macos" => {
+            // Without systemd, the instance is running when the master from its pid file is
+            let mut sys = System::new();
+            sys.refresh_processes(ProcessesToUpdate::All);
+            if health::find_master(&sys, instance).is_some() {
+                "active".to_string()
+            } else {
+                "inactive".to_string()
+            }
+        }
+        _ => "unsupported".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rustinx-logging-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn append(path: &PathBuf, text: &str) {
+        fs::OpenOptions::new().append(true).create(true).open(path).unwrap().write_all(text.as_bytes()).unwrap();
+    }
+
+    /// Lines the tailer for `instance` has forwarded so far
+    fn forwarded(receiver: &mut tokio::sync::broadcast::Receiver<event_bus::BusEvent>, instance: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        while let Ok(bus_event) = receiver.try_recv() {
+            if let (Some(from), RustinxEvent::AccessLog(line)) = (&bus_event.instance, bus_event.event) {
+                if from == instance {
+                    lines.push(line);
                 }
             }
-            _ => "unsupported".to_string(),
-        };
+        }
+        lines
+    }
 
-        app.emit_all("nginx_status_check", &status)
-            .expect("Failed to emit nginx status event");
+    #[test]
+    fn resumes_after_a_failed_read_without_replaying() {
+        let dir = TempDir::new("resume");
+        let path = dir.0.join("access.log");
+        let instance = "logging-resume";
+        let mut receiver = event_bus::subscribe();
 
-        thread::sleep(Duration::from_secs(5));
-    });
-}
+        append(&path, "history\n");
+        let mut tailer = LogTailer::new(instance, path.clone(), RustinxEvent::AccessLog);
+        tailer.open(true);
+        append(&path, "first\nsec");
+        tailer.read_available();
+        assert_eq!(forwarded(&mut receiver, instance), vec!["first\n"]);
 
+        // What a read error leaves behind
+        tailer.reader = None;
+        append(&path, "ond\n");
+        tailer.check_rotation();
+        tailer.read_available();
+        assert_eq!(forwarded(&mut receiver, instance), vec!["second\n"]);
+    }
+
+    #[test]
+    fn starts_over_when_the_file_was_replaced_or_truncated_meanwhile() {
+        let dir = TempDir::new("replace");
+        let path = dir.0.join("access.log");
+        let instance = "logging-replace";
+        let mut receiver = event_bus::subscribe();
+
+        append(&path, "old\n");
+        let mut tailer = LogTailer::new(instance, path.clone(), RustinxEvent::AccessLog);
+        tailer.open(false);
+        tailer.read_available();
+        assert_eq!(forwarded(&mut receiver, instance), vec!["old\n"]);
+
+        tailer.reader = None;
+        fs::rename(&path, dir.0.join("access.log.1")).unwrap();
+        append(&path, "rotated\n");
+        tailer.check_rotation();
+        tailer.read_available();
+        assert_eq!(forwarded(&mut receiver, instance), vec!["rotated\n"]);
+
+        tailer.reader = None;
+        fs::write(&path, "cut\n").unwrap();
+        tailer.check_rotation();
+        tailer.read_available();
+        assert_eq!(forwarded(&mut receiver, instance), vec!["cut\n"]);
+    }
+}
@@ -3,14 +3,35 @@ use actix_web::{cookie::Key, web, App, HttpServer};
 use tauri::{CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu};
 use actix_files as fs;
 
+mod acme;
 mod auth;
 mod actix_routes;
 mod commands;
 mod config;
+mod config_format;
+mod config_watcher;
+mod deploy;
+mod effective_config;
+mod event_bus;
 mod events_service;
+mod fleet;
+mod fs_watch;
+mod health;
+mod instances;
+mod lint;
+mod log_export;
+mod log_search;
 mod logging;
+mod nginx_build;
+mod nginx_config;
+mod rollout;
+mod route_resolver;
 mod systemd;
+mod templates;
+mod tls;
+mod upstreams;
 mod util;
+mod vhosts;
 
 #[tokio::main]
 async fn main() {
@@ -43,8 +64,9 @@ async fn main() {
     tauri::Builder::default()
         .setup(|app| {
             let app_handle = app.handle();
-            tokio::spawn(events_service::start_emitting_events(app_handle.clone()));
-            logging::start_log_monitoring(app_handle.clone());
+            events_service::forward_to_tauri(app_handle);
+            tokio::spawn(events_service::start_emitting_events());
+            logging::start_log_monitoring();
             Ok(())
         })
         .system_tray(
@@ -73,6 +95,8 @@ async fn main() {
             window.set_focus().unwrap();
         }))
         .invoke_handler(tauri::generate_handler![
+            acme::list_acme_certificates,
+            acme::issue_acme_certificate,
             commands::restart_nginx,
             commands::stop_nginx,
             commands::start_nginx,
@@ -82,8 +106,36 @@ async fn main() {
             config::get_nginx_version,
             config::modify_nginx_service,
             config::reload_and_restart_nginx_service,
+            config_format::format_nginx_config,
+            effective_config::get_effective_config,
+            effective_config::search_effective_config,
+            events_service::select_event_instance,
+            fleet::get_fleet_status,
+            health::get_nginx_health,
+            instances::get_instances,
+            lint::lint_nginx_config,
+            nginx_build::get_nginx_build,
+            rollout::get_fleet_rollouts,
+            rollout::start_fleet_rollout,
+            route_resolver::resolve_nginx_route,
+            log_export::export_nginx_logs,
+            log_search::search_nginx_logs,
             util::check_sudo_status,
-            systemd::get_systemd_logs
+            systemd::get_systemd_logs,
+            systemd::follow_systemd_logs,
+            systemd::get_allowed_units,
+            systemd::stop_following_systemd_logs,
+            templates::get_templates,
+            templates::save_nginx_template,
+            templates::delete_nginx_template,
+            templates::preview_nginx_template,
+            tls::get_tls_certificates,
+            upstreams::get_upstream_health,
+            vhosts::get_vhosts,
+            vhosts::create_site,
+            vhosts::update_site,
+            vhosts::set_site_enabled,
+            vhosts::remove_site
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
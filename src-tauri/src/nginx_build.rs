@@ -0,0 +1,398 @@
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::instances::{self, NginxInstance};
+
+// Where `./configure` puts everything when no --prefix is given
+const DEFAULT_PREFIX: &str = "/usr/local/nginx";
+
+// HTTP modules nginx builds in unless told `--without-<name>`
+const DEFAULT_HTTP_MODULES: [&str; 29] = [
+    "http_access_module",
+    "http_auth_basic_module",
+    "http_autoindex_module",
+    "http_browser_module",
+    "http_charset_module",
+    "http_empty_gif_module",
+    "http_fastcgi_module",
+    "http_geo_module",
+    "http_grpc_module",
+    "http_gzip_module",
+    "http_limit_conn_module",
+    "http_limit_req_module",
+    "http_map_module",
+    "http_memcached_module",
+    "http_mirror_module",
+    "http_proxy_module",
+    "http_referer_module",
+    "http_rewrite_module",
+    "http_scgi_module",
+    "http_split_clients_module",
+    "http_ssi_module",
+    "http_upstream_hash_module",
+    "http_upstream_ip_hash_module",
+    "http_upstream_keepalive_module",
+    "http_upstream_least_conn_module",
+    "http_upstream_random_module",
+    "http_upstream_zone_module",
+    "http_userid_module",
+    "http_uwsgi_module",
+];
+
+/// The install paths nginx was configured with, made absolute against the prefix where nginx
+/// itself would. Log paths may also be `stderr`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BuildPaths {
+    pub prefix: String,
+    pub sbin: String,
+    pub conf: String,
+    pub pid: String,
+    pub lock: String,
+    pub error_log: String,
+    pub http_log: String,
+    pub modules: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DynamicModule {
+    /// File stem, e.g. `ngx_http_geoip2_module`
+    pub name: String,
+    pub file: String,
+}
+
+/// Parsed `nginx -V` output
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NginxBuild {
+    /// `nginx`, or a distribution such as `openresty` or `angie`
+    pub product: String,
+    pub version: String,
+    /// e.g. `gcc 12.2.0 (Debian 12.2.0-14)`
+    pub compiler: Option<String>,
+    /// The OpenSSL nginx was built against
+    pub openssl: Option<String>,
+    /// Set when the library loaded at runtime differs from the one nginx was built against
+    pub openssl_runtime: Option<String>,
+    pub tls_sni: bool,
+    /// Every configure argument, with quotes removed, e.g. `--with-cc-opt=-g -O2`
+    pub arguments: Vec<String>,
+    /// `--with-*` flags without the prefix, e.g. `http_ssl_module`, `threads`, `cc-opt`
+    pub with: Vec<String>,
+    /// `--without-*` flags without the prefix, e.g. `http_ssi_module`
+    pub without: Vec<String>,
+    pub paths: BuildPaths,
+    /// Modules compiled into the binary, including the default ones that weren't turned off
+    pub static_modules: Vec<String>,
+    /// Shared objects found in the modules directory, which `load_module` can pull in
+    pub dynamic_modules: Vec<DynamicModule>,
+}
+
+impl NginxBuild {
+    /// The configured paths, with relative ones resolved against `prefix`
+    fn paths_under(&self, prefix: &str) -> BuildPaths {
+        let path = |name: &str, default: &str| prefixed(prefix, self.option(name).unwrap_or(default));
+        BuildPaths {
+            sbin: path("sbin-path", "sbin/nginx"),
+            conf: path("conf-path", "conf/nginx.conf"),
+            pid: path("pid-path", "logs/nginx.pid"),
+            lock: path("lock-path", "logs/nginx.lock"),
+            error_log: path("error-log-path", "logs/error.log"),
+            http_log: path("http-log-path", "logs/access.log"),
+            modules: path("modules-path", "modules"),
+            prefix: prefix.to_string(),
+        }
+    }
+
+    /// The value of a `--name=value` argument
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.arguments.iter().find_map(|arg| {
+            arg.strip_prefix("--")
+                .and_then(|arg| arg.strip_prefix(name))
+                .and_then(|rest| rest.strip_prefix('='))
+        })
+    }
+}
+
+/// Splits the configure arguments line the way the shell that ran `./configure` did
+fn split_arguments(line: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut in_word = false;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    arguments.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        arguments.push(current);
+    }
+    arguments
+}
+
+/// Makes a configure path absolute: nginx resolves relative ones against the prefix
+fn prefixed(prefix: &str, path: &str) -> String {
+    if path.starts_with('/') || path == "stderr" {
+        path.to_string()
+    } else {
+        Path::new(prefix).join(path).to_string_lossy().to_string()
+    }
+}
+
+/// Parses the text `nginx -V` prints (to stderr)
+pub fn parse_build_info(output: &str) -> NginxBuild {
+    let mut build = NginxBuild::default();
+
+    for line in output.lines().map(str::trim) {
+        if let Some(version) = line.strip_prefix("nginx version:") {
+            let (product, version) = version.trim().split_once('/').unwrap_or(("nginx", version.trim()));
+            build.product = product.to_string();
+            // Some builds append a distribution tag, e.g. `1.18.0 (Ubuntu)`
+            build.version = version.split_whitespace().next().unwrap_or("").to_string();
+        } else if let Some(compiler) = line.strip_prefix("built by ") {
+            build.compiler = Some(compiler.trim().to_string());
+        } else if let Some(openssl) = line.strip_prefix("built with ") {
+            match openssl.split_once(" (running with ") {
+                Some((built, running)) => {
+                    build.openssl = Some(built.trim().to_string());
+                    build.openssl_runtime = Some(running.trim_end_matches(')').trim().to_string());
+                }
+                None => build.openssl = Some(openssl.trim().to_string()),
+            }
+        } else if line.starts_with("TLS SNI support enabled") {
+            build.tls_sni = true;
+        } else if let Some(arguments) = line.strip_prefix("configure arguments:") {
+            build.arguments = split_arguments(arguments);
+        }
+    }
+
+    for argument in &build.arguments {
+        if let Some(flag) = argument.strip_prefix("--with-") {
+            build.with.push(flag.split('=').next().unwrap_or(flag).to_string());
+        } else if let Some(flag) = argument.strip_prefix("--without-") {
+            build.without.push(flag.to_string());
+        }
+    }
+
+    let prefix = build.option("prefix").unwrap_or(DEFAULT_PREFIX).to_string();
+    build.paths = build.paths_under(&prefix);
+    build.static_modules = static_modules(&build);
+    build
+}
+
+fn static_modules(build: &NginxBuild) -> Vec<String> {
+    let mut modules: Vec<String> = if build.without.iter().any(|flag| flag == "http") {
+        Vec::new()
+    } else {
+        DEFAULT_HTTP_MODULES
+            .iter()
+            .filter(|module| !build.without.iter().any(|flag| flag == *module))
+            .map(|module| module.to_string())
+            .collect()
+    };
+
+    for argument in &build.arguments {
+        if let Some(module) = argument.strip_prefix("--with-") {
+            // `--with-stream=dynamic` and the like build a shared object instead
+            if !module.contains('=') && (module.ends_with("_module") || matches!(module, "stream" | "mail")) {
+                modules.push(module.to_string());
+            }
+        } else if let Some(path) = argument.strip_prefix("--add-module=") {
+            // Third-party modules are named after their source directory
+            if let Some(name) = Path::new(path.trim_end_matches('/')).file_name() {
+                modules.push(name.to_string_lossy().to_string());
+            }
+        }
+    }
+    modules.sort();
+    modules.dedup();
+    modules
+}
+
+fn dynamic_modules(dir: &str) -> Vec<DynamicModule> {
+    let mut modules: Vec<DynamicModule> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "so"))
+                .filter_map(|path| {
+                    Some(DynamicModule {
+                        name: path.file_stem()?.to_string_lossy().to_string(),
+                        file: path.to_string_lossy().to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    modules.sort_by(|a, b| a.name.cmp(&b.name));
+    modules
+}
+
+/// Runs `nginx -V` for the instance and parses it, listing the dynamic modules available on disk
+pub fn nginx_build(instance: &NginxInstance) -> Result<NginxBuild, String> {
+    let output = instance
+        .command()
+        .arg("-V")
+        .output()
+        .map_err(|e| format!("Failed to execute nginx -V: {}", e))?;
+    if !output.status.success() {
+        return Err("Failed to get NGINX version.".to_string());
+    }
+
+    // nginx -V prints the build info to stderr
+    let mut build = parse_build_info(&String::from_utf8_lossy(&output.stderr));
+    // `-p` moves everything configured with a relative path
+    if let Some(prefix) = &instance.prefix {
+        build.paths = build.paths_under(prefix);
+    }
+    build.dynamic_modules = dynamic_modules(&build.paths.modules);
+    Ok(build)
+}
+
+#[tauri::command]
+pub(crate) fn get_nginx_build(instance: Option<String>) -> Result<NginxBuild, String> {
+    nginx_build(&instances::get_instance(instance.as_deref())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rustinx-nginx-build-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const DEBIAN: &str = "nginx version: nginx/1.22.1
+built with OpenSSL 3.0.9 30 May 2023 (running with OpenSSL 3.0.11 19 Sep 2023)
+TLS SNI support enabled
+configure arguments: --with-cc-opt='-g -O2 -ffile-prefix-map=/build/nginx-AoTv4W/nginx-1.22.1=. -fstack-protector-strong -Wformat -Werror=format-security -fPIC -Wdate-time -D_FORTIFY_SOURCE=2' --with-ld-opt='-Wl,-z,relro -Wl,-z,now -fPIC' --prefix=/usr/share/nginx --conf-path=/etc/nginx/nginx.conf --http-log-path=/var/log/nginx/access.log --error-log-path=stderr --lock-path=/var/lock/nginx.lock --pid-path=/run/nginx.pid --modules-path=/usr/lib/nginx/modules --http-client-body-temp-path=/var/lib/nginx/body --with-compat --with-debug --with-pcre-jit --with-http_ssl_module --with-http_stub_status_module --with-http_realip_module --with-http_v2_module --with-threads --with-http_gzip_static_module --with-mail_ssl_module --with-stream_ssl_module --with-http_geoip_module=dynamic --with-http_image_filter_module=dynamic --with-mail=dynamic --with-stream=dynamic
+";
+
+    const OPENRESTY_ALPINE: &str = "nginx version: openresty/1.25.3.1
+built by gcc 12.2.1 20220924 (Alpine 12.2.1_git20220924-r10)
+built with OpenSSL 3.0.12 24 Oct 2023
+TLS SNI support enabled
+configure arguments: --prefix=/usr/local/openresty/nginx --with-cc-opt=\"-O2 -DNGX_LUA_ABORT_AT_PANIC -I/usr/local/openresty/pcre2/include\" --add-module=../ngx_devel_kit-0.3.3 --add-module=../echo-nginx-module-0.63/ --add-module=../ngx_lua-0.10.26 --with-ld-opt=\"-Wl,-rpath,/usr/local/openresty/luajit/lib\" --without-http_ssi_module --with-http_ssl_module --with-stream --with-stream_ssl_module --with-threads
+";
+
+    fn has(list: &[String], item: &str) -> bool {
+        list.iter().any(|entry| entry == item)
+    }
+
+    #[test]
+    fn parses_debian_builds() {
+        let build = parse_build_info(DEBIAN);
+        assert_eq!(build.product, "nginx");
+        assert_eq!(build.version, "1.22.1");
+        assert_eq!(build.compiler, None);
+        assert_eq!(build.openssl.as_deref(), Some("OpenSSL 3.0.9 30 May 2023"));
+        assert_eq!(build.openssl_runtime.as_deref(), Some("OpenSSL 3.0.11 19 Sep 2023"));
+        assert!(build.tls_sni);
+
+        assert_eq!(build.arguments[0], "--with-cc-opt=-g -O2 -ffile-prefix-map=/build/nginx-AoTv4W/nginx-1.22.1=. -fstack-protector-strong -Wformat -Werror=format-security -fPIC -Wdate-time -D_FORTIFY_SOURCE=2");
+        assert_eq!(build.option("with-ld-opt"), Some("-Wl,-z,relro -Wl,-z,now -fPIC"));
+        assert!(has(&build.with, "cc-opt") && has(&build.with, "stream") && has(&build.with, "threads"));
+
+        assert_eq!(build.paths.prefix, "/usr/share/nginx");
+        assert_eq!(build.paths.sbin, "/usr/share/nginx/sbin/nginx");
+        assert_eq!(build.paths.conf, "/etc/nginx/nginx.conf");
+        assert_eq!(build.paths.pid, "/run/nginx.pid");
+        assert_eq!(build.paths.error_log, "stderr");
+        assert_eq!(build.paths.http_log, "/var/log/nginx/access.log");
+        assert_eq!(build.paths.modules, "/usr/lib/nginx/modules");
+
+        // Default modules stay, `=dynamic` ones are shared objects rather than compiled in
+        assert!(has(&build.static_modules, "http_ssl_module"));
+        assert!(has(&build.static_modules, "http_gzip_module"));
+        assert!(has(&build.static_modules, "stream_ssl_module"));
+        assert!(!has(&build.static_modules, "stream"));
+        assert!(!has(&build.static_modules, "http_geoip_module"));
+        assert!(!has(&build.static_modules, "cc-opt"));
+    }
+
+    #[test]
+    fn parses_openresty_on_alpine() {
+        let build = parse_build_info(OPENRESTY_ALPINE);
+        assert_eq!(build.product, "openresty");
+        assert_eq!(build.version, "1.25.3.1");
+        assert_eq!(build.compiler.as_deref(), Some("gcc 12.2.1 20220924 (Alpine 12.2.1_git20220924-r10)"));
+        assert_eq!(build.openssl.as_deref(), Some("OpenSSL 3.0.12 24 Oct 2023"));
+        assert_eq!(build.openssl_runtime, None);
+        assert_eq!(build.option("with-cc-opt"), Some("-O2 -DNGX_LUA_ABORT_AT_PANIC -I/usr/local/openresty/pcre2/include"));
+
+        // Nothing but the prefix was given, so everything lives under it
+        assert_eq!(build.paths.conf, "/usr/local/openresty/nginx/conf/nginx.conf");
+        assert_eq!(build.paths.error_log, "/usr/local/openresty/nginx/logs/error.log");
+        assert_eq!(build.paths.modules, "/usr/local/openresty/nginx/modules");
+
+        assert_eq!(build.without, vec!["http_ssi_module"]);
+        assert!(!has(&build.static_modules, "http_ssi_module"));
+        assert!(has(&build.static_modules, "http_rewrite_module"));
+        assert!(has(&build.static_modules, "stream"));
+        assert!(has(&build.static_modules, "ngx_devel_kit-0.3.3"));
+        assert!(has(&build.static_modules, "echo-nginx-module-0.63"));
+    }
+
+    #[test]
+    fn parses_distribution_tags_and_minimal_builds() {
+        let build = parse_build_info(
+            "nginx version: nginx/1.18.0 (Ubuntu)\nconfigure arguments: --sbin-path=bin/nginx --without-http\n",
+        );
+        assert_eq!(build.version, "1.18.0");
+        assert_eq!(build.paths.prefix, DEFAULT_PREFIX);
+        assert_eq!(build.paths.sbin, "/usr/local/nginx/bin/nginx");
+        assert!(build.static_modules.is_empty());
+        assert!(!build.tls_sni);
+
+        let build = parse_build_info("");
+        assert_eq!(build.version, "");
+        assert!(build.arguments.is_empty());
+        assert_eq!(build.static_modules.len(), DEFAULT_HTTP_MODULES.len());
+    }
+
+    #[test]
+    fn lists_shared_objects_in_the_modules_directory() {
+        let dir = TempDir::new("modules");
+        for file in ["ngx_stream_module.so", "ngx_http_geoip2_module.so", "README", "ngx_mail_module.so.old"] {
+            fs::write(dir.0.join(file), "").unwrap();
+        }
+
+        let modules = dynamic_modules(&dir.0.to_string_lossy());
+        let names: Vec<_> = modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["ngx_http_geoip2_module", "ngx_stream_module"]);
+        assert_eq!(modules[1].file, dir.0.join("ngx_stream_module.so").to_string_lossy());
+
+        assert!(dynamic_modules("/nonexistent/rustinx/modules").is_empty());
+    }
+}
@@ -0,0 +1,540 @@
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::net::Ipv6Addr;
+use std::path::{Path, PathBuf};
+
+use crate::instances::NginxInstance;
+use crate::nginx_build;
+
+// Common nginx.conf locations, checked in order when `nginx -V` doesn't report one
+pub const CONFIG_PATH_CANDIDATES: [&str; 4] = [
+    "/etc/nginx/nginx.conf",
+    "/usr/local/etc/nginx/nginx.conf",
+    "/opt/nginx/nginx.conf",
+    "/usr/local/nginx/conf/nginx.conf",
+];
+
+// Guards against include cycles such as a file that includes its own directory
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// Values Rustinx writes into directives must not contain anything that could end the directive,
+// open or close a block, quote, or start a comment
+lazy_static::lazy_static! {
+    static ref HOST_NAME: Regex =
+        Regex::new(r"^(\*\.)?([A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?\.)*[A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?$").unwrap();
+    static ref SAFE_PATH: Regex = Regex::new(r"^/[^\s;{}'\x22#]*$").unwrap();
+    static ref URL: Regex = Regex::new(r"^https?://[^\s;{}'\x22#]+$").unwrap();
+}
+
+/// One nginx directive, e.g. `listen 443 ssl;` or `server { ... }`
+#[derive(Clone, Debug, Serialize)]
+pub struct Directive {
+    pub name: String,
+    pub args: Vec<String>,
+    pub file: String,
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block: Option<Vec<Directive>>,
+    /// For `include` directives, indexes into `ParsedConfig::files` of the files it pulled in
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigFile {
+    pub path: String,
+    pub directives: Vec<Directive>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ParseError {
+    pub file: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// The main config plus every file reachable through `include`
+#[derive(Clone, Debug, Serialize)]
+pub struct ParsedConfig {
+    pub root: String,
+    pub files: Vec<ConfigFile>,
+    pub errors: Vec<ParseError>,
+    /// Absolute include patterns (possibly globs), so watchers can spot files added later
+    pub include_patterns: Vec<String>,
+}
+
+impl Directive {
+    pub fn arg(&self, index: usize) -> Option<&str> {
+        self.args.get(index).map(|s| s.as_str())
+    }
+
+    pub fn children(&self) -> &[Directive] {
+        self.block.as_deref().unwrap_or(&[])
+    }
+
+    pub fn location(&self) -> String {
+        format!("{}:{}", self.file, self.line)
+    }
+}
+
+impl ParsedConfig {
+    pub fn file_paths(&self) -> Vec<String> {
+        self.files.iter().map(|f| f.path.clone()).collect()
+    }
+
+    /// The directive tree with every `include` replaced by the directives it pulls in.
+    /// Each directive keeps the file and line it was written at.
+    pub fn expanded(&self) -> Vec<Directive> {
+        match self.files.first() {
+            Some(root) => self.expand_directives(&root.directives, 0),
+            None => vec![],
+        }
+    }
+
+    fn expand_directives(&self, directives: &[Directive], depth: usize) -> Vec<Directive> {
+        let mut expanded = Vec::new();
+        for directive in directives {
+            if directive.name == "include" && depth < MAX_INCLUDE_DEPTH {
+                for index in &directive.includes {
+                    if let Some(file) = self.files.get(*index) {
+                        expanded.extend(self.expand_directives(&file.directives, depth + 1));
+                    }
+                }
+                continue;
+            }
+
+            let mut copy = directive.clone();
+            if let Some(block) = &directive.block {
+                copy.block = Some(self.expand_directives(block, depth));
+            }
+            expanded.push(copy);
+        }
+        expanded
+    }
+}
+
+/// Calls `visit` for every directive in the tree, together with the chain of blocks enclosing it
+pub fn walk<'a, F>(directives: &'a [Directive], visit: &mut F)
+where
+    F: FnMut(&'a Directive, &[&'a Directive]),
+{
+    fn walk_inner<'a, F>(directives: &'a [Directive], parents: &mut Vec<&'a Directive>, visit: &mut F)
+    where
+        F: FnMut(&'a Directive, &[&'a Directive]),
+    {
+        for directive in directives {
+            visit(directive, parents);
+            if let Some(block) = &directive.block {
+                parents.push(directive);
+                walk_inner(block, parents, visit);
+                parents.pop();
+            }
+        }
+    }
+
+    walk_inner(directives, &mut Vec::new(), visit);
+}
+
+/// Locates the instance's main nginx.conf: its `-c`, then the path the binary was built with
+pub fn find_main_config(instance: &NginxInstance) -> Option<String> {
+    if let Some(config) = instance.config_path() {
+        return Some(config);
+    }
+    if let Ok(build) = nginx_build::nginx_build(instance) {
+        if Path::new(&build.paths.conf).exists() {
+            return Some(build.paths.conf);
+        }
+    }
+
+    // The usual locations are a guess for a packaged nginx, not for one run from its own prefix
+    if instance.prefix.is_some() {
+        return None;
+    }
+    CONFIG_PATH_CANDIDATES
+        .iter()
+        .find(|path| Path::new(path).exists())
+        .map(|path| path.to_string())
+}
+
+/// Parses `path` and every file it includes
+pub fn parse_config(path: &str) -> ParsedConfig {
+    let mut parsed = ParsedConfig {
+        root: path.to_string(),
+        files: Vec::new(),
+        errors: Vec::new(),
+        include_patterns: Vec::new(),
+    };
+    let base_dir = Path::new(path)
+        .parent()
+        .unwrap_or(Path::new("/etc/nginx"))
+        .to_path_buf();
+    let mut indexes = HashMap::new();
+    parse_file(path, &base_dir, &mut parsed, &mut indexes, 0);
+    parsed
+}
+
+fn parse_file(
+    path: &str,
+    base_dir: &Path,
+    parsed: &mut ParsedConfig,
+    indexes: &mut HashMap<String, usize>,
+    depth: usize,
+) -> Option<usize> {
+    if let Some(index) = indexes.get(path) {
+        return Some(*index);
+    }
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            parsed.errors.push(ParseError {
+                file: path.to_string(),
+                line: None,
+                message: format!("Failed to read {}: {}", path, e),
+            });
+            return None;
+        }
+    };
+
+    let mut directives = match parse_config_str(&content, path) {
+        Ok(directives) => directives,
+        Err(e) => {
+            parsed.errors.push(e);
+            return None;
+        }
+    };
+
+    // Reserve the slot before descending so cycles resolve to this file instead of recursing
+    let index = parsed.files.len();
+    indexes.insert(path.to_string(), index);
+    parsed.files.push(ConfigFile {
+        path: path.to_string(),
+        directives: Vec::new(),
+    });
+
+    resolve_includes(&mut directives, base_dir, parsed, indexes, depth);
+    parsed.files[index].directives = directives;
+    Some(index)
+}
+
+fn resolve_includes(
+    directives: &mut [Directive],
+    base_dir: &Path,
+    parsed: &mut ParsedConfig,
+    indexes: &mut HashMap<String, usize>,
+    depth: usize,
+) {
+    for directive in directives.iter_mut() {
+        if let Some(block) = directive.block.as_mut() {
+            resolve_includes(block, base_dir, parsed, indexes, depth);
+            continue;
+        }
+        if directive.name != "include" {
+            continue;
+        }
+        let pattern = match directive.arg(0) {
+            Some(pattern) => pattern.to_string(),
+            None => continue,
+        };
+        if depth >= MAX_INCLUDE_DEPTH {
+            parsed.errors.push(ParseError {
+                file: directive.file.clone(),
+                line: Some(directive.line),
+                message: format!("Includes nested deeper than {} levels", MAX_INCLUDE_DEPTH),
+            });
+            continue;
+        }
+
+        let full_pattern = resolve_path(base_dir, &pattern);
+        if !parsed.include_patterns.contains(&full_pattern) {
+            parsed.include_patterns.push(full_pattern.clone());
+        }
+
+        for path in expand_include(&full_pattern) {
+            if let Some(index) = parse_file(&path, base_dir, parsed, indexes, depth + 1) {
+                directive.includes.push(index);
+            }
+        }
+
+        if directive.includes.is_empty() && !is_glob(&full_pattern) {
+            parsed.errors.push(ParseError {
+                file: directive.file.clone(),
+                line: Some(directive.line),
+                message: format!("Included file {} could not be read", full_pattern),
+            });
+        }
+    }
+}
+
+/// Makes a path from the config absolute; nginx resolves relative paths against the config directory
+pub fn resolve_path(base_dir: &Path, path: &str) -> String {
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        base_dir.join(path).to_string_lossy().to_string()
+    }
+}
+
+/// A host name, optionally with a leading `*.` wildcard
+pub(crate) fn is_host_name(value: &str) -> bool {
+    value.len() <= 253 && HOST_NAME.is_match(value)
+}
+
+/// Something `server_name` accepts literally: a host name, an IP address or the `_` catch-all
+pub(crate) fn is_server_name(value: &str) -> bool {
+    let ipv6 = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')).unwrap_or(value);
+    value == "_" || is_host_name(value) || ipv6.parse::<Ipv6Addr>().is_ok()
+}
+
+/// An absolute path that can be written as a directive argument
+pub(crate) fn is_safe_path(value: &str) -> bool {
+    SAFE_PATH.is_match(value)
+}
+
+/// An `http://` or `https://` URL that can be written as a directive argument
+pub(crate) fn is_safe_url(value: &str) -> bool {
+    URL.is_match(value)
+}
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+fn expand_include(pattern: &str) -> Vec<String> {
+    if !is_glob(pattern) {
+        return vec![pattern.to_string()];
+    }
+
+    match glob::glob(pattern) {
+        Ok(paths) => {
+            let mut files: Vec<String> = paths
+                .flatten()
+                .filter(|p| p.is_file())
+                .map(|p: PathBuf| p.to_string_lossy().to_string())
+                .collect();
+            files.sort();
+            files
+        }
+        Err(_) => vec![],
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Token {
+    Word(String),
+    OpenBrace,
+    CloseBrace,
+    Semicolon,
+    /// Only produced by `tokenize_with_comments`; the text after `#`
+    Comment(String),
+}
+
+fn tokenize(content: &str, file: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    tokenize_inner(content, file, false)
+}
+
+/// Tokens including comments, for tools like the formatter that must not lose them
+pub(crate) fn tokenize_with_comments(content: &str, file: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    tokenize_inner(content, file, true)
+}
+
+fn tokenize_inner(content: &str, file: &str, keep_comments: bool) -> Result<Vec<(Token, usize)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = content.chars().peekable();
+    let mut line = 1;
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => {
+                chars.next();
+                let mut comment = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    comment.push(c);
+                    chars.next();
+                }
+                if keep_comments {
+                    tokens.push((Token::Comment(comment), line));
+                }
+            }
+            '{' => {
+                tokens.push((Token::OpenBrace, line));
+                chars.next();
+            }
+            '}' => {
+                tokens.push((Token::CloseBrace, line));
+                chars.next();
+            }
+            ';' => {
+                tokens.push((Token::Semicolon, line));
+                chars.next();
+            }
+            '"' | '\'' => {
+                let quote = c;
+                let start_line = line;
+                chars.next();
+                let mut word = String::new();
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => match chars.next() {
+                            Some(escaped) if escaped == quote => word.push(escaped),
+                            Some(escaped) => {
+                                if escaped == '\n' {
+                                    line += 1;
+                                }
+                                word.push('\\');
+                                word.push(escaped);
+                            }
+                            None => break,
+                        },
+                        c if c == quote => {
+                            closed = true;
+                            break;
+                        }
+                        c => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            word.push(c);
+                        }
+                    }
+                }
+                if !closed {
+                    return Err(ParseError {
+                        file: file.to_string(),
+                        line: Some(start_line),
+                        message: "Unterminated quoted string".to_string(),
+                    });
+                }
+                tokens.push((Token::Word(word), start_line));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == ';' || c == '}' || c == '"' || c == '\'' {
+                        break;
+                    }
+                    // `${var}` is a variable, not the start of a block
+                    if c == '{' && !word.ends_with('$') {
+                        break;
+                    }
+                    chars.next();
+                    if c == '\\' {
+                        word.push(c);
+                        if let Some(escaped) = chars.next() {
+                            word.push(escaped);
+                        }
+                        continue;
+                    }
+                    word.push(c);
+                    if c == '{' {
+                        // Consume the rest of the variable name up to the closing brace
+                        for c in chars.by_ref() {
+                            word.push(c);
+                            if c == '}' {
+                                break;
+                            }
+                        }
+                    }
+                }
+                tokens.push((Token::Word(word), line));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Parses config text without following includes. `file` is only used to label directives and errors.
+pub fn parse_config_str(content: &str, file: &str) -> Result<Vec<Directive>, ParseError> {
+    let tokens = tokenize(content, file)?;
+    let mut position = 0;
+    let directives = parse_block(&tokens, &mut position, file, false)?;
+    Ok(directives)
+}
+
+fn parse_block(
+    tokens: &[(Token, usize)],
+    position: &mut usize,
+    file: &str,
+    nested: bool,
+) -> Result<Vec<Directive>, ParseError> {
+    let mut directives = Vec::new();
+    let error = |line: usize, message: &str| ParseError {
+        file: file.to_string(),
+        line: Some(line),
+        message: message.to_string(),
+    };
+
+    while let Some((token, line)) = tokens.get(*position) {
+        *position += 1;
+        let name = match token {
+            Token::Word(name) => name.clone(),
+            Token::CloseBrace if nested => return Ok(directives),
+            Token::CloseBrace => return Err(error(*line, "Unexpected \"}\"")),
+            Token::OpenBrace => return Err(error(*line, "Unexpected \"{\"")),
+            Token::Semicolon => return Err(error(*line, "Unexpected \";\"")),
+            Token::Comment(_) => continue,
+        };
+
+        let mut args = Vec::new();
+        loop {
+            match tokens.get(*position) {
+                Some((Token::Word(arg), _)) => {
+                    args.push(arg.clone());
+                    *position += 1;
+                }
+                Some((Token::Comment(_), _)) => *position += 1,
+                Some((Token::Semicolon, _)) => {
+                    *position += 1;
+                    directives.push(Directive {
+                        name,
+                        args,
+                        file: file.to_string(),
+                        line: *line,
+                        block: None,
+                        includes: Vec::new(),
+                    });
+                    break;
+                }
+                Some((Token::OpenBrace, _)) => {
+                    *position += 1;
+                    let block = parse_block(tokens, position, file, true)?;
+                    directives.push(Directive {
+                        name,
+                        args,
+                        file: file.to_string(),
+                        line: *line,
+                        block: Some(block),
+                        includes: Vec::new(),
+                    });
+                    break;
+                }
+                Some((Token::CloseBrace, close_line)) => {
+                    return Err(error(*close_line, &format!("Directive \"{}\" is not terminated by \";\"", name)));
+                }
+                None => {
+                    return Err(error(*line, &format!("Unexpected end of file in directive \"{}\"", name)));
+                }
+            }
+        }
+    }
+
+    if nested {
+        let last_line = tokens.last().map(|(_, line)| *line).unwrap_or(1);
+        return Err(error(last_line, "Unexpected end of file, expecting \"}\""));
+    }
+    Ok(directives)
+}
@@ -0,0 +1,629 @@
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::auth;
+use crate::deploy::{DeployFile, DeployRequest, Deployment};
+use crate::event_bus::{self, RustinxEvent};
+use crate::fleet::{self, Agent, FleetError, FleetTls};
+use crate::health::{self, HealthSnapshot};
+use crate::instances;
+use crate::log_search::TrafficStats;
+
+// Used when the request leaves them out
+const DEFAULT_WAVE_SIZE: usize = 5;
+const DEFAULT_OBSERVE_SECS: u64 = 60;
+const DEFAULT_MAX_ERROR_RATE: f64 = 0.05;
+const DEFAULT_MIN_REQUESTS: u64 = 20;
+
+// Finished rollouts kept for the dashboard
+const MAX_KEPT_ROLLOUTS: usize = 20;
+
+lazy_static::lazy_static! {
+    static ref ROLLOUTS: Mutex<Vec<Rollout>> = Mutex::new(Vec::new());
+}
+
+/// When a wave counts as degraded. Error rates are only judged once a host served `min_requests`
+/// in the observation window, so a quiet host isn't rolled back over a single failed request.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RolloutThresholds {
+    /// Highest share of 5xx responses
+    #[serde(default = "default_max_error_rate")]
+    pub max_error_rate: f64,
+    /// Highest share of 4xx responses, unchecked when unset
+    pub max_client_error_rate: Option<f64>,
+    #[serde(default = "default_min_requests")]
+    pub min_requests: u64,
+}
+
+fn default_max_error_rate() -> f64 {
+    DEFAULT_MAX_ERROR_RATE
+}
+
+fn default_min_requests() -> u64 {
+    DEFAULT_MIN_REQUESTS
+}
+
+impl Default for RolloutThresholds {
+    fn default() -> Self {
+        RolloutThresholds {
+            max_error_rate: DEFAULT_MAX_ERROR_RATE,
+            max_client_error_rate: None,
+            min_requests: DEFAULT_MIN_REQUESTS,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RolloutRequest {
+    pub files: Vec<DeployFile>,
+    /// The nginx instance to change on every agent; each agent's first instance when omitted
+    pub instance: Option<String>,
+    /// Explicit waves of agent ids, e.g. `[["web-01"], ["web-02", "web-03"]]` for a canary first
+    #[serde(default)]
+    pub waves: Vec<Vec<String>>,
+    /// Without `waves`: these agents (all of them by default) in waves of `wave_size`
+    #[serde(default)]
+    pub agents: Vec<String>,
+    pub wave_size: Option<usize>,
+    /// How long a wave serves traffic on the new config before its health is judged
+    pub observe_secs: Option<u64>,
+    #[serde(default)]
+    pub thresholds: RolloutThresholds,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RolloutState {
+    Running,
+    Succeeded,
+    /// A wave degraded and every host that got the change is back on its previous config
+    RolledBack,
+    /// A wave degraded and at least one host couldn't be rolled back
+    Failed,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HostState {
+    Pending,
+    Deploying,
+    /// Running the new config, waiting for the health check
+    Deployed,
+    Healthy,
+    Degraded,
+    /// `nginx -t` or the reload failed; the agent restored its previous files itself
+    Failed,
+    RolledBack,
+    RollbackFailed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HostProgress {
+    pub agent: String,
+    pub state: HostState,
+    pub deployment: Option<String>,
+    pub message: Option<String>,
+    pub status: Option<String>,
+    pub traffic: Option<TrafficStats>,
+}
+
+/// A rollout and where each host stands, published as `fleet_rollout` on every change
+#[derive(Clone, Debug, Serialize)]
+pub struct Rollout {
+    pub id: String,
+    pub state: RolloutState,
+    pub instance: Option<String>,
+    pub files: Vec<String>,
+    pub current_wave: usize,
+    pub waves: Vec<Vec<HostProgress>>,
+    pub message: Option<String>,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+/// Applies `change` to the stored rollout and publishes the result
+fn update(id: &str, change: impl FnOnce(&mut Rollout)) {
+    let updated = match ROLLOUTS.lock() {
+        Ok(mut rollouts) => rollouts.iter_mut().find(|rollout| rollout.id == id).map(|rollout| {
+            change(rollout);
+            rollout.clone()
+        }),
+        Err(_) => None,
+    };
+    if let Some(rollout) = updated {
+        event_bus::publish(RustinxEvent::RolloutProgress(rollout));
+    }
+}
+
+fn update_host(id: &str, wave: usize, host: usize, change: impl FnOnce(&mut HostProgress)) {
+    update(id, |rollout| {
+        if let Some(progress) = rollout.waves.get_mut(wave).and_then(|hosts| hosts.get_mut(host)) {
+            change(progress);
+        }
+    });
+}
+
+/// Resolves the requested waves to agents, each agent at most once
+fn plan_waves(agents: &[Agent], request: &RolloutRequest) -> Result<Vec<Vec<Agent>>, FleetError> {
+    let waves: Vec<Vec<String>> = if !request.waves.is_empty() {
+        request.waves.clone()
+    } else {
+        let ids: Vec<String> = if request.agents.is_empty() {
+            agents.iter().map(|agent| agent.id.clone()).collect()
+        } else {
+            request.agents.clone()
+        };
+        let size = request.wave_size.unwrap_or(DEFAULT_WAVE_SIZE).max(1);
+        ids.chunks(size).map(|chunk| chunk.to_vec()).collect()
+    };
+
+    let mut seen = HashSet::new();
+    let mut planned = Vec::new();
+    for wave in waves.into_iter().filter(|wave| !wave.is_empty()) {
+        let mut hosts = Vec::new();
+        for id in wave {
+            if !seen.insert(id.clone()) {
+                return Err(FleetError::InvalidRequest(format!("Agent {} is in more than one wave", id)));
+            }
+            let agent = agents
+                .iter()
+                .find(|agent| agent.id == id)
+                .ok_or_else(|| FleetError::NotFound(format!("Unknown agent: {}", id)))?;
+            hosts.push(agent.clone());
+        }
+        planned.push(hosts);
+    }
+    if planned.is_empty() {
+        return Err(FleetError::InvalidRequest("No agents to roll out to".to_string()));
+    }
+    Ok(planned)
+}
+
+/// `path` on the agent, for the instance the rollout targets
+fn instance_path(path: &str, instance: &Option<String>) -> String {
+    match instance {
+        Some(instance) if path.contains('?') => format!("{}&instance={}", path, instance),
+        Some(instance) => format!("{}?instance={}", path, instance),
+        None => path.to_string(),
+    }
+}
+
+/// A rollout of `request` that hasn't started on any host yet
+fn new_rollout(request: &RolloutRequest, waves: &[Vec<Agent>]) -> Rollout {
+    Rollout {
+        id: auth::generate_id(8),
+        state: RolloutState::Running,
+        instance: request.instance.clone(),
+        files: request.files.iter().map(|file| file.path.clone()).collect(),
+        current_wave: 0,
+        waves: waves
+            .iter()
+            .map(|wave| {
+                wave.iter()
+                    .map(|agent| HostProgress {
+                        agent: agent.id.clone(),
+                        state: HostState::Pending,
+                        deployment: None,
+                        message: None,
+                        status: None,
+                        traffic: None,
+                    })
+                    .collect()
+            })
+            .collect(),
+        message: None,
+        started_at: health::now_secs(),
+        finished_at: None,
+    }
+}
+
+/// Starts rolling the files out in the background and returns the new rollout. Only one rollout
+/// runs at a time.
+pub fn start_rollout(request: RolloutRequest) -> Result<Rollout, FleetError> {
+    let agents = fleet::list_agents()?;
+    if request.files.is_empty() {
+        return Err(FleetError::InvalidRequest("No files to roll out".to_string()));
+    }
+    if let Some(instance) = request.instance.as_deref().filter(|id| !instances::is_valid_id(id)) {
+        return Err(FleetError::InvalidRequest(format!("Invalid instance id \"{}\"", instance)));
+    }
+    let waves = plan_waves(&agents, &request)?;
+    let client = fleet::controller_client(&FleetTls::from_env()?)?;
+
+    let rollout = new_rollout(&request, &waves);
+
+    {
+        let mut rollouts = ROLLOUTS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(running) = rollouts.iter().find(|rollout| rollout.state == RolloutState::Running) {
+            return Err(FleetError::InvalidRequest(format!("Rollout {} is still running", running.id)));
+        }
+        rollouts.push(rollout.clone());
+        if rollouts.len() > MAX_KEPT_ROLLOUTS {
+            rollouts.remove(0);
+        }
+    }
+    event_bus::publish(RustinxEvent::RolloutProgress(rollout.clone()));
+
+    let id = rollout.id.clone();
+    thread::spawn(move || run_rollout(&id, &client, &waves, &request));
+    Ok(rollout)
+}
+
+/// Checks one host after its wave served traffic for `observe_secs`
+fn check_host(
+    client: &Client,
+    agent: &Agent,
+    request: &RolloutRequest,
+    observe_secs: u64,
+) -> (Option<String>, Option<TrafficStats>, Result<(), String>) {
+    let health: HealthSnapshot = match fleet::get_json(client, agent, &instance_path("nginx/health", &request.instance)) {
+        Ok(health) => health,
+        Err(e) => return (None, None, Err(e)),
+    };
+    let status = Some(health.status.clone());
+    if health.status != "active" {
+        return (status, None, Err(format!("nginx is {}", health.status)));
+    }
+
+    let path = instance_path(&format!("nginx/traffic?since=-{}s", observe_secs), &request.instance);
+    let traffic: TrafficStats = match fleet::get_json(client, agent, &path) {
+        Ok(traffic) => traffic,
+        // Without the access log there is no telling whether the change hurt
+        Err(e) => return (status, None, Err(format!("No traffic stats: {}", e))),
+    };
+
+    let verdict = judge_traffic(&request.thresholds, &traffic);
+    (status, Some(traffic), verdict)
+}
+
+/// Whether a host's traffic since the change is within the thresholds
+fn judge_traffic(thresholds: &RolloutThresholds, traffic: &TrafficStats) -> Result<(), String> {
+    if traffic.requests < thresholds.min_requests {
+        return Ok(());
+    }
+    if traffic.error_rate > thresholds.max_error_rate {
+        return Err(format!(
+            "{:.1}% of {} requests failed with 5xx (limit {:.1}%)",
+            traffic.error_rate * 100.0,
+            traffic.requests,
+            thresholds.max_error_rate * 100.0
+        ));
+    }
+    if let Some(limit) = thresholds.max_client_error_rate.filter(|limit| traffic.client_error_rate > *limit) {
+        return Err(format!(
+            "{:.1}% of {} requests got a 4xx (limit {:.1}%)",
+            traffic.client_error_rate * 100.0,
+            traffic.requests,
+            limit * 100.0
+        ));
+    }
+    Ok(())
+}
+
+fn run_rollout(id: &str, client: &Client, waves: &[Vec<Agent>], request: &RolloutRequest) {
+    let observe_secs = request.observe_secs.unwrap_or(DEFAULT_OBSERVE_SECS);
+    let deploy_request = DeployRequest { files: request.files.clone() };
+    // (wave, host, agent, deployment) of every host running the new config
+    let mut deployed: Vec<(usize, usize, &Agent, String)> = Vec::new();
+
+    for (wave_index, wave) in waves.iter().enumerate() {
+        update(id, |rollout| {
+            rollout.current_wave = wave_index;
+            rollout.message = Some(format!("Deploying wave {} of {}", wave_index + 1, waves.len()));
+            for host in rollout.waves[wave_index].iter_mut() {
+                host.state = HostState::Deploying;
+            }
+        });
+
+        let results: Vec<Result<Deployment, String>> = thread::scope(|scope| {
+            let handles: Vec<_> = wave
+                .iter()
+                .map(|agent| {
+                    let path = instance_path("nginx/deploy", &request.instance);
+                    let deploy_request = &deploy_request;
+                    scope.spawn(move || fleet::post_json(client, agent, &path, deploy_request))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|_| Err("deployment thread panicked".to_string())))
+                .collect()
+        });
+
+        let mut problems = Vec::new();
+        for (host_index, (agent, result)) in wave.iter().zip(results).enumerate() {
+            match result {
+                Ok(deployment) => {
+                    deployed.push((wave_index, host_index, agent, deployment.id.clone()));
+                    update_host(id, wave_index, host_index, |host| {
+                        host.state = HostState::Deployed;
+                        host.deployment = Some(deployment.id);
+                    });
+                }
+                Err(e) => {
+                    problems.push(e.clone());
+                    update_host(id, wave_index, host_index, |host| {
+                        host.state = HostState::Failed;
+                        host.message = Some(e);
+                    });
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            update(id, |rollout| {
+                rollout.message = Some(format!("Observing wave {} for {}s", wave_index + 1, observe_secs));
+            });
+            thread::sleep(Duration::from_secs(observe_secs));
+
+            let checks: Vec<_> = thread::scope(|scope| {
+                let handles: Vec<_> = wave
+                    .iter()
+                    .map(|agent| scope.spawn(move || check_host(client, agent, request, observe_secs)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap_or_else(|_| (None, None, Err("health check panicked".to_string()))))
+                    .collect()
+            });
+            for (host_index, (agent, (status, traffic, verdict))) in wave.iter().zip(checks).enumerate() {
+                if let Err(e) = &verdict {
+                    problems.push(format!("{}: {}", agent.id, e));
+                }
+                update_host(id, wave_index, host_index, |host| {
+                    host.state = if verdict.is_ok() { HostState::Healthy } else { HostState::Degraded };
+                    host.message = verdict.err();
+                    host.status = status;
+                    host.traffic = traffic;
+                });
+            }
+        }
+
+        if !problems.is_empty() {
+            let reason = format!("Wave {} degraded: {}", wave_index + 1, problems.join("; "));
+            roll_back(id, client, request, &deployed, reason);
+            return;
+        }
+    }
+
+    update(id, |rollout| {
+        rollout.state = RolloutState::Succeeded;
+        rollout.message = Some(format!("Rolled out to {} hosts", deployed.len()));
+        rollout.finished_at = Some(health::now_secs());
+    });
+}
+
+/// Puts every host that got the change back on its previous config, newest first
+fn roll_back(id: &str, client: &Client, request: &RolloutRequest, deployed: &[(usize, usize, &Agent, String)], reason: String) {
+    update(id, |rollout| rollout.message = Some(format!("{}; rolling back", reason)));
+
+    let mut failed = 0;
+    for (wave_index, host_index, agent, deployment) in deployed.iter().rev() {
+        let path = instance_path(&format!("nginx/deploy/{}/rollback", deployment), &request.instance);
+        let result = fleet::post_json::<Deployment>(client, agent, &path, &serde_json::json!({}));
+        if result.is_err() {
+            failed += 1;
+        }
+        update_host(id, *wave_index, *host_index, |host| match result {
+            Ok(_) => host.state = HostState::RolledBack,
+            Err(e) => {
+                host.state = HostState::RollbackFailed;
+                host.message = Some(e);
+            }
+        });
+    }
+
+    update(id, |rollout| {
+        if failed == 0 {
+            rollout.state = RolloutState::RolledBack;
+            rollout.message = Some(format!("{}; rolled back {} hosts", reason, deployed.len()));
+        } else {
+            rollout.state = RolloutState::Failed;
+            rollout.message = Some(format!("{}; {} of {} hosts could not be rolled back", reason, failed, deployed.len()));
+        }
+        rollout.finished_at = Some(health::now_secs());
+    });
+}
+
+/// Rollouts of this controller, newest first
+pub fn list_rollouts() -> Vec<Rollout> {
+    let mut rollouts = ROLLOUTS.lock().map(|rollouts| rollouts.clone()).unwrap_or_default();
+    rollouts.reverse();
+    rollouts
+}
+
+pub fn get_rollout(id: &str) -> Result<Rollout, FleetError> {
+    list_rollouts()
+        .into_iter()
+        .find(|rollout| rollout.id == id)
+        .ok_or_else(|| FleetError::NotFound(format!("Unknown rollout: {}", id)))
+}
+
+#[tauri::command]
+pub(crate) fn start_fleet_rollout(request: RolloutRequest) -> Result<Rollout, String> {
+    start_rollout(request).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn get_fleet_rollouts() -> Vec<Rollout> {
+    list_rollouts()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use serde_json::json;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+
+    fn agent(id: &str) -> Agent {
+        Agent { id: id.to_string(), url: format!("https://{}:8443", id) }
+    }
+
+    fn request(waves: &[&[&str]], agents: &[&str], wave_size: Option<usize>) -> RolloutRequest {
+        RolloutRequest {
+            files: vec![DeployFile { path: "conf.d/app.conf".to_string(), content: "# new".to_string() }],
+            instance: None,
+            waves: waves.iter().map(|wave| wave.iter().map(|id| id.to_string()).collect()).collect(),
+            agents: agents.iter().map(|id| id.to_string()).collect(),
+            wave_size,
+            observe_secs: Some(0),
+            thresholds: RolloutThresholds::default(),
+        }
+    }
+
+    fn ids(waves: &[Vec<Agent>]) -> Vec<Vec<&str>> {
+        waves.iter().map(|wave| wave.iter().map(|agent| agent.id.as_str()).collect()).collect()
+    }
+
+    fn traffic(requests: u64, error_rate: f64, client_error_rate: f64) -> TrafficStats {
+        TrafficStats { requests, error_rate, client_error_rate, ..TrafficStats::default() }
+    }
+
+    #[test]
+    fn waves_follow_the_request() {
+        let agents: Vec<Agent> = ["web-01", "web-02", "web-03"].iter().map(|id| agent(id)).collect();
+
+        let planned = plan_waves(&agents, &request(&[&["web-02"], &[], &["web-03", "web-01"]], &[], None)).unwrap();
+        assert_eq!(ids(&planned), vec![vec!["web-02"], vec!["web-03", "web-01"]]);
+        let planned = plan_waves(&agents, &request(&[], &[], Some(2))).unwrap();
+        assert_eq!(ids(&planned), vec![vec!["web-01", "web-02"], vec!["web-03"]]);
+        let planned = plan_waves(&agents, &request(&[], &["web-03", "web-01"], Some(0))).unwrap();
+        assert_eq!(ids(&planned), vec![vec!["web-03"], vec!["web-01"]]);
+
+        let twice = plan_waves(&agents, &request(&[&["web-01"], &["web-01"]], &[], None));
+        assert!(matches!(twice, Err(FleetError::InvalidRequest(_))));
+        assert!(matches!(plan_waves(&agents, &request(&[&["web-09"]], &[], None)), Err(FleetError::NotFound(_))));
+        assert!(matches!(plan_waves(&[], &request(&[], &[], None)), Err(FleetError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn traffic_is_judged_once_enough_requests_arrived() {
+        let thresholds = RolloutThresholds { max_error_rate: 0.05, max_client_error_rate: Some(0.2), min_requests: 20 };
+
+        // A quiet host isn't judged, however bad its few requests went
+        assert!(judge_traffic(&thresholds, &traffic(19, 1.0, 1.0)).is_ok());
+        assert!(judge_traffic(&thresholds, &traffic(20, 0.05, 0.2)).is_ok());
+        assert!(judge_traffic(&thresholds, &traffic(20, 0.06, 0.0)).unwrap_err().contains("5xx"));
+        assert!(judge_traffic(&thresholds, &traffic(20, 0.0, 0.25)).unwrap_err().contains("4xx"));
+
+        let unchecked = RolloutThresholds { max_client_error_rate: None, ..thresholds };
+        assert!(judge_traffic(&unchecked, &traffic(20, 0.0, 0.9)).is_ok());
+    }
+
+    /// Deploy and rollback calls the stand-in agents received, e.g. `deploy web-01`
+    type Calls = Arc<Mutex<Vec<String>>>;
+
+    async fn deploy(calls: web::Data<Calls>, path: web::Path<String>) -> HttpResponse {
+        let agent = path.into_inner();
+        calls.lock().unwrap().push(format!("deploy {}", agent));
+        HttpResponse::Ok().json(json!({ "id": format!("{}-1", agent), "instance": "main", "files": [], "deployed_at": 0 }))
+    }
+
+    async fn rollback(calls: web::Data<Calls>, path: web::Path<(String, String)>) -> HttpResponse {
+        let (agent, deployment) = path.into_inner();
+        calls.lock().unwrap().push(format!("rollback {} {}", agent, deployment));
+        HttpResponse::Ok().json(json!({ "id": deployment, "instance": "main", "files": [], "deployed_at": 0 }))
+    }
+
+    async fn health() -> HttpResponse {
+        HttpResponse::Ok().json(json!({
+            "instance": "main",
+            "timestamp": 1,
+            "status": "active",
+            "config_valid": true,
+            "config_message": null,
+            "master_pid": 100,
+            "uptime_secs": 60,
+            "worker_count": 2,
+            "last_reload": null,
+            "alerts": []
+        }))
+    }
+
+    async fn traffic_stats(degraded: web::Data<Vec<String>>, path: web::Path<String>) -> HttpResponse {
+        let error_rate = if degraded.contains(&path.into_inner()) { 0.5 } else { 0.0 };
+        HttpResponse::Ok().json(traffic(100, error_rate, 0.0))
+    }
+
+    /// Runs a rollout against stand-in agents served over plain HTTP, where `degraded` agents report
+    /// half their requests failing. Returns the finished rollout and the calls the agents got.
+    fn roll_out(request: RolloutRequest, agent_ids: &[&str], degraded: &[&str]) -> (Rollout, Vec<String>) {
+        let calls: Calls = Arc::new(Mutex::new(Vec::new()));
+        let degraded: Vec<String> = degraded.iter().map(|id| id.to_string()).collect();
+        let (sender, receiver) = mpsc::channel();
+        let agent_calls = calls.clone();
+        thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                let server = HttpServer::new(move || {
+                    App::new()
+                        .app_data(web::Data::new(agent_calls.clone()))
+                        .app_data(web::Data::new(degraded.clone()))
+                        .route("/{agent}/api/nginx/deploy", web::post().to(deploy))
+                        .route("/{agent}/api/nginx/deploy/{id}/rollback", web::post().to(rollback))
+                        .route("/{agent}/api/nginx/health", web::get().to(health))
+                        .route("/{agent}/api/nginx/traffic", web::get().to(traffic_stats))
+                })
+                .workers(1)
+                .bind("127.0.0.1:0")
+                .unwrap();
+                let address = server.addrs()[0];
+                let server = server.run();
+                sender.send((address, server.handle())).unwrap();
+                server.await.unwrap();
+            });
+        });
+        let (address, server) = receiver.recv().unwrap();
+
+        let agents: Vec<Agent> = agent_ids
+            .iter()
+            .map(|id| Agent { id: id.to_string(), url: format!("http://{}/{}", address, id) })
+            .collect();
+        let waves = plan_waves(&agents, &request).unwrap();
+        let rollout = new_rollout(&request, &waves);
+        ROLLOUTS.lock().unwrap().push(rollout.clone());
+        run_rollout(&rollout.id, &Client::new(), &waves, &request);
+
+        actix_web::rt::System::new().block_on(server.stop(false));
+        let calls = calls.lock().unwrap().clone();
+        (get_rollout(&rollout.id).unwrap(), calls)
+    }
+
+    fn states(rollout: &Rollout) -> Vec<Vec<HostState>> {
+        rollout.waves.iter().map(|wave| wave.iter().map(|host| host.state).collect()).collect()
+    }
+
+    #[test]
+    fn healthy_waves_are_deployed_one_after_another() {
+        let (rollout, calls) = roll_out(request(&[], &[], Some(2)), &["web-01", "web-02", "web-03"], &[]);
+
+        assert_eq!(rollout.state, RolloutState::Succeeded);
+        assert_eq!(states(&rollout), vec![vec![HostState::Healthy; 2], vec![HostState::Healthy]]);
+        // The second wave only starts once the first one passed its check
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[2], "deploy web-03");
+    }
+
+    #[test]
+    fn degraded_wave_rolls_back_every_deployed_host_newest_first() {
+        let waves: &[&[&str]] = &[&["web-01"], &["web-02", "web-03"], &["web-04"]];
+        let (rollout, calls) = roll_out(request(waves, &[], None), &["web-01", "web-02", "web-03", "web-04"], &["web-03"]);
+
+        assert_eq!(rollout.state, RolloutState::RolledBack);
+        assert_eq!(
+            states(&rollout),
+            vec![vec![HostState::RolledBack], vec![HostState::RolledBack; 2], vec![HostState::Pending]]
+        );
+        assert!(rollout.waves[1][1].message.as_deref().unwrap_or_default().contains("5xx"));
+        assert_eq!(calls[0], "deploy web-01");
+        assert_eq!(
+            calls[3..],
+            ["rollback web-03 web-03-1", "rollback web-02 web-02-1", "rollback web-01 web-01-1"]
+        );
+    }
+}
@@ -0,0 +1,683 @@
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::instances::{self, NginxInstance};
+use crate::nginx_config::{self, Directive, ParsedConfig};
+
+#[derive(Debug)]
+pub enum ResolveError {
+    /// Missing host or a URI nginx itself would reject
+    InvalidRequest(String),
+    /// No server listens on the port
+    NotFound(String),
+    Failed(String),
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::InvalidRequest(message) | ResolveError::NotFound(message) | ResolveError::Failed(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+/// Query accepted by `/api/nginx/resolve` and the `resolve_nginx_route` command
+#[derive(Clone, Debug, Deserialize)]
+pub struct RouteQuery {
+    /// The Host header; a port suffix is ignored
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Path as sent by the client; the query string is dropped before matching
+    pub uri: String,
+}
+
+fn default_port() -> u16 {
+    80
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerMatchKind {
+    ExactName,
+    LeadingWildcard,
+    TrailingWildcard,
+    Regex,
+    /// No name matched; the port's `default_server`
+    DefaultServer,
+    /// No name matched and no `default_server`, so the first server on the port
+    FirstOnPort,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ServerMatch {
+    pub server_names: Vec<String>,
+    pub listen: Vec<String>,
+    pub kind: ServerMatchKind,
+    /// The `server_name` entry that matched, when one did
+    pub matched_name: Option<String>,
+    pub file: String,
+    pub line: usize,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationMatchKind {
+    /// `location = /path`
+    Exact,
+    /// `location ^~ /path`, which stops the regex search
+    PrefixNoRegex,
+    /// The longest plain prefix
+    Prefix,
+    /// `location ~` or `location ~*`, the first match in config order
+    Regex,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LocationMatch {
+    /// The location arguments as written, e.g. `~* \.php$`
+    pub pattern: String,
+    pub kind: LocationMatchKind,
+    pub file: String,
+    pub line: usize,
+}
+
+/// A directive that applies to the request, and where it was set
+#[derive(Clone, Debug, Serialize)]
+pub struct EffectiveDirective {
+    pub value: String,
+    pub file: String,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RouteResolution {
+    pub host: String,
+    pub port: u16,
+    /// The URI after decoding and dot-segment/slash normalization, which is what locations see
+    pub uri: String,
+    pub server: ServerMatch,
+    /// Outermost first; empty when no location matched and the server block handles the request
+    pub locations: Vec<LocationMatch>,
+    pub root: Option<EffectiveDirective>,
+    pub alias: Option<EffectiveDirective>,
+    pub proxy_pass: Option<EffectiveDirective>,
+    pub try_files: Option<EffectiveDirective>,
+    /// Things the answer depends on that can't be known from the config alone
+    pub notes: Vec<String>,
+}
+
+/// Decodes `%XX`, merges slashes and resolves `.` and `..` like nginx does before matching locations
+pub fn normalize_uri(uri: &str) -> Result<String, String> {
+    let path = uri.split(['?', '#']).next().unwrap_or("");
+    if !path.starts_with('/') {
+        return Err(format!("URI must start with \"/\": {}", uri));
+    }
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    let decoded = String::from_utf8_lossy(&decoded).to_string();
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in decoded.split('/').skip(1) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(format!("URI escapes the document root: {}", uri));
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    let mut normalized = format!("/{}", segments.join("/"));
+    // A trailing slash, or a trailing dot segment that resolved to a directory, is significant
+    if segments.is_empty() {
+        return Ok(normalized);
+    }
+    if decoded.ends_with('/') || decoded.ends_with("/.") || decoded.ends_with("/..") {
+        normalized.push('/');
+    }
+    Ok(normalized)
+}
+
+/// The port of a `listen` address such as `80`, `127.0.0.1:8080`, `[::]:443` or `localhost`
+fn listen_port(address: &str) -> Option<u16> {
+    if address.starts_with("unix:") {
+        return None;
+    }
+    if let Ok(port) = address.parse() {
+        return Some(port);
+    }
+    let after_host = match address.rfind(']') {
+        Some(end) => &address[end + 1..],
+        None => address,
+    };
+    match after_host.rsplit_once(':') {
+        Some((_, port)) => port.parse().ok(),
+        None => Some(80),
+    }
+}
+
+/// Whether a listen address accepts connections to any local address, e.g. `80` or `[::]:80`
+fn is_wildcard_listen(listen: &str) -> bool {
+    let address = listen.split_whitespace().next().unwrap_or("");
+    if address.chars().all(|c| c.is_ascii_digit()) {
+        return true;
+    }
+    match address.rsplit_once(':') {
+        Some((host, _)) => matches!(host, "*" | "0.0.0.0" | "[::]"),
+        None => false,
+    }
+}
+
+/// The servers listening on `port`, each with its listen addresses and whether it is the default there
+fn servers_on_port(tree: &[Directive], port: u16) -> Vec<(&Directive, Vec<String>, bool)> {
+    let mut servers = Vec::new();
+    nginx_config::walk(tree, &mut |directive, parents| {
+        if directive.name != "server" || parents.last().map(|parent| parent.name.as_str()) != Some("http") {
+            return;
+        }
+        let listens: Vec<&Directive> = directive.children().iter().filter(|c| c.name == "listen").collect();
+        if listens.is_empty() {
+            if port == 80 {
+                servers.push((directive, vec!["*:80".to_string()], false));
+            }
+            return;
+        }
+
+        let matching: Vec<&Directive> = listens
+            .into_iter()
+            .filter(|listen| listen.arg(0).and_then(listen_port) == Some(port))
+            .collect();
+        if matching.is_empty() {
+            return;
+        }
+        let is_default = matching
+            .iter()
+            .any(|listen| listen.args.iter().skip(1).any(|arg| arg == "default_server" || arg == "default"));
+        let addresses = matching.iter().map(|listen| listen.args.join(" ")).collect();
+        servers.push((directive, addresses, is_default));
+    });
+    servers
+}
+
+fn server_names(server: &Directive) -> Vec<String> {
+    server
+        .children()
+        .iter()
+        .filter(|child| child.name == "server_name")
+        .flat_map(|child| child.args.iter().cloned())
+        .collect()
+}
+
+/// How well `name` matches `host`: the kind and, for wildcards, the length nginx ranks them by
+fn name_match(name: &str, host: &str) -> Option<(ServerMatchKind, usize)> {
+    if let Some(pattern) = name.strip_prefix('~') {
+        let regex = RegexBuilder::new(pattern).case_insensitive(true).build().ok()?;
+        return regex.is_match(host).then_some((ServerMatchKind::Regex, 0));
+    }
+
+    let name = name.to_ascii_lowercase();
+    if name == host {
+        return Some((ServerMatchKind::ExactName, name.len()));
+    }
+    if let Some(suffix) = name.strip_prefix("*.") {
+        let matches = host.strip_suffix(suffix).is_some_and(|rest| rest.ends_with('.') && rest.len() > 1);
+        return matches.then_some((ServerMatchKind::LeadingWildcard, suffix.len()));
+    }
+    if let Some(suffix) = name.strip_prefix('.') {
+        // `.example.org` is shorthand for `example.org` plus `*.example.org`
+        if host == suffix {
+            return Some((ServerMatchKind::ExactName, suffix.len()));
+        }
+        let matches = host.strip_suffix(suffix).is_some_and(|rest| rest.ends_with('.') && rest.len() > 1);
+        return matches.then_some((ServerMatchKind::LeadingWildcard, suffix.len()));
+    }
+    if let Some(prefix) = name.strip_suffix(".*") {
+        let matches = host.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('.') && rest.len() > 1);
+        return matches.then_some((ServerMatchKind::TrailingWildcard, prefix.len()));
+    }
+    None
+}
+
+fn rank(kind: ServerMatchKind) -> u8 {
+    match kind {
+        ServerMatchKind::ExactName => 0,
+        ServerMatchKind::LeadingWildcard => 1,
+        ServerMatchKind::TrailingWildcard => 2,
+        _ => 3,
+    }
+}
+
+/// Picks the server the way nginx does: exact name, longest leading wildcard, longest trailing
+/// wildcard, first matching regex, then the port's default server
+fn select_server<'a>(servers: &[(&'a Directive, Vec<String>, bool)], host: &str) -> (&'a Directive, ServerMatch) {
+    let mut best: Option<(usize, ServerMatchKind, usize, String)> = None;
+    for (index, (server, _, _)) in servers.iter().enumerate() {
+        for name in server_names(server) {
+            let Some((kind, length)) = name_match(&name, host) else { continue };
+            let better = match &best {
+                None => true,
+                // Regexes keep config order, so a later regex never replaces an earlier one
+                Some((_, best_kind, best_length, _)) => {
+                    rank(kind) < rank(*best_kind)
+                        || (rank(kind) == rank(*best_kind) && kind != ServerMatchKind::Regex && length > *best_length)
+                }
+            };
+            if better {
+                best = Some((index, kind, length, name));
+            }
+        }
+    }
+
+    let (index, kind, matched_name) = match best {
+        Some((index, kind, _, name)) => (index, kind, Some(name)),
+        None => match servers.iter().position(|(_, _, is_default)| *is_default) {
+            Some(index) => (index, ServerMatchKind::DefaultServer, None),
+            None => (0, ServerMatchKind::FirstOnPort, None),
+        },
+    };
+
+    let (server, listen, _) = &servers[index];
+    let matched = ServerMatch {
+        server_names: server_names(server),
+        listen: listen.clone(),
+        kind,
+        matched_name,
+        file: server.file.clone(),
+        line: server.line,
+    };
+    (server, matched)
+}
+
+/// Splits `location` arguments into the modifier and the path or pattern
+fn location_parts(location: &Directive) -> (&str, &str) {
+    match (location.arg(0), location.arg(1)) {
+        (Some(modifier @ ("=" | "^~" | "~" | "~*")), Some(pattern)) => (modifier, pattern),
+        (Some(arg), _) => {
+            // nginx also accepts the modifier glued to the path, e.g. `location =/`
+            for modifier in ["^~", "~*", "=", "~"] {
+                if let Some(pattern) = arg.strip_prefix(modifier).filter(|p| !p.is_empty()) {
+                    return (modifier, pattern);
+                }
+            }
+            ("", arg)
+        }
+        (None, _) => ("", ""),
+    }
+}
+
+enum Found {
+    /// An exact or regex location matched; outer levels don't look any further
+    Done,
+    /// Only a prefix location matched; outer levels still try their regexes
+    Prefix,
+    None,
+}
+
+fn describe_location(location: &Directive, kind: LocationMatchKind) -> LocationMatch {
+    LocationMatch {
+        pattern: location.args.join(" "),
+        kind,
+        file: location.file.clone(),
+        line: location.line,
+    }
+}
+
+/// `ngx_http_core_find_location`: the longest prefix (or an exact match) first, then that
+/// location's nested locations, then the regexes of this level in order unless `^~` was used
+fn find_location<'a>(
+    block: &'a Directive,
+    uri: &str,
+    chain: &mut Vec<(&'a Directive, LocationMatchKind)>,
+    notes: &mut Vec<String>,
+) -> Found {
+    let locations: Vec<&Directive> = block.children().iter().filter(|c| c.name == "location").collect();
+    let level = chain.len();
+
+    let mut longest: Option<(&Directive, &str, bool)> = None;
+    for location in &locations {
+        let (modifier, path) = location_parts(location);
+        let longest_length = longest.map_or(0, |(_, best, _)| best.len());
+        match modifier {
+            "=" if path == uri => {
+                chain.push((location, LocationMatchKind::Exact));
+                return Found::Done;
+            }
+            "" | "^~" if !path.starts_with('@') && uri.starts_with(path) && path.len() > longest_length => {
+                longest = Some((location, path, modifier == "^~"));
+            }
+            _ => {}
+        }
+    }
+
+    let mut no_regex = false;
+    let mut found = Found::None;
+    if let Some((location, _, stops_regex)) = longest {
+        no_regex = stops_regex;
+        let kind = if stops_regex { LocationMatchKind::PrefixNoRegex } else { LocationMatchKind::Prefix };
+        chain.push((location, kind));
+        if let Found::Done = find_location(location, uri, chain, notes) {
+            return Found::Done;
+        }
+        found = Found::Prefix;
+    }
+    if no_regex {
+        return found;
+    }
+
+    for location in &locations {
+        let (modifier, pattern) = location_parts(location);
+        if modifier != "~" && modifier != "~*" {
+            continue;
+        }
+        let regex = match RegexBuilder::new(pattern).case_insensitive(modifier == "~*").build() {
+            Ok(regex) => regex,
+            Err(_) => {
+                notes.push(format!(
+                    "Could not evaluate regex location {} at {}; nginx may match it",
+                    pattern,
+                    location.location()
+                ));
+                continue;
+            }
+        };
+        if regex.is_match(uri) {
+            // A regex replaces whatever prefix matched at this level and below
+            chain.truncate(level);
+            chain.push((location, LocationMatchKind::Regex));
+            find_location(location, uri, chain, notes);
+            return Found::Done;
+        }
+    }
+    found
+}
+
+/// The last value of `name` set directly in `block`
+fn own_directive(block: &Directive, name: &str) -> Option<EffectiveDirective> {
+    block.children().iter().rev().find(|child| child.name == name).map(|directive| EffectiveDirective {
+        value: directive.args.join(" "),
+        file: directive.file.clone(),
+        line: directive.line,
+    })
+}
+
+/// Resolves which server and location handle a request, following nginx's precedence rules
+pub fn resolve_route(parsed: &ParsedConfig, query: &RouteQuery) -> Result<RouteResolution, ResolveError> {
+    let host = query.host.trim().to_ascii_lowercase();
+    // Drop the port; IPv6 literals keep their brackets, as in server_name
+    let host = match host.find(']') {
+        Some(end) if host.starts_with('[') => &host[..=end],
+        _ => host.split(':').next().unwrap_or(""),
+    };
+    let host = host.trim_end_matches('.').to_string();
+    let uri = normalize_uri(query.uri.trim()).map_err(ResolveError::InvalidRequest)?;
+
+    let tree = parsed.expanded();
+    let http = tree
+        .iter()
+        .find(|directive| directive.name == "http")
+        .ok_or_else(|| ResolveError::Failed(format!("{} has no http block", parsed.root)))?;
+    let servers = servers_on_port(std::slice::from_ref(http), query.port);
+    if servers.is_empty() {
+        return Err(ResolveError::NotFound(format!("No server listens on port {}", query.port)));
+    }
+
+    let mut notes: Vec<String> = parsed
+        .errors
+        .iter()
+        .map(|e| format!("Part of the config could not be read: {}", e.message))
+        .collect();
+    let (server, server_match) = select_server(&servers, &host);
+    if server_match.listen.iter().any(|listen| !is_wildcard_listen(listen)) {
+        notes.push("The server listens on a specific address; a different server may win for connections to other addresses".to_string());
+    }
+
+    let mut chain = Vec::new();
+    find_location(server, &uri, &mut chain, &mut notes);
+
+    // root and alias inherit inward from http; proxy_pass and try_files only count where they are set
+    let mut blocks: Vec<&Directive> = vec![http, server];
+    blocks.extend(chain.iter().map(|(location, _)| *location));
+    let innermost = chain.last().map(|(location, _)| *location).unwrap_or(server);
+    let root = blocks.iter().rev().find_map(|block| own_directive(block, "root"));
+    let alias = chain.last().and_then(|(location, _)| own_directive(location, "alias"));
+    if innermost.children().iter().any(|child| child.name == "if") {
+        notes.push("The location contains if blocks, which can change the directives below at runtime".to_string());
+    }
+
+    Ok(RouteResolution {
+        host,
+        port: query.port,
+        uri,
+        server: server_match,
+        locations: chain.iter().map(|(location, kind)| describe_location(location, *kind)).collect(),
+        root: if alias.is_some() { None } else { root },
+        alias,
+        proxy_pass: own_directive(innermost, "proxy_pass"),
+        try_files: own_directive(innermost, "try_files"),
+        notes,
+    })
+}
+
+pub fn resolve_current_route(instance: &NginxInstance, query: &RouteQuery) -> Result<RouteResolution, ResolveError> {
+    if query.host.trim().is_empty() {
+        return Err(ResolveError::InvalidRequest("host is required".to_string()));
+    }
+    let root = nginx_config::find_main_config(instance)
+        .ok_or_else(|| ResolveError::Failed("Could not find nginx.conf".to_string()))?;
+    resolve_route(&nginx_config::parse_config(&root), query)
+}
+
+#[tauri::command]
+pub(crate) fn resolve_nginx_route(instance: Option<String>, query: RouteQuery) -> Result<RouteResolution, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+    resolve_current_route(&instance, &query).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nginx_config::ConfigFile;
+
+    fn parsed(content: &str) -> ParsedConfig {
+        ParsedConfig {
+            root: "nginx.conf".to_string(),
+            files: vec![ConfigFile {
+                path: "nginx.conf".to_string(),
+                directives: nginx_config::parse_config_str(content, "nginx.conf").unwrap(),
+            }],
+            errors: Vec::new(),
+            include_patterns: Vec::new(),
+        }
+    }
+
+    fn resolve(config: &ParsedConfig, host: &str, uri: &str) -> RouteResolution {
+        let query = RouteQuery { host: host.to_string(), port: 80, uri: uri.to_string() };
+        resolve_route(config, &query).unwrap()
+    }
+
+    fn locations(resolution: &RouteResolution) -> Vec<(&str, LocationMatchKind)> {
+        resolution.locations.iter().map(|location| (location.pattern.as_str(), location.kind)).collect()
+    }
+
+    #[test]
+    fn normalize_uri_decodes_and_resolves_dot_segments() {
+        assert_eq!(normalize_uri("/").unwrap(), "/");
+        assert_eq!(normalize_uri("/a//b/./c/../d?x=1#top").unwrap(), "/a/b/d");
+        assert_eq!(normalize_uri("/static/%2e%2e/app%20name").unwrap(), "/app name");
+        assert_eq!(normalize_uri("/docs/").unwrap(), "/docs/");
+        assert_eq!(normalize_uri("/docs/guide/..").unwrap(), "/docs/");
+        assert_eq!(normalize_uri("/docs/.").unwrap(), "/docs/");
+        assert_eq!(normalize_uri("/100%").unwrap(), "/100%");
+        assert!(normalize_uri("/..").is_err());
+        assert!(normalize_uri("/a/%2e%2e/..").is_err());
+        assert!(normalize_uri("index.html").is_err());
+    }
+
+    const SERVERS: &str = r"
+http {
+    server {
+        listen 80;
+        server_name first.example.com;
+    }
+    server {
+        listen 80 default_server;
+        server_name _;
+    }
+    server {
+        listen 80;
+        server_name www.example.com *.example.com;
+    }
+    server {
+        listen 80;
+        server_name *.api.example.com mail.*;
+    }
+    server {
+        listen 80;
+        server_name ~^(?<user>.+)\.users\.example\.com$;
+    }
+    server {
+        listen 80;
+        server_name ~^shop\. ~users;
+    }
+    server {
+        listen 8080;
+        server_name www.example.com;
+    }
+}
+";
+
+    #[test]
+    fn select_server_follows_name_precedence() {
+        let config = parsed(SERVERS);
+        let server = |host: &str| {
+            let resolution = resolve(&config, host, "/");
+            (resolution.server.kind, resolution.server.matched_name, resolution.server.line)
+        };
+
+        // Exact beats the wildcard that also matches
+        assert_eq!(server("WWW.example.com:80"), (ServerMatchKind::ExactName, Some("www.example.com".to_string()), 11));
+        // The longest leading wildcard wins
+        assert_eq!(server("v1.api.example.com"), (ServerMatchKind::LeadingWildcard, Some("*.api.example.com".to_string()), 15));
+        assert_eq!(server("blog.example.com"), (ServerMatchKind::LeadingWildcard, Some("*.example.com".to_string()), 11));
+        assert_eq!(server("mail.example.org"), (ServerMatchKind::TrailingWildcard, Some("mail.*".to_string()), 15));
+        // Wildcards beat regexes, and the first matching regex in config order wins
+        assert_eq!(server("ann.users.example.com").0, ServerMatchKind::LeadingWildcard);
+        assert_eq!(server("ann.users.example.org"), (ServerMatchKind::Regex, Some("~users".to_string()), 23));
+        assert_eq!(server("shop.users.net"), (ServerMatchKind::Regex, Some("~^shop\\.".to_string()), 23));
+        assert_eq!(server("unknown.org"), (ServerMatchKind::DefaultServer, None, 7));
+    }
+
+    #[test]
+    fn select_server_falls_back_to_the_first_server_on_the_port() {
+        let config = parsed(SERVERS);
+        let query = RouteQuery { host: "unknown.org".to_string(), port: 8080, uri: "/".to_string() };
+        let resolution = resolve_route(&config, &query).unwrap();
+        assert_eq!(resolution.server.kind, ServerMatchKind::FirstOnPort);
+        assert_eq!(resolution.server.line, 27);
+
+        let query = RouteQuery { host: "www.example.com".to_string(), port: 9090, uri: "/".to_string() };
+        assert!(matches!(resolve_route(&config, &query), Err(ResolveError::NotFound(_))));
+    }
+
+    const LOCATIONS: &str = r"
+http {
+    root /srv/default;
+    server {
+        listen 80;
+        server_name example.com;
+        location / {
+            try_files $uri /index.html;
+        }
+        location = /health {
+            return 200;
+        }
+        location /static/ {
+            root /srv/static;
+        }
+        location ^~ /static/vendor/ {
+            root /srv/vendor;
+        }
+        location /app/ {
+            proxy_pass http://app;
+            location /app/assets/ {
+                alias /srv/assets/;
+            }
+            location ~ \.json$ {
+                proxy_pass http://api;
+            }
+        }
+        location ~ \.(png|jpg)$ {
+            expires 30d;
+        }
+        location ~* \.PNG$ {
+            expires 1d;
+        }
+    }
+}
+";
+
+    #[test]
+    fn find_location_follows_nginx_precedence() {
+        use LocationMatchKind::*;
+        let config = parsed(LOCATIONS);
+        let matched = |uri: &str| resolve(&config, "example.com", uri);
+
+        assert_eq!(locations(&matched("/health")), vec![("= /health", Exact)]);
+        assert_eq!(locations(&matched("/health/more")), vec![("/", Prefix)]);
+        assert_eq!(locations(&matched("/static/site.css")), vec![("/static/", Prefix)]);
+        // Longest prefix, then the regexes in config order
+        assert_eq!(locations(&matched("/static/logo.png")), vec![("~ \\.(png|jpg)$", Regex)]);
+        assert_eq!(locations(&matched("/static/LOGO.PNG")), vec![("~* \\.PNG$", Regex)]);
+        // `^~` stops the regex search
+        let vendor = matched("/static/vendor/logo.png");
+        assert_eq!(locations(&vendor), vec![("^~ /static/vendor/", PrefixNoRegex)]);
+        assert_eq!(vendor.root.map(|root| root.value), Some("/srv/vendor".to_string()));
+    }
+
+    #[test]
+    fn find_location_checks_nested_locations_before_outer_regexes() {
+        use LocationMatchKind::*;
+        let config = parsed(LOCATIONS);
+        let matched = |uri: &str| resolve(&config, "example.com", uri);
+
+        let assets = matched("/app/assets/app.js");
+        assert_eq!(locations(&assets), vec![("/app/", Prefix), ("/app/assets/", Prefix)]);
+        assert_eq!(assets.alias.map(|alias| alias.value), Some("/srv/assets/".to_string()));
+        assert!(assets.root.is_none());
+
+        // A nested regex wins before the outer level gets to try its own
+        let data = matched("/app/data.json");
+        assert_eq!(locations(&data), vec![("/app/", Prefix), ("~ \\.json$", Regex)]);
+        assert_eq!(data.proxy_pass.map(|proxy| proxy.value), Some("http://api".to_string()));
+
+        // Only a nested prefix matched, so the outer regex still overrides the whole chain
+        let image = matched("/app/assets/logo.png");
+        assert_eq!(locations(&image), vec![("~ \\.(png|jpg)$", Regex)]);
+        assert_eq!(image.root.map(|root| root.value), Some("/srv/default".to_string()));
+        assert!(image.proxy_pass.is_none());
+    }
+}
@@ -1,125 +1,882 @@
-use std::process::Command;
-use serde::Deserialize;
+use std::process::{Command, Stdio};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::sync::Mutex;
+use chrono::{DateTime, Local, TimeZone};
+use futures_util::{stream, Stream, StreamExt};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::env::consts::OS;
+use tokio::io::AsyncBufReadExt;
+use tokio::task::JoinHandle;
+
+use crate::event_bus::{self, RustinxEvent};
+use crate::instances;
+use crate::log_search::parse_time_bound;
+
+// Units that may always be viewed; RUSTINX_EXTRA_UNITS (comma separated) adds more
+const DEFAULT_UNITS: [&str; 1] = ["nginx.service"];
+
+const MAX_UNIT_NAME_LENGTH: usize = 256;
+const MAX_GREP_LENGTH: usize = 512;
+const MAX_CURSOR_LENGTH: usize = 512;
+
+const DEFAULT_NUM_LINES: u32 = 100;
+
+// How far back `log show` looks when neither `since` nor a cursor narrows it
+const MACOS_DEFAULT_WINDOW: &str = "1d";
+const MAX_NUM_LINES: u32 = 10_000;
+
+// Syslog priorities as journald numbers them, most severe first
+const PRIORITY_NAMES: [&str; 8] = ["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+
+lazy_static::lazy_static! {
+    // systemd unit name characters; the first one can't be `-` so a name is never read as a flag
+    static ref UNIT_NAME: Regex = Regex::new(r"^[A-Za-z0-9_@][A-Za-z0-9:_.@\-]*$").unwrap();
+    // journald cursors are `key=value` pairs joined with `;`
+    static ref JOURNAL_CURSOR: Regex = Regex::new(r"^[A-Za-z0-9=;]+$").unwrap();
+    // The desktop app follows one journal at a time; starting another follow replaces it
+    static ref DESKTOP_FOLLOW: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+}
 
 #[derive(Debug, Deserialize)]
 pub struct SystemdLogOptions {
     pub service_name: String,
+    /// Accepted for compatibility; output is always captured without a pager
+    #[serde(default)]
     pub no_pager: bool,
     pub num_lines: Option<u32>,
     pub since: Option<String>,
     pub until: Option<String>,
+    #[serde(default)]
     pub reverse: bool,
+    /// Continue with the entries after this one, the `cursor` of a previous page
+    pub after_cursor: Option<String>,
+    /// Most verbose priority to include, by name (`warning`) or number (`4`)
+    pub priority: Option<String>,
+    /// Only entries whose message matches this pattern (`journalctl --grep`)
+    pub grep: Option<String>,
 }
 
-#[tauri::command]
-pub fn get_systemd_logs(options: SystemdLogOptions) -> Result<String, String> {
-    match OS {
-        "linux" => get_linux_logs(options),
-        "macos" => get_macos_logs(options),
-        _ => Err("Unsupported operating system".to_string()),
+/// One journal record, shared by the Linux journal and the macOS unified log
+#[derive(Clone, Debug, Serialize)]
+pub struct JournalEntry {
+    /// Opaque position to continue from with `after_cursor`
+    pub cursor: Option<String>,
+    /// Microseconds since the epoch
+    pub timestamp_us: Option<u64>,
+    pub priority: Option<u8>,
+    pub pid: Option<u32>,
+    pub unit: Option<String>,
+    pub identifier: Option<String>,
+    pub message: String,
+}
+
+impl JournalEntry {
+    pub fn timestamp_secs(&self) -> Option<i64> {
+        self.timestamp_us.map(|us| (us / 1_000_000) as i64)
+    }
+
+    pub fn priority_name(&self) -> Option<&'static str> {
+        self.priority.and_then(|p| PRIORITY_NAMES.get(p as usize).copied())
     }
 }
 
-fn get_linux_logs(options: SystemdLogOptions) -> Result<String, String> {
-    let mut cmd = Command::new("journalctl");
+#[derive(Clone, Debug, Serialize)]
+pub struct JournalPage {
+    pub entries: Vec<JournalEntry>,
+    /// Cursor of the newest entry returned; pass it back as `after_cursor` for what came next
+    pub cursor: Option<String>,
+}
 
-    cmd.arg("-u").arg(&options.service_name);
+/// A validated journal query, translated to `journalctl` arguments
+#[derive(Clone, Debug, Default)]
+pub(crate) struct JournalQuery {
+    pub(crate) unit: String,
+    pub(crate) since: Option<i64>,
+    pub(crate) until: Option<i64>,
+    pub(crate) priority: Option<u8>,
+    pub(crate) grep: Option<String>,
+    pub(crate) after_cursor: Option<String>,
+}
 
-    if options.no_pager {
-        cmd.arg("--no-pager");
+fn parse_priority(value: &str) -> Result<u8, String> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Ok(number) = value.parse::<u8>() {
+        if (number as usize) < PRIORITY_NAMES.len() {
+            return Ok(number);
+        }
     }
+    let value = match value.as_str() {
+        "error" => "err",
+        "warn" => "warning",
+        other => other,
+    };
+    PRIORITY_NAMES
+        .iter()
+        .position(|name| *name == value)
+        .map(|p| p as u8)
+        .ok_or_else(|| format!("Invalid priority '{}': expected 0-7 or one of {}", value, PRIORITY_NAMES.join(", ")))
+}
 
-    if let Some(num_lines) = options.num_lines {
-        cmd.arg("-n").arg(num_lines.to_string());
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Checks a unit name and adds the `.service` suffix systemd assumes when there is none
+fn normalize_unit(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > MAX_UNIT_NAME_LENGTH || !UNIT_NAME.is_match(name) {
+        return Err(format!("Invalid unit name '{}'", name));
+    }
+    if name.contains('.') {
+        Ok(name.to_string())
+    } else {
+        Ok(format!("{}.service", name))
     }
+}
 
-    if let Some(since) = &options.since {
-        if !since.trim().is_empty() {
-            let formatted_since = since.replace('T', " ");
-            cmd.arg("--since").arg(formatted_since);
+/// Units whose logs may be viewed: nginx, the unit of every configured instance, plus whatever
+/// RUSTINX_EXTRA_UNITS lists
+pub fn allowed_units() -> Vec<String> {
+    let mut units: Vec<String> = DEFAULT_UNITS.iter().map(|u| u.to_string()).collect();
+    for instance in instances::list_instances() {
+        match normalize_unit(&instance.unit) {
+            Ok(unit) if !units.contains(&unit) => units.push(unit),
+            Ok(_) => {}
+            Err(e) => eprintln!("Ignoring the unit of instance {}: {}", instance.id, e),
+        }
+    }
+    if let Ok(extra) = std::env::var("RUSTINX_EXTRA_UNITS") {
+        for unit in extra.split(',').map(str::trim).filter(|u| !u.is_empty()) {
+            match normalize_unit(unit) {
+                Ok(unit) if !units.contains(&unit) => units.push(unit),
+                Ok(_) => {}
+                Err(e) => eprintln!("Ignoring RUSTINX_EXTRA_UNITS entry: {}", e),
+            }
         }
     }
+    units
+}
+
+fn allowed_unit(name: &str) -> Result<String, String> {
+    let unit = normalize_unit(name)?;
+    let allowed = allowed_units();
+    if allowed.contains(&unit) {
+        Ok(unit)
+    } else {
+        Err(format!(
+            "Unit '{}' is not allowed; allowed units are {} (extend with RUSTINX_EXTRA_UNITS)",
+            unit,
+            allowed.join(", ")
+        ))
+    }
+}
+
+fn validate_cursor(cursor: &str) -> Result<String, String> {
+    if cursor.len() > MAX_CURSOR_LENGTH || !JOURNAL_CURSOR.is_match(cursor) {
+        return Err("Invalid cursor".to_string());
+    }
+    Ok(cursor.to_string())
+}
+
+fn validate_grep(pattern: &str) -> Result<String, String> {
+    if pattern.len() > MAX_GREP_LENGTH {
+        return Err(format!("grep pattern is longer than {} characters", MAX_GREP_LENGTH));
+    }
+    if pattern.chars().any(char::is_control) {
+        return Err("grep pattern contains control characters".to_string());
+    }
+    Ok(pattern.to_string())
+}
+
+/// Rejects anything in `options` that shouldn't reach `journalctl` or `log`; errors are meant for a 400
+pub fn validate_options(options: &SystemdLogOptions) -> Result<(), String> {
+    JournalQuery::from_options(options).map(|_| ())
+}
 
-    if let Some(until) = &options.until {
-        if !until.trim().is_empty() {
-            let formatted_until = until.replace('T', " ");
-            cmd.arg("--until").arg(formatted_until);
+impl JournalQuery {
+    pub(crate) fn from_options(options: &SystemdLogOptions) -> Result<Self, String> {
+        let query = JournalQuery {
+            unit: allowed_unit(&options.service_name)?,
+            since: non_empty(&options.since).map(parse_time_bound).transpose()?,
+            until: non_empty(&options.until).map(parse_time_bound).transpose()?,
+            priority: non_empty(&options.priority).map(parse_priority).transpose()?,
+            grep: non_empty(&options.grep).map(validate_grep).transpose()?,
+            after_cursor: non_empty(&options.after_cursor).map(validate_cursor).transpose()?,
+        };
+        if let (Some(since), Some(until)) = (query.since, query.until) {
+            if since > until {
+                return Err("'since' must not be later than 'until'".to_string());
+            }
         }
+        Ok(query)
     }
 
-    if options.reverse {
-        cmd.arg("--reverse");
+    fn command(&self) -> Command {
+        let mut cmd = Command::new("journalctl");
+        cmd.arg("-u").arg(&self.unit).arg("--no-pager").arg("-o").arg("json");
+        if let Some(since) = self.since {
+            cmd.arg("--since").arg(format!("@{}", since));
+        }
+        if let Some(until) = self.until {
+            cmd.arg("--until").arg(format!("@{}", until));
+        }
+        if let Some(priority) = self.priority {
+            cmd.arg("-p").arg(priority.to_string());
+        }
+        if let Some(grep) = &self.grep {
+            cmd.arg("--grep").arg(grep);
+        }
+        if let Some(cursor) = &self.after_cursor {
+            cmd.arg("--after-cursor").arg(cursor);
+        }
+        cmd
+    }
+}
+
+fn field_str(record: &Value, key: &str) -> Option<String> {
+    match record.get(key)? {
+        Value::String(value) => Some(value.clone()),
+        // Fields that aren't valid UTF-8 come as an array of bytes
+        Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes.iter().filter_map(|b| b.as_u64().map(|b| b as u8)).collect();
+            Some(String::from_utf8_lossy(&bytes).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Parses one line of `journalctl -o json`
+pub(crate) fn parse_journal_json(line: &str) -> Option<JournalEntry> {
+    let record: Value = serde_json::from_str(line).ok()?;
+    Some(JournalEntry {
+        cursor: field_str(&record, "__CURSOR"),
+        timestamp_us: field_str(&record, "__REALTIME_TIMESTAMP").and_then(|t| t.parse().ok()),
+        priority: field_str(&record, "PRIORITY").and_then(|p| p.parse().ok()),
+        pid: field_str(&record, "_PID").and_then(|p| p.parse().ok()),
+        unit: field_str(&record, "_SYSTEMD_UNIT").or_else(|| field_str(&record, "UNIT")),
+        identifier: field_str(&record, "SYSLOG_IDENTIFIER"),
+        message: field_str(&record, "MESSAGE").unwrap_or_default(),
+    })
+}
+
+/// Runs `journalctl` and hands each entry to `visit` until it returns `false`.
+/// `tail` limits the output to the newest entries, as `-n` does.
+pub(crate) fn read_journal(
+    query: &JournalQuery,
+    tail: Option<u32>,
+    mut visit: impl FnMut(JournalEntry) -> bool,
+) -> Result<(), String> {
+    let mut cmd = query.command();
+    if let Some(lines) = tail {
+        cmd.arg("-n").arg(lines.to_string());
     }
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute journalctl: {}", e))?;
 
-    match cmd.output() {
-        Ok(output) => {
-            if output.status.success() {
-                let logs = String::from_utf8_lossy(&output.stdout).to_string();
-                Ok(logs)
-            } else {
-                let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-                Err(format!("journalctl error: {}", error_message.trim()))
+    let mut stopped_early = false;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            let line = line.map_err(|e| format!("Failed to read journalctl output: {}", e))?;
+            if let Some(entry) = parse_journal_json(&line) {
+                if !visit(entry) {
+                    stopped_early = true;
+                    break;
+                }
             }
         }
-        Err(e) => Err(format!("Failed to execute journalctl: {}", e)),
     }
+
+    if stopped_early {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Ok(());
+    }
+
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    let status = child.wait().map_err(|e| format!("Failed to execute journalctl: {}", e))?;
+    // `--grep` exits non-zero without a message when nothing matched
+    if !status.success() && !stderr.trim().is_empty() {
+        return Err(format!("journalctl error: {}", stderr.trim()));
+    }
+    Ok(())
 }
 
-fn get_macos_logs(options: SystemdLogOptions) -> Result<String, String> {
-    let mut cmd = Command::new("log");
+fn get_linux_logs(options: &SystemdLogOptions) -> Result<JournalPage, String> {
+    let query = JournalQuery::from_options(options)?;
+    let limit = options.num_lines.unwrap_or(DEFAULT_NUM_LINES).clamp(1, MAX_NUM_LINES);
 
-    // Strip the .service or .socket suffix from the service name
-    let service_name = options.service_name.trim_end_matches(".service").trim_end_matches(".socket");
+    let mut entries = Vec::new();
+    if query.after_cursor.is_some() {
+        // Paging forward: the first `limit` entries after the cursor
+        read_journal(&query, None, |entry| {
+            entries.push(entry);
+            entries.len() < limit as usize
+        })?;
+    } else {
+        read_journal(&query, Some(limit), |entry| {
+            entries.push(entry);
+            true
+        })?;
+    }
 
-    // Use the `show` subcommand to query the unified logging system on macOS
-    cmd.arg("show").arg("--predicate").arg(format!("process == '{}'", service_name));
+    Ok(finish_page(entries, &query, options.reverse))
+}
 
-    if let Some(num_lines) = options.num_lines {
-        // macOS does not support direct line counts, but we can simulate it by specifying a time duration
-        cmd.arg("--last").arg(format!("{}s", num_lines));  // Fetch the last X seconds of logs
+/// Entries arrive oldest first; `reverse` flips them only after the page has been cut
+fn finish_page(mut entries: Vec<JournalEntry>, query: &JournalQuery, reverse: bool) -> JournalPage {
+    let cursor = entries
+        .last()
+        .and_then(|entry| entry.cursor.clone())
+        .or_else(|| query.after_cursor.clone());
+    if reverse {
+        entries.reverse();
     }
+    JournalPage { entries, cursor }
+}
 
-    if let Some(since) = &options.since {
-        if !since.trim().is_empty() {
-            // Ensure the format matches macOS expectations
-            let formatted_since = format_datetime(since);
-            cmd.arg("--start").arg(formatted_since);
+pub fn query_systemd_logs(options: &SystemdLogOptions) -> Result<JournalPage, String> {
+    match OS {
+        "linux" => get_linux_logs(options),
+        "macos" => get_macos_logs(options),
+        _ => Err("Unsupported operating system".to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn get_systemd_logs(options: SystemdLogOptions) -> Result<JournalPage, String> {
+    query_systemd_logs(&options)
+}
+
+/// Streams entries as they are written, starting after `after_cursor` or with new entries only.
+/// `journalctl` is killed when the stream is dropped.
+pub fn follow_journal(options: &SystemdLogOptions) -> Result<impl Stream<Item = JournalEntry>, String> {
+    if OS != "linux" {
+        return Err("Following logs is only supported with journald".to_string());
+    }
+    let query = JournalQuery::from_options(options)?;
+    let mut cmd = query.command();
+    cmd.arg("--follow");
+    if query.after_cursor.is_none() {
+        cmd.arg("-n").arg("0");
+    }
+
+    let mut child = tokio::process::Command::from(cmd)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to execute journalctl: {}", e))?;
+    let stdout = child.stdout.take().ok_or("Failed to capture journalctl output")?;
+    let lines = tokio::io::BufReader::new(stdout).lines();
+
+    // The child rides along in the stream state so it lives exactly as long as the stream
+    Ok(stream::unfold((child, lines), |(child, mut lines)| async move {
+        loop {
+            match lines.next_line().await {
+                Ok(Some(line)) => {
+                    if let Some(entry) = parse_journal_json(&line) {
+                        return Some((entry, (child, lines)));
+                    }
+                }
+                _ => return None,
+            }
         }
+    }))
+}
+
+/// Desktop follow mode: publishes new entries as `journal_entry` events until stopped
+#[tauri::command]
+pub(crate) async fn follow_systemd_logs(options: SystemdLogOptions) -> Result<(), String> {
+    let entries = follow_journal(&options)?;
+    let task = tokio::spawn(async move {
+        let mut entries = Box::pin(entries);
+        while let Some(entry) = entries.next().await {
+            event_bus::publish(RustinxEvent::JournalEntry(entry));
+        }
+    });
+
+    let mut current = DESKTOP_FOLLOW.lock().map_err(|e| e.to_string())?;
+    if let Some(previous) = current.replace(task) {
+        previous.abort();
     }
+    Ok(())
+}
 
-    if let Some(until) = &options.until {
-        if !until.trim().is_empty() {
-            // Ensure the format matches macOS expectations
-            let formatted_until = format_datetime(until);
-            cmd.arg("--end").arg(formatted_until);
+#[tauri::command]
+pub(crate) fn get_allowed_units() -> Vec<String> {
+    allowed_units()
+}
+
+#[tauri::command]
+pub(crate) fn stop_following_systemd_logs() -> Result<(), String> {
+    let mut current = DESKTOP_FOLLOW.lock().map_err(|e| e.to_string())?;
+    if let Some(task) = current.take() {
+        task.abort();
+    }
+    Ok(())
+}
+
+/// Unified log message types as syslog priorities
+fn unified_log_priority(message_type: &str) -> Option<u8> {
+    match message_type {
+        "Fault" => Some(2),
+        "Error" => Some(3),
+        "Default" => Some(5),
+        "Info" => Some(6),
+        "Debug" => Some(7),
+        _ => None,
+    }
+}
+
+/// Parses one line of `log show --style ndjson`. The unified log has no cursors; the entry gets
+/// one from `UnifiedLogFilter`, which knows the entries before it.
+fn parse_unified_log_json(line: &str) -> Option<JournalEntry> {
+    let record: Value = serde_json::from_str(line).ok()?;
+    // The trailing summary object has no timestamp
+    let timestamp = record.get("timestamp")?.as_str()?;
+    let timestamp_us = DateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.f%z")
+        .ok()
+        .map(|time| time.timestamp_micros() as u64)?;
+    let identifier = record
+        .get("processImagePath")
+        .and_then(Value::as_str)
+        .and_then(|path| path.rsplit('/').next())
+        .map(str::to_string);
+
+    Some(JournalEntry {
+        cursor: None,
+        timestamp_us: Some(timestamp_us),
+        priority: record.get("messageType").and_then(Value::as_str).and_then(unified_log_priority),
+        pid: record.get("processID").and_then(Value::as_u64).map(|pid| pid as u32),
+        unit: None,
+        identifier,
+        message: record.get("eventMessage").and_then(Value::as_str).unwrap_or_default().to_string(),
+    })
+}
+
+/// Position in the unified log: everything before `timestamp_us`, plus the first `seen` entries
+/// stamped with exactly that microsecond. Busy processes log several entries per microsecond, so
+/// the timestamp alone would drop the rest of them on the next page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct UnifiedLogCursor {
+    timestamp_us: u64,
+    seen: usize,
+}
+
+impl UnifiedLogCursor {
+    /// Reads `t=<microseconds>;n=<count>`
+    fn parse(cursor: &str) -> Result<Self, String> {
+        let invalid = || "Invalid cursor".to_string();
+        let (timestamp, seen) = cursor
+            .strip_prefix("t=")
+            .and_then(|rest| rest.split_once(";n="))
+            .ok_or_else(invalid)?;
+        Ok(UnifiedLogCursor {
+            timestamp_us: timestamp.parse().map_err(|_| invalid())?,
+            seen: seen.parse().map_err(|_| invalid())?,
+        })
+    }
+
+    fn format(&self) -> String {
+        format!("t={};n={}", self.timestamp_us, self.seen)
+    }
+}
+
+/// Turns `log show` lines into entries, skipping those up to the query's cursor and applying
+/// priority and grep, which the unified log has no flags for
+struct UnifiedLogFilter {
+    after: Option<UnifiedLogCursor>,
+    priority: Option<u8>,
+    grep: Option<Regex>,
+    // Where the previous entry was; counts every entry, filtered or not, so cursors stay stable
+    position: Option<UnifiedLogCursor>,
+}
+
+impl UnifiedLogFilter {
+    fn new(query: &JournalQuery) -> Result<Self, String> {
+        let grep = match &query.grep {
+            // Smart case, like journalctl: case-insensitive unless the pattern has capitals
+            Some(pattern) => Some(
+                RegexBuilder::new(pattern)
+                    .case_insensitive(!pattern.chars().any(char::is_uppercase))
+                    .build()
+                    .map_err(|e| format!("Invalid grep pattern: {}", e))?,
+            ),
+            None => None,
+        };
+        Ok(UnifiedLogFilter {
+            after: query.after_cursor.as_deref().map(UnifiedLogCursor::parse).transpose()?,
+            priority: query.priority,
+            grep,
+            position: None,
+        })
+    }
+
+    fn accept(&mut self, line: &str) -> Option<JournalEntry> {
+        let mut entry = parse_unified_log_json(line)?;
+        let timestamp_us = entry.timestamp_us.unwrap_or(0);
+        let position = match self.position {
+            Some(previous) if previous.timestamp_us == timestamp_us => UnifiedLogCursor {
+                timestamp_us,
+                seen: previous.seen + 1,
+            },
+            _ => UnifiedLogCursor { timestamp_us, seen: 1 },
+        };
+        self.position = Some(position);
+        entry.cursor = Some(position.format());
+
+        if self.after.is_some_and(|after| position <= after) {
+            return None;
+        }
+        if let Some(max) = self.priority {
+            if entry.priority.is_some_and(|p| p > max) {
+                return None;
+            }
         }
+        if self.grep.as_ref().is_some_and(|grep| !grep.is_match(&entry.message)) {
+            return None;
+        }
+        Some(entry)
     }
+}
 
-    if options.reverse {
-        cmd.arg("--reverse");
+/// The unified log knows processes, not units. The unit passed validation, so it has no quotes
+/// or operators and can't change the meaning of the predicate.
+fn unified_log_predicate(unit: &str) -> String {
+    let process = unit.trim_end_matches(".service").trim_end_matches(".socket");
+    format!("process == \"{}\"", process)
+}
+
+/// `log show` counterpart of `read_journal`. Priority and grep are applied here because the
+/// unified log has no equivalent flags.
+fn read_unified_log(query: &JournalQuery, mut visit: impl FnMut(JournalEntry) -> bool) -> Result<(), String> {
+    let mut filter = UnifiedLogFilter::new(query)?;
+    let after_us = filter.after.map(|after| after.timestamp_us);
+
+    let mut cmd = Command::new("log");
+    cmd.arg("show")
+        .arg("--style")
+        .arg("ndjson")
+        .arg("--predicate")
+        .arg(unified_log_predicate(&query.unit));
+
+    let start = match (query.since, after_us) {
+        (Some(since), Some(after)) => Some(since.max((after / 1_000_000) as i64)),
+        (since, after) => since.or(after.map(|us| (us / 1_000_000) as i64)),
+    };
+    match start {
+        Some(start) => {
+            cmd.arg("--start").arg(format_datetime(start)?);
+        }
+        // Without a start `log show` walks the entire log store
+        None => {
+            cmd.arg("--last").arg(MACOS_DEFAULT_WINDOW);
+        }
     }
+    if let Some(until) = query.until {
+        cmd.arg("--end").arg(format_datetime(until)?);
+    }
+
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute log command: {}", e))?;
 
-    match cmd.output() {
-        Ok(output) => {
-            if output.status.success() {
-                let logs = String::from_utf8_lossy(&output.stdout).to_string();
-                Ok(logs)
-            } else {
-                let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-                Err(format!("log command error: {}", error_message.trim()))
+    let mut stopped_early = false;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            let line = line.map_err(|e| format!("Failed to read log output: {}", e))?;
+            let entry = match filter.accept(&line) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if !visit(entry) {
+                stopped_early = true;
+                break;
             }
         }
-        Err(e) => Err(format!("Failed to execute log command: {}", e)),
     }
+
+    if stopped_early {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Ok(());
+    }
+
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    let status = child.wait().map_err(|e| format!("Failed to execute log command: {}", e))?;
+    if !status.success() {
+        return Err(format!("log command error: {}", stderr.trim()));
+    }
+    Ok(())
 }
 
-fn format_datetime(datetime: &str) -> String {
-    if datetime.len() == 10 {
-        // If the datetime only includes the date (YYYY-MM-DD), append a time
-        format!("{} 00:00:00", datetime)
+fn get_macos_logs(options: &SystemdLogOptions) -> Result<JournalPage, String> {
+    let query = JournalQuery::from_options(options)?;
+    let limit = options.num_lines.unwrap_or(DEFAULT_NUM_LINES).clamp(1, MAX_NUM_LINES) as usize;
+
+    let mut entries = VecDeque::new();
+    if query.after_cursor.is_some() {
+        read_unified_log(&query, |entry| {
+            entries.push_back(entry);
+            entries.len() < limit
+        })?;
     } else {
-        datetime.to_string()
+        // `log show` can't count from the end, so keep a window of the newest `limit` entries
+        read_unified_log(&query, |entry| {
+            keep_newest(&mut entries, entry, limit);
+            true
+        })?;
+    }
+
+    Ok(finish_page(entries.into(), &query, options.reverse))
+}
+
+fn keep_newest(entries: &mut VecDeque<JournalEntry>, entry: JournalEntry, limit: usize) {
+    if entries.len() == limit {
+        entries.pop_front();
     }
+    entries.push_back(entry);
 }
 
+/// `log show` takes local `YYYY-MM-DD HH:MM:SS`
+fn format_datetime(timestamp: i64) -> Result<String, String> {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .ok_or_else(|| format!("Invalid time {}", timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(value: Value) -> SystemdLogOptions {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn unit_names_are_checked_and_completed() {
+        assert_eq!(normalize_unit("nginx").unwrap(), "nginx.service");
+        assert_eq!(normalize_unit(" nginx.service ").unwrap(), "nginx.service");
+        assert_eq!(normalize_unit("php-fpm@8.2.service").unwrap(), "php-fpm@8.2.service");
+
+        for name in ["", "-flag", "--since=today", "nginx\"", "'nginx'", "nginx\"; rm -rf /", "nginx service", "nginx/.."] {
+            assert!(normalize_unit(name).is_err(), "{:?} should be rejected", name);
+        }
+        assert!(normalize_unit(&"a".repeat(MAX_UNIT_NAME_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn only_listed_units_may_be_viewed() {
+        assert_eq!(allowed_unit("nginx").unwrap(), "nginx.service");
+        assert!(allowed_unit("sshd.service").unwrap_err().contains("not allowed"));
+        assert!(allowed_unit("-u").is_err());
+
+        std::env::set_var("RUSTINX_EXTRA_UNITS", "rustinx-test-extra, bad\"unit ,");
+        let allowed = allowed_units();
+        std::env::remove_var("RUSTINX_EXTRA_UNITS");
+        assert!(allowed.contains(&"nginx.service".to_string()));
+        assert!(allowed.contains(&"rustinx-test-extra.service".to_string()));
+        assert!(!allowed.iter().any(|unit| unit.contains('"')));
+    }
+
+    #[test]
+    fn cursors_and_grep_patterns_are_checked() {
+        let cursor = "s=6a3f0c2bd1e84c0f;i=1c2e;b=0f9e8d7c6b5a;m=2b1e3f4;t=5f1e2d3c4b5a6;x=9a8b7c6d";
+        assert_eq!(validate_cursor(cursor).unwrap(), cursor);
+        for bad in ["--after-cursor", "s=1 i=2", "s='1'", "s=1\n", "s=1&&x"] {
+            assert!(validate_cursor(bad).is_err(), "{:?} should be rejected", bad);
+        }
+        assert!(validate_cursor(&"a".repeat(MAX_CURSOR_LENGTH + 1)).is_err());
+
+        assert_eq!(validate_grep("upstream (timed out|refused)").unwrap(), "upstream (timed out|refused)");
+        assert!(validate_grep("error\n--since").is_err());
+        assert!(validate_grep("\u{1b}[31m").is_err());
+        assert!(validate_grep(&"a".repeat(MAX_GREP_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn queries_are_built_from_valid_options_only() {
+        let query = JournalQuery::from_options(&options(serde_json::json!({
+            "service_name": "nginx",
+            "since": "100",
+            "until": "200",
+            "priority": "warn",
+            "grep": " ",
+        })))
+        .unwrap();
+        assert_eq!(query.unit, "nginx.service");
+        assert_eq!((query.since, query.until), (Some(100), Some(200)));
+        assert_eq!(query.priority, Some(4));
+        assert_eq!(query.grep, None);
+
+        let invalid = [
+            serde_json::json!({ "service_name": "nginx", "since": "200", "until": "100" }),
+            serde_json::json!({ "service_name": "nginx", "priority": "loud" }),
+            serde_json::json!({ "service_name": "nginx", "after_cursor": "--follow" }),
+            serde_json::json!({ "service_name": "sshd" }),
+        ];
+        for value in invalid {
+            assert!(JournalQuery::from_options(&options(value.clone())).is_err(), "{} should be rejected", value);
+        }
+    }
+
+    fn entry(cursor: &str, message: &str) -> JournalEntry {
+        JournalEntry {
+            cursor: Some(cursor.to_string()),
+            timestamp_us: None,
+            priority: None,
+            pid: None,
+            unit: None,
+            identifier: None,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn journal_json_lines_are_parsed() {
+        let line = r#"{"__CURSOR":"s=ab;i=1f;b=cd;m=12;t=5f;x=99","__REALTIME_TIMESTAMP":"1700000000123456","PRIORITY":"3","_PID":"812","_SYSTEMD_UNIT":"nginx.service","SYSLOG_IDENTIFIER":"nginx","MESSAGE":"connect() failed (111: Connection refused)"}"#;
+        let parsed = parse_journal_json(line).unwrap();
+        assert_eq!(parsed.cursor.as_deref(), Some("s=ab;i=1f;b=cd;m=12;t=5f;x=99"));
+        assert_eq!(parsed.timestamp_us, Some(1_700_000_000_123_456));
+        assert_eq!(parsed.timestamp_secs(), Some(1_700_000_000));
+        assert_eq!(parsed.priority_name(), Some("err"));
+        assert_eq!(parsed.pid, Some(812));
+        assert_eq!(parsed.unit.as_deref(), Some("nginx.service"));
+        assert_eq!(parsed.identifier.as_deref(), Some("nginx"));
+        assert_eq!(parsed.message, "connect() failed (111: Connection refused)");
+
+        // Messages that aren't valid UTF-8 come as byte arrays; systemd's own lines name the unit in UNIT
+        let line = r#"{"__CURSOR":"s=ab;i=20","MESSAGE":[104,105,255,33],"UNIT":"nginx.service","_PID":"1"}"#;
+        let parsed = parse_journal_json(line).unwrap();
+        assert_eq!(parsed.message, "hi\u{fffd}!");
+        assert_eq!(parsed.unit.as_deref(), Some("nginx.service"));
+        assert_eq!(parsed.timestamp_us, None);
+        assert_eq!(parsed.priority, None);
+
+        assert!(parse_journal_json("-- No entries --").is_none());
+        assert_eq!(parse_journal_json("{}").unwrap().message, "");
+    }
+
+    #[test]
+    fn pages_continue_from_their_newest_entry() {
+        let query = JournalQuery::default();
+        let page = finish_page(vec![entry("c1", "first"), entry("c2", "second")], &query, false);
+        assert_eq!(page.cursor.as_deref(), Some("c2"));
+        assert_eq!(page.entries.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(), vec!["first", "second"]);
+
+        // Reversed pages still hand out the newest cursor
+        let page = finish_page(vec![entry("c1", "first"), entry("c2", "second")], &query, true);
+        assert_eq!(page.cursor.as_deref(), Some("c2"));
+        assert_eq!(page.entries.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(), vec!["second", "first"]);
+
+        // Nothing new yet: keep polling from the same place
+        let query = JournalQuery { after_cursor: Some("c2".to_string()), ..JournalQuery::default() };
+        let page = finish_page(Vec::new(), &query, false);
+        assert_eq!(page.cursor.as_deref(), Some("c2"));
+        assert!(page.entries.is_empty());
+    }
+
+    fn unified_line(timestamp: &str, message_type: &str, message: &str) -> String {
+        serde_json::json!({
+            "timestamp": timestamp,
+            "messageType": message_type,
+            "eventMessage": message,
+            "processImagePath": "/opt/homebrew/bin/nginx",
+            "processID": 512,
+            "subsystem": "",
+        })
+        .to_string()
+    }
+
+    fn messages(entries: &[JournalEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.message.as_str()).collect()
+    }
+
+    #[test]
+    fn unified_log_lines_are_parsed() {
+        let parsed = parse_unified_log_json(&unified_line("2024-03-01 10:15:30.123456+0100", "Error", "bind() failed")).unwrap();
+        assert_eq!(parsed.timestamp_us, Some(1_709_284_530_123_456));
+        assert_eq!(parsed.priority_name(), Some("err"));
+        assert_eq!(parsed.pid, Some(512));
+        assert_eq!(parsed.identifier.as_deref(), Some("nginx"));
+        assert_eq!(parsed.message, "bind() failed");
+
+        // `log show` ends with a summary object
+        assert!(parse_unified_log_json(r#"{"count":1,"finished":1}"#).is_none());
+        assert!(parse_unified_log_json("Filtering the log data using \"process == nginx\"").is_none());
+    }
+
+    #[test]
+    fn unified_log_pages_keep_entries_sharing_the_boundary_microsecond() {
+        let lines = [
+            unified_line("2024-03-01 10:15:30.000001+0000", "Default", "one"),
+            unified_line("2024-03-01 10:15:30.000002+0000", "Default", "two"),
+            unified_line("2024-03-01 10:15:30.000002+0000", "Debug", "three"),
+            unified_line("2024-03-01 10:15:30.000002+0000", "Default", "four"),
+            unified_line("2024-03-01 10:15:30.000003+0000", "Default", "five"),
+        ];
+        let read = |query: &JournalQuery| -> Vec<JournalEntry> {
+            let mut filter = UnifiedLogFilter::new(query).unwrap();
+            lines.iter().filter_map(|line| filter.accept(line)).collect()
+        };
+
+        let everything = read(&JournalQuery::default());
+        let cursors: Vec<_> = everything.iter().map(|e| e.cursor.clone().unwrap()).collect();
+        assert_eq!(
+            cursors,
+            vec!["t=1709288130000001;n=1", "t=1709288130000002;n=1", "t=1709288130000002;n=2", "t=1709288130000002;n=3", "t=1709288130000003;n=1"]
+        );
+        for cursor in &cursors {
+            assert!(validate_cursor(cursor).is_ok());
+        }
+
+        // A page that ended halfway through the shared microsecond picks up with the rest of it
+        let query = JournalQuery { after_cursor: Some(cursors[2].clone()), ..JournalQuery::default() };
+        assert_eq!(messages(&read(&query)), vec!["four", "five"]);
+
+        // Filtered entries still count, so the cursors don't shift under a priority filter
+        let query = JournalQuery { priority: Some(5), ..JournalQuery::default() };
+        let filtered = read(&query);
+        assert_eq!(messages(&filtered), vec!["one", "two", "four", "five"]);
+        assert_eq!(filtered[2].cursor.as_deref(), Some("t=1709288130000002;n=3"));
+
+        let query = JournalQuery { grep: Some("fi".to_string()), after_cursor: Some(cursors[0].clone()), ..JournalQuery::default() };
+        assert_eq!(messages(&read(&query)), vec!["five"]);
+
+        for bad in ["t=1709288130000002", "t=x;n=1", "1709288130000002;n=1", "t=1;n=-1"] {
+            let query = JournalQuery { after_cursor: Some(bad.to_string()), ..JournalQuery::default() };
+            assert!(UnifiedLogFilter::new(&query).is_err(), "{:?} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn only_the_newest_entries_are_kept() {
+        let mut entries = VecDeque::new();
+        for message in ["one", "two", "three", "four", "five"] {
+            keep_newest(&mut entries, entry("c", message), 3);
+        }
+        assert_eq!(messages(&Vec::from(entries)), vec!["three", "four", "five"]);
+    }
+
+    #[test]
+    fn the_unified_log_predicate_names_the_process() {
+        assert_eq!(unified_log_predicate("nginx.service"), "process == \"nginx\"");
+        assert_eq!(unified_log_predicate("php-fpm.socket"), "process == \"php-fpm\"");
+    }
+}
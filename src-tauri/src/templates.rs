@@ -0,0 +1,514 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::config_watcher;
+use crate::instances::{self, NginxInstance};
+use crate::nginx_config;
+
+// Default for RUSTINX_TEMPLATE_DIR
+const DEFAULT_TEMPLATE_DIR: &str = "/var/lib/rustinx/templates";
+
+// Host name used for the throwaway server that wraps server-context snippets during preview
+const PREVIEW_SERVER_NAME: &str = "rustinx-preview.invalid";
+
+// Numbers the preview files, so concurrent previews in one process don't share them
+static PREVIEW_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static::lazy_static! {
+    static ref TEMPLATE_NAME: Regex = Regex::new(r"^[A-Za-z0-9][A-Za-z0-9_-]*$").unwrap();
+    static ref VARIABLE_NAME: Regex = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+    static ref PLACEHOLDER: Regex = Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap();
+    static ref UPSTREAM: Regex = Regex::new(r"^(https?://)?(unix:/[^\s;{}'\x22#]+|[A-Za-z0-9.\-\[\]:]+)(/[^\s;{}'\x22#]*)?$").unwrap();
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    /// Bad template definition or variable values
+    InvalidRequest(String),
+    NotFound(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::InvalidRequest(message) | TemplateError::NotFound(message) | TemplateError::Failed(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableType {
+    /// A host name, optionally a leading wildcard
+    Domain,
+    /// Space-separated `server_name` values: host names, IP addresses or `_`
+    Domains,
+    /// `host:port`, a URL or `unix:/path`, for `proxy_pass` and `server`
+    Upstream,
+    Port,
+    /// An absolute path, e.g. a root or certificate file
+    Path,
+    /// Free text that can't end the directive it's in
+    Text,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: VariableType,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// Where the rendered snippet goes, which decides how preview wraps it for `nginx -t`
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateContext {
+    /// Whole `server` or `upstream` blocks
+    #[default]
+    Http,
+    /// Directives and locations that go inside a `server`
+    Server,
+}
+
+/// A stored template; `{{name}}` in the body is replaced by the variable's value
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Template {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub context: TemplateContext,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+    pub body: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PreviewRequest {
+    #[serde(default)]
+    pub values: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PreviewCheck {
+    pub valid: bool,
+    pub message: String,
+    /// Line in the rendered snippet nginx complained about, when the error is in the snippet
+    pub error_line: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TemplatePreview {
+    pub content: String,
+    pub check: PreviewCheck,
+}
+
+pub fn template_dir() -> PathBuf {
+    PathBuf::from(
+        std::env::var("RUSTINX_TEMPLATE_DIR")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .unwrap_or_else(|| DEFAULT_TEMPLATE_DIR.to_string()),
+    )
+}
+
+fn template_path(name: &str) -> Result<PathBuf, TemplateError> {
+    if !TEMPLATE_NAME.is_match(name) {
+        return Err(TemplateError::InvalidRequest(format!("{:?} is not a valid template name", name)));
+    }
+    Ok(template_dir().join(format!("{}.json", name)))
+}
+
+fn validate_value(kind: VariableType, value: &str) -> bool {
+    match kind {
+        VariableType::Domain => nginx_config::is_host_name(value),
+        VariableType::Domains => !value.trim().is_empty() && value.split_whitespace().all(nginx_config::is_server_name),
+        VariableType::Upstream => UPSTREAM.is_match(value),
+        VariableType::Port => value.parse::<u16>().is_ok_and(|port| port > 0),
+        VariableType::Path => nginx_config::is_safe_path(value),
+        VariableType::Text => !value.contains([';', '{', '}', '\n', '\r', '"', '\'', '#']),
+    }
+}
+
+/// Checks that variable names are unique, defaults are valid and every placeholder is declared
+pub fn validate_template(template: &Template) -> Result<(), TemplateError> {
+    let invalid = |message: String| Err(TemplateError::InvalidRequest(message));
+    if !TEMPLATE_NAME.is_match(&template.name) {
+        return invalid(format!("{:?} is not a valid template name", template.name));
+    }
+
+    for (index, variable) in template.variables.iter().enumerate() {
+        if !VARIABLE_NAME.is_match(&variable.name) {
+            return invalid(format!("{:?} is not a valid variable name", variable.name));
+        }
+        if template.variables[..index].iter().any(|other| other.name == variable.name) {
+            return invalid(format!("Variable {} is declared twice", variable.name));
+        }
+        if let Some(default) = &variable.default {
+            if !validate_value(variable.kind, default) {
+                return invalid(format!("Default {:?} is not a valid {:?} for {}", default, variable.kind, variable.name));
+            }
+        }
+    }
+
+    for captures in PLACEHOLDER.captures_iter(&template.body) {
+        if !template.variables.iter().any(|variable| variable.name == captures[1]) {
+            return invalid(format!("{{{{{}}}}} is used but not declared", &captures[1]));
+        }
+    }
+    Ok(())
+}
+
+/// Fills in the placeholders, checking each value against its variable's type
+pub fn render_template(template: &Template, values: &HashMap<String, String>) -> Result<String, TemplateError> {
+    if let Some(unknown) = values.keys().find(|name| !template.variables.iter().any(|variable| &variable.name == *name)) {
+        return Err(TemplateError::InvalidRequest(format!("{} is not a variable of {}", unknown, template.name)));
+    }
+
+    let mut resolved: HashMap<&str, &str> = HashMap::new();
+    for variable in &template.variables {
+        let value = values
+            .get(&variable.name)
+            .or(variable.default.as_ref())
+            .ok_or_else(|| TemplateError::InvalidRequest(format!("{} is required", variable.name)))?;
+        if !validate_value(variable.kind, value) {
+            return Err(TemplateError::InvalidRequest(format!(
+                "{:?} is not a valid {:?} for {}",
+                value, variable.kind, variable.name
+            )));
+        }
+        resolved.insert(&variable.name, value);
+    }
+
+    Ok(PLACEHOLDER
+        .replace_all(&template.body, |captures: &regex::Captures| resolved.get(&captures[1]).copied().unwrap_or_default().to_string())
+        .to_string())
+}
+
+/// The templates Rustinx ships with, written to the template directory the first time it's used
+fn builtin_templates() -> Vec<Template> {
+    let variable = |name: &str, kind: VariableType, default: Option<&str>| TemplateVariable {
+        name: name.to_string(),
+        kind,
+        description: None,
+        default: default.map(|value| value.to_string()),
+    };
+    vec![
+        Template {
+            name: "reverse-proxy-tls".to_string(),
+            description: Some("HTTPS reverse proxy with an HTTP redirect".to_string()),
+            context: TemplateContext::Http,
+            variables: vec![
+                variable("domain", VariableType::Domains, None),
+                variable("upstream", VariableType::Upstream, Some("http://127.0.0.1:3000")),
+                variable("port", VariableType::Port, Some("443")),
+                variable("certificate", VariableType::Path, None),
+                variable("key", VariableType::Path, None),
+            ],
+            body: "server {\n    listen 80;\n    server_name {{domain}};\n    return 301 https://$host$request_uri;\n}\n\n\
+                   server {\n    listen {{port}} ssl;\n    server_name {{domain}};\n\n    \
+                   ssl_certificate {{certificate}};\n    ssl_certificate_key {{key}};\n\n    \
+                   location / {\n        proxy_pass {{upstream}};\n        proxy_set_header Host $host;\n        \
+                   proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;\n        \
+                   proxy_set_header X-Forwarded-Proto $scheme;\n    }\n}\n"
+                .to_string(),
+        },
+        Template {
+            name: "static-cache".to_string(),
+            description: Some("Long-lived caching for static assets inside a server".to_string()),
+            context: TemplateContext::Server,
+            variables: vec![variable("root", VariableType::Path, None)],
+            body: "location ~* \\.(css|js|png|jpg|jpeg|gif|svg|ico|woff2?)$ {\n    root {{root}};\n    \
+                   expires 30d;\n    add_header Cache-Control \"public, immutable\";\n}\n"
+                .to_string(),
+        },
+    ]
+}
+
+fn ensure_template_dir() -> Result<PathBuf, TemplateError> {
+    let dir = template_dir();
+    if !dir.exists() {
+        fs::create_dir_all(&dir).map_err(|e| TemplateError::Failed(format!("Failed to create {}: {}", dir.display(), e)))?;
+        for template in builtin_templates() {
+            write_template(&dir, &template)?;
+        }
+    }
+    Ok(dir)
+}
+
+fn write_template(dir: &Path, template: &Template) -> Result<(), TemplateError> {
+    let path = dir.join(format!("{}.json", template.name));
+    let json = serde_json::to_string_pretty(template).map_err(|e| TemplateError::Failed(e.to_string()))?;
+    fs::write(&path, json).map_err(|e| TemplateError::Failed(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+pub fn list_templates() -> Result<Vec<Template>, TemplateError> {
+    let dir = ensure_template_dir()?;
+    let entries = fs::read_dir(&dir).map_err(|e| TemplateError::Failed(format!("Failed to read {}: {}", dir.display(), e)))?;
+    let mut templates: Vec<Template> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let content = fs::read_to_string(entry.path()).ok()?;
+            serde_json::from_str(&content).ok()
+        })
+        .collect();
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+pub fn load_template(name: &str) -> Result<Template, TemplateError> {
+    ensure_template_dir()?;
+    let path = template_path(name)?;
+    let content = fs::read_to_string(&path).map_err(|_| TemplateError::NotFound(format!("Template {} does not exist", name)))?;
+    serde_json::from_str(&content).map_err(|e| TemplateError::Failed(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+pub fn save_template(template: &Template) -> Result<(), TemplateError> {
+    validate_template(template)?;
+    let dir = ensure_template_dir()?;
+    write_template(&dir, template)
+}
+
+pub fn delete_template(name: &str) -> Result<(), TemplateError> {
+    let path = template_path(name)?;
+    fs::remove_file(&path).map_err(|_| TemplateError::NotFound(format!("Template {} does not exist", name)))
+}
+
+/// Removes the preview files however the check ends
+struct PreviewFiles(Vec<PathBuf>);
+
+impl Drop for PreviewFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Runs `nginx -t` on a copy of the live config with the snippet included in `http`. Nothing in the
+/// live config is touched; the copy sits next to nginx.conf so relative includes still resolve.
+pub(crate) fn check_snippet(
+    instance: &NginxInstance,
+    context: TemplateContext,
+    content: &str,
+) -> Result<PreviewCheck, TemplateError> {
+    let root = nginx_config::find_main_config(instance).ok_or_else(|| TemplateError::Failed("Could not find nginx.conf".to_string()))?;
+    let conf_dir = Path::new(&root).parent().unwrap_or(Path::new("/etc/nginx")).to_path_buf();
+
+    let parsed = nginx_config::parse_config(&root);
+    let root_directives = parsed.files.first().map(|file| file.directives.as_slice()).unwrap_or_default();
+    let http = root_directives
+        .iter()
+        .find(|directive| directive.name == "http")
+        .ok_or_else(|| TemplateError::Failed(format!("{} has no http block", root)))?;
+    let first = http
+        .children()
+        .first()
+        .filter(|child| child.line > http.line)
+        .ok_or_else(|| TemplateError::Failed("Can't find where the http block starts".to_string()))?;
+
+    let id = format!("{}-{}", std::process::id(), PREVIEW_COUNTER.fetch_add(1, Ordering::Relaxed));
+    let snippet_path = conf_dir.join(format!(".rustinx-preview-{}.snippet", id));
+    let main_path = conf_dir.join(format!(".rustinx-preview-{}.conf", id));
+    let _files = PreviewFiles(vec![snippet_path.clone(), main_path.clone()]);
+
+    // Server snippets get a throwaway server around them; its two lines shift the snippet's line numbers
+    let (snippet, line_offset) = match context {
+        TemplateContext::Http => (content.to_string(), 0),
+        TemplateContext::Server => (
+            format!("server {{\nserver_name {};\n{}\n}}\n", PREVIEW_SERVER_NAME, content),
+            2,
+        ),
+    };
+    fs::write(&snippet_path, snippet).map_err(|e| TemplateError::Failed(format!("Failed to write {}: {}", snippet_path.display(), e)))?;
+
+    let main = fs::read_to_string(&root).map_err(|e| TemplateError::Failed(format!("Failed to read {}: {}", root, e)))?;
+    let mut lines: Vec<&str> = main.split('\n').collect();
+    let include = format!("include {};", snippet_path.display());
+    lines.insert(first.line - 1, &include);
+    fs::write(&main_path, lines.join("\n")).map_err(|e| TemplateError::Failed(format!("Failed to write {}: {}", main_path.display(), e)))?;
+
+    let output = instance
+        .command_without_config()
+        .arg("-t")
+        .arg("-c")
+        .arg(&main_path)
+        .output()
+        .map_err(|e| TemplateError::Failed(format!("Failed to run nginx -t: {}", e)))?;
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+
+    let snippet_name = snippet_path.to_string_lossy().to_string();
+    let error_line = config_watcher::parse_error_location(&stderr)
+        .filter(|(file, _)| *file == snippet_name)
+        .and_then(|(_, line)| line.checked_sub(line_offset));
+    // Point at the rendered snippet rather than our temporary file names
+    let message = stderr
+        .replace(&snippet_name, "<preview>")
+        .replace(&main_path.to_string_lossy().to_string(), &root);
+
+    Ok(PreviewCheck {
+        valid: output.status.success(),
+        message: message.trim().to_string(),
+        error_line,
+    })
+}
+
+/// Renders a stored template and runs it through `nginx -t` without writing to the live config
+pub fn preview_template(
+    instance: &NginxInstance,
+    name: &str,
+    values: &HashMap<String, String>,
+) -> Result<TemplatePreview, TemplateError> {
+    let template = load_template(name)?;
+    let content = render_template(&template, values)?;
+    let check = check_snippet(instance, template.context, &content).unwrap_or_else(|e| PreviewCheck {
+        valid: false,
+        message: e.to_string(),
+        error_line: None,
+    });
+    Ok(TemplatePreview { content, check })
+}
+
+#[tauri::command]
+pub(crate) fn get_templates() -> Result<Vec<Template>, String> {
+    list_templates().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn save_nginx_template(template: Template) -> Result<(), String> {
+    save_template(&template).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn delete_nginx_template(name: String) -> Result<(), String> {
+    delete_template(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn preview_nginx_template(
+    instance: Option<String>,
+    name: String,
+    values: HashMap<String, String>,
+) -> Result<TemplatePreview, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+    preview_template(&instance, &name, &values).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(body: &str, variables: &[(&str, VariableType, Option<&str>)]) -> Template {
+        Template {
+            name: "test".to_string(),
+            description: None,
+            context: TemplateContext::Http,
+            variables: variables
+                .iter()
+                .map(|(name, kind, default)| TemplateVariable {
+                    name: name.to_string(),
+                    kind: *kind,
+                    description: None,
+                    default: default.map(|value| value.to_string()),
+                })
+                .collect(),
+            body: body.to_string(),
+        }
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    fn rejected(result: Result<impl std::fmt::Debug, TemplateError>) -> bool {
+        matches!(result, Err(TemplateError::InvalidRequest(_)))
+    }
+
+    #[test]
+    fn builtin_templates_are_valid() {
+        for template in builtin_templates() {
+            assert!(validate_template(&template).is_ok(), "{} should be valid", template.name);
+        }
+    }
+
+    #[test]
+    fn templates_declare_every_placeholder_once() {
+        let root = ("root", VariableType::Path, None);
+        assert!(validate_template(&template("root {{ root }};", &[root])).is_ok());
+        assert!(rejected(validate_template(&template("root {{root}};\nalias {{alias}};", &[root]))));
+        assert!(rejected(validate_template(&template("root {{root}};", &[root, root]))));
+        assert!(rejected(validate_template(&template("", &[("1st", VariableType::Text, None)]))));
+        assert!(rejected(validate_template(&template("", &[("root", VariableType::Path, Some("relative"))]))));
+
+        let mut bad_name = template("", &[]);
+        bad_name.name = "../escape".to_string();
+        assert!(rejected(validate_template(&bad_name)));
+    }
+
+    #[test]
+    fn renders_values_and_defaults() {
+        let proxy = template(
+            "server_name {{domain}};\nlisten {{ port }};\nproxy_pass {{upstream}};",
+            &[
+                ("domain", VariableType::Domains, None),
+                ("port", VariableType::Port, Some("8080")),
+                ("upstream", VariableType::Upstream, Some("http://127.0.0.1:3000")),
+            ],
+        );
+        let rendered = render_template(&proxy, &values(&[("domain", "example.com *.example.com _")])).unwrap();
+        assert_eq!(rendered, "server_name example.com *.example.com _;\nlisten 8080;\nproxy_pass http://127.0.0.1:3000;");
+
+        assert!(rejected(render_template(&proxy, &values(&[]))));
+        assert!(rejected(render_template(&proxy, &values(&[("domain", "example.com"), ("extra", "x")]))));
+    }
+
+    #[test]
+    fn values_are_checked_against_their_type() {
+        let valid = [
+            (VariableType::Domain, "*.example.com"),
+            (VariableType::Domains, "example.com 192.0.2.1 [2001:db8::1]"),
+            (VariableType::Upstream, "backend:8080"),
+            (VariableType::Upstream, "unix:/run/app.sock"),
+            (VariableType::Upstream, "https://api.internal/v1"),
+            (VariableType::Port, "443"),
+            (VariableType::Path, "/etc/ssl/example.pem"),
+            (VariableType::Text, "max-age=31536000"),
+        ];
+        for (kind, value) in valid {
+            assert!(validate_value(kind, value), "{:?} should be a valid {:?}", value, kind);
+        }
+
+        let invalid = [
+            (VariableType::Domain, "_"),
+            (VariableType::Domain, "example.com;"),
+            (VariableType::Domains, " "),
+            (VariableType::Domains, "example.com evil.org;include"),
+            (VariableType::Upstream, "backend; include /etc/shadow"),
+            (VariableType::Upstream, "http://backend/#x"),
+            (VariableType::Port, "0"),
+            (VariableType::Port, "65536"),
+            (VariableType::Path, "relative/path"),
+            (VariableType::Path, "/var/www #"),
+            (VariableType::Path, "/var/www/#x"),
+            (VariableType::Text, "a; b"),
+            (VariableType::Text, "a } server {"),
+            (VariableType::Text, "value # comment"),
+            (VariableType::Text, "line\nbreak"),
+        ];
+        for (kind, value) in invalid {
+            assert!(!validate_value(kind, value), "{:?} should not be a valid {:?}", value, kind);
+        }
+    }
+}
@@ -0,0 +1,631 @@
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Private};
+use openssl::x509::{X509NameRef, X509};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::health::{self, AlertSeverity};
+use crate::instances::{self, NginxInstance};
+use crate::lint;
+use crate::nginx_config::{self, Directive, ParsedConfig};
+
+// Defaults for RUSTINX_CERT_WARN_DAYS and RUSTINX_CERT_CRITICAL_DAYS
+const DEFAULT_WARN_DAYS: i64 = 30;
+const DEFAULT_CRITICAL_DAYS: i64 = 7;
+
+// Certificates rarely change, so the files are only re-read this often
+const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+const SECS_PER_DAY: i64 = 86_400;
+
+lazy_static::lazy_static! {
+    // Alert keys raised by each instance's last check, so alerts for certificates removed from the
+    // config get cleared
+    static ref RAISED_ALERTS: Mutex<HashMap<String, HashSet<String>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ExpiryThresholds {
+    pub warn_days: i64,
+    pub critical_days: i64,
+}
+
+impl ExpiryThresholds {
+    pub fn from_env() -> Self {
+        let days = |name: &str, default: i64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(default)
+        };
+        let critical_days = days("RUSTINX_CERT_CRITICAL_DAYS", DEFAULT_CRITICAL_DAYS);
+        ExpiryThresholds {
+            warn_days: days("RUSTINX_CERT_WARN_DAYS", DEFAULT_WARN_DAYS).max(critical_days),
+            critical_days,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CertificateStatus {
+    Ok,
+    Expiring,
+    Critical,
+    Expired,
+    /// The certificate couldn't be read or parsed
+    Error,
+    /// Chosen per handshake from a variable, or inlined as `data:`, so there's no file to check
+    Dynamic,
+}
+
+/// One `ssl_certificate` / `ssl_certificate_key` pair as a server block uses it
+#[derive(Clone, Debug, Serialize)]
+pub struct CertificateInfo {
+    pub certificate: String,
+    pub key: Option<String>,
+    /// The `ssl_certificate` directive, which may be inherited from `http`
+    pub source: String,
+    pub server_names: Vec<String>,
+    pub subject: Option<String>,
+    pub issuer: Option<String>,
+    pub sans: Vec<String>,
+    pub not_before: Option<i64>,
+    pub not_after: Option<i64>,
+    pub days_remaining: Option<i64>,
+    /// e.g. `RSA 2048` or `EC prime256v1`
+    pub key_type: Option<String>,
+    /// Certificates after the leaf in the file
+    pub chain_length: usize,
+    /// `None` when the key file couldn't be read, which is common when it's root-only
+    pub key_matches: Option<bool>,
+    pub key_error: Option<String>,
+    /// `server_name`s the certificate's SANs don't cover
+    pub uncovered_names: Vec<String>,
+    pub status: CertificateStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TlsInventory {
+    pub checked_at: u64,
+    pub thresholds: ExpiryThresholds,
+    pub certificates: Vec<CertificateInfo>,
+}
+
+struct CertificatePair<'a> {
+    certificate: &'a Directive,
+    key: Option<&'a Directive>,
+    server_names: Vec<String>,
+}
+
+/// Pairs `ssl_certificate` with `ssl_certificate_key` by position, as nginx does when a server
+/// has both an RSA and an ECDSA certificate
+fn pair_certificates<'a>(directives: &[&'a Directive]) -> Vec<(&'a Directive, Option<&'a Directive>)> {
+    let certificates = directives.iter().filter(|d| d.name == "ssl_certificate");
+    let keys: Vec<&Directive> = directives.iter().copied().filter(|d| d.name == "ssl_certificate_key").collect();
+    certificates
+        .enumerate()
+        .map(|(index, certificate)| (*certificate, keys.get(index).copied()))
+        .collect()
+}
+
+/// Every certificate/key pair a TLS `server` block uses, with certificates set at `http` level
+/// applying to servers that don't set their own. Plain HTTP servers inherit them too but never
+/// present them, so they are skipped.
+fn find_certificate_pairs(tree: &[Directive]) -> Vec<CertificatePair<'_>> {
+    let mut pairs = Vec::new();
+
+    nginx_config::walk(tree, &mut |directive, parents| {
+        if directive.name != "server" || parents.last().map(|parent| parent.name.as_str()) != Some("http") {
+            return;
+        }
+        if !lint::is_tls_server(directive) {
+            return;
+        }
+        let own: Vec<&Directive> = directive.children().iter().collect();
+        let inherited: Vec<&Directive> = parents.last().map(|http| http.children().iter().collect()).unwrap_or_default();
+
+        let mut server_pairs = pair_certificates(&own);
+        if server_pairs.is_empty() {
+            server_pairs = pair_certificates(&inherited);
+        }
+
+        let server_names: Vec<String> = own
+            .iter()
+            .filter(|d| d.name == "server_name")
+            .flat_map(|d| d.args.iter().cloned())
+            .collect();
+        for (certificate, key) in server_pairs {
+            pairs.push(CertificatePair { certificate, key, server_names: server_names.clone() });
+        }
+    });
+
+    pairs
+}
+
+fn name_to_string(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let field = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry.data().to_string().unwrap_or_default();
+            format!("{}={}", field, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn common_name(name: &X509NameRef) -> Option<String> {
+    name.entries_by_nid(Nid::COMMONNAME)
+        .next()
+        .and_then(|entry| entry.data().to_string().ok())
+}
+
+/// Seconds since the epoch for an ASN.1 time
+fn unix_time(time: &Asn1TimeRef) -> Option<i64> {
+    let epoch = Asn1Time::from_unix(0).ok()?;
+    let diff = epoch.diff(time).ok()?;
+    Some(diff.days as i64 * SECS_PER_DAY + diff.secs as i64)
+}
+
+fn key_type(key: &PKey<openssl::pkey::Public>) -> String {
+    match key.id() {
+        Id::RSA => format!("RSA {}", key.bits()),
+        Id::EC => {
+            let curve = key
+                .ec_key()
+                .ok()
+                .and_then(|ec| ec.group().curve_name())
+                .and_then(|nid| nid.short_name().ok())
+                .unwrap_or("unknown curve");
+            format!("EC {}", curve)
+        }
+        Id::ED25519 => "Ed25519".to_string(),
+        Id::ED448 => "Ed448".to_string(),
+        Id::DSA => format!("DSA {}", key.bits()),
+        other => format!("unknown ({})", other.as_raw()),
+    }
+}
+
+/// Whether a SAN (possibly `*.example.com`) covers `name`; a wildcard matches exactly one label
+fn san_covers(san: &str, name: &str) -> bool {
+    let san = san.to_ascii_lowercase();
+    let name = name.to_ascii_lowercase();
+    if san == name {
+        return true;
+    }
+    match san.strip_prefix("*.") {
+        Some(domain) => name
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && label != "*" && rest == domain),
+        None => false,
+    }
+}
+
+/// The `server_name`s no SAN covers. Regex names and catch-alls can't be checked and are skipped.
+fn uncovered_names(server_names: &[String], sans: &[String]) -> Vec<String> {
+    server_names
+        .iter()
+        .filter(|name| !name.is_empty() && *name != "_" && !name.starts_with('~') && !name.starts_with('$'))
+        .filter(|name| {
+            // `.example.com` is shorthand for `example.com` plus `*.example.com`
+            let candidates: Vec<String> = match name.strip_prefix('.') {
+                Some(domain) => vec![domain.to_string(), format!("*.{}", domain)],
+                None => vec![name.to_string()],
+            };
+            !candidates.iter().all(|candidate| sans.iter().any(|san| san_covers(san, candidate)))
+        })
+        .cloned()
+        .collect()
+}
+
+fn read_private_key(path: &str) -> Result<PKey<Private>, String> {
+    let pem = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    PKey::private_key_from_pem(&pem).map_err(|e| format!("Failed to parse {}: {}", path, e))
+}
+
+fn inspect_pair(pair: &CertificatePair, base_dir: &Path, thresholds: &ExpiryThresholds, now: i64) -> CertificateInfo {
+    let certificate_arg = pair.certificate.arg(0).unwrap_or_default();
+    let mut info = CertificateInfo {
+        certificate: nginx_config::resolve_path(base_dir, certificate_arg),
+        key: pair.key.and_then(|key| key.arg(0)).map(|path| nginx_config::resolve_path(base_dir, path)),
+        source: pair.certificate.location(),
+        server_names: pair.server_names.clone(),
+        subject: None,
+        issuer: None,
+        sans: Vec::new(),
+        not_before: None,
+        not_after: None,
+        days_remaining: None,
+        key_type: None,
+        chain_length: 0,
+        key_matches: None,
+        key_error: None,
+        uncovered_names: Vec::new(),
+        status: CertificateStatus::Error,
+        error: None,
+    };
+
+    if certificate_arg.contains('$') || certificate_arg.starts_with("data:") {
+        info.status = CertificateStatus::Dynamic;
+        return info;
+    }
+
+    let chain = match fs::read(&info.certificate)
+        .map_err(|e| format!("Failed to read {}: {}", info.certificate, e))
+        .and_then(|pem| X509::stack_from_pem(&pem).map_err(|e| format!("Failed to parse {}: {}", info.certificate, e)))
+    {
+        Ok(chain) if !chain.is_empty() => chain,
+        Ok(_) => {
+            info.error = Some(format!("No certificate found in {}", info.certificate));
+            return info;
+        }
+        Err(e) => {
+            info.error = Some(e);
+            return info;
+        }
+    };
+    let leaf = &chain[0];
+    info.chain_length = chain.len() - 1;
+
+    info.subject = Some(name_to_string(leaf.subject_name()));
+    info.issuer = Some(name_to_string(leaf.issuer_name()));
+    info.sans = leaf
+        .subject_alt_names()
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|name| {
+                    name.dnsname().map(|dns| dns.to_string()).or_else(|| {
+                        name.ipaddress().and_then(|ip| match ip.len() {
+                            4 => <[u8; 4]>::try_from(ip).ok().map(|octets| std::net::Ipv4Addr::from(octets).to_string()),
+                            16 => <[u8; 16]>::try_from(ip).ok().map(|octets| std::net::Ipv6Addr::from(octets).to_string()),
+                            _ => None,
+                        })
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    info.not_before = unix_time(leaf.not_before());
+    info.not_after = unix_time(leaf.not_after());
+
+    let public_key = leaf.public_key().ok();
+    info.key_type = public_key.as_ref().map(key_type);
+
+    if let Some(key_path) = &info.key {
+        match read_private_key(key_path) {
+            Ok(private_key) => info.key_matches = public_key.as_ref().map(|public| public.public_eq(&private_key)),
+            Err(e) => info.key_error = Some(e),
+        }
+    } else {
+        info.key_error = Some("No ssl_certificate_key for this certificate".to_string());
+    }
+
+    // Clients only fall back to the CN when there are no SANs at all
+    let names = if info.sans.is_empty() {
+        common_name(leaf.subject_name()).into_iter().collect()
+    } else {
+        info.sans.clone()
+    };
+    info.uncovered_names = uncovered_names(&info.server_names, &names);
+
+    info.status = match info.not_after {
+        Some(not_after) => {
+            let remaining = not_after - now;
+            let days = remaining.div_euclid(SECS_PER_DAY);
+            info.days_remaining = Some(days);
+            if remaining <= 0 {
+                CertificateStatus::Expired
+            } else if days < thresholds.critical_days {
+                CertificateStatus::Critical
+            } else if days < thresholds.warn_days {
+                CertificateStatus::Expiring
+            } else {
+                CertificateStatus::Ok
+            }
+        }
+        None => CertificateStatus::Error,
+    };
+
+    info
+}
+
+/// Reads every certificate the config references
+pub fn inspect_certificates(parsed: &ParsedConfig, thresholds: &ExpiryThresholds) -> Vec<CertificateInfo> {
+    let tree = parsed.expanded();
+    let base_dir = Path::new(&parsed.root).parent().unwrap_or(Path::new("/etc/nginx")).to_path_buf();
+    let now = health::now_secs() as i64;
+
+    let mut certificates: Vec<CertificateInfo> = Vec::new();
+    for pair in find_certificate_pairs(&tree) {
+        let info = inspect_pair(&pair, &base_dir, thresholds, now);
+        // Servers sharing a certificate are reported once, with all of their names
+        match certificates
+            .iter_mut()
+            .find(|existing| existing.certificate == info.certificate && existing.key == info.key)
+        {
+            Some(existing) => {
+                for name in info.server_names {
+                    if !existing.server_names.contains(&name) {
+                        existing.server_names.push(name);
+                    }
+                }
+                for name in info.uncovered_names {
+                    if !existing.uncovered_names.contains(&name) {
+                        existing.uncovered_names.push(name);
+                    }
+                }
+            }
+            None => certificates.push(info),
+        }
+    }
+
+    certificates.sort_by(|a, b| a.days_remaining.unwrap_or(i64::MAX).cmp(&b.days_remaining.unwrap_or(i64::MAX)));
+    certificates
+}
+
+pub fn tls_inventory(instance: &NginxInstance) -> Result<TlsInventory, String> {
+    let root = nginx_config::find_main_config(instance).ok_or("Could not find nginx.conf")?;
+    let thresholds = ExpiryThresholds::from_env();
+    let certificates = inspect_certificates(&nginx_config::parse_config(&root), &thresholds);
+    Ok(TlsInventory { checked_at: health::now_secs(), thresholds, certificates })
+}
+
+/// Raises expiry, mismatch and coverage alerts for the inventory and clears the ones that no longer apply
+fn update_alerts(instance: &str, inventory: &TlsInventory) {
+    let mut raised: HashSet<String> = HashSet::new();
+    let mut raise = |key: String, severity: AlertSeverity, message: String| {
+        health::raise_instance_alert(instance, &key, severity, &message);
+        raised.insert(key);
+    };
+
+    for cert in &inventory.certificates {
+        let days = cert.days_remaining.unwrap_or_default();
+        match cert.status {
+            CertificateStatus::Expired => raise(
+                format!("cert_expiry:{}", cert.certificate),
+                AlertSeverity::Critical,
+                format!("Certificate {} expired {} days ago", cert.certificate, -days),
+            ),
+            CertificateStatus::Critical | CertificateStatus::Expiring => raise(
+                format!("cert_expiry:{}", cert.certificate),
+                if cert.status == CertificateStatus::Critical { AlertSeverity::Critical } else { AlertSeverity::Warning },
+                format!("Certificate {} expires in {} days", cert.certificate, days),
+            ),
+            CertificateStatus::Error => raise(
+                format!("cert_error:{}", cert.certificate),
+                AlertSeverity::Warning,
+                cert.error.clone().unwrap_or_else(|| format!("Failed to read {}", cert.certificate)),
+            ),
+            CertificateStatus::Ok | CertificateStatus::Dynamic => {}
+        }
+        if cert.key_matches == Some(false) {
+            raise(
+                format!("cert_key_mismatch:{}", cert.certificate),
+                AlertSeverity::Critical,
+                format!(
+                    "Key {} does not match certificate {}",
+                    cert.key.as_deref().unwrap_or_default(),
+                    cert.certificate
+                ),
+            );
+        }
+        if !cert.uncovered_names.is_empty() {
+            raise(
+                format!("cert_names:{}", cert.certificate),
+                AlertSeverity::Warning,
+                format!("Certificate {} does not cover {}", cert.certificate, cert.uncovered_names.join(", ")),
+            );
+        }
+    }
+
+    if let Ok(mut previous) = RAISED_ALERTS.lock() {
+        let previous = previous.entry(instance.to_string()).or_default();
+        for key in previous.difference(&raised) {
+            health::clear_instance_alert(instance, key);
+        }
+        *previous = raised;
+    }
+}
+
+/// Re-reads every certificate of the instance periodically and keeps its TLS alerts up to date
+pub fn start_certificate_monitor(instance: NginxInstance) {
+    thread::spawn(move || loop {
+        // Without a config there's nothing to check; the config watcher already reports that
+        if let Ok(inventory) = tls_inventory(&instance) {
+            update_alerts(&instance.id, &inventory);
+        }
+        thread::sleep(CHECK_INTERVAL);
+    });
+}
+
+#[tauri::command]
+pub(crate) fn get_tls_certificates(instance: Option<String>) -> Result<TlsInventory, String> {
+    tls_inventory(&instances::get_instance(instance.as_deref())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::x509::extension::SubjectAlternativeName;
+    use openssl::x509::X509NameBuilder;
+    use std::path::PathBuf;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rustinx-tls-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn file(&self, name: &str) -> String {
+            self.0.join(name).to_string_lossy().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn new_key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    /// Writes a self-signed `<name>.pem` and `<name>.key` valid for `days`; SANs with a digit first are IPs
+    fn issue(dir: &TempDir, name: &str, sans: &[&str], days: u32) {
+        let key = new_key();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_nid(Nid::COMMONNAME, name).unwrap();
+        let subject = subject.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap()).unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_issuer_name(&subject).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(days).unwrap()).unwrap();
+        if !sans.is_empty() {
+            let mut san = SubjectAlternativeName::new();
+            for entry in sans {
+                if entry.starts_with(|c: char| c.is_ascii_digit()) {
+                    san.ip(entry);
+                } else {
+                    san.dns(entry);
+                }
+            }
+            let san = san.build(&builder.x509v3_context(None, None)).unwrap();
+            builder.append_extension(san).unwrap();
+        }
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+        fs::write(dir.0.join(format!("{}.pem", name)), builder.build().to_pem().unwrap()).unwrap();
+        fs::write(dir.0.join(format!("{}.key", name)), key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+    }
+
+    fn thresholds() -> ExpiryThresholds {
+        ExpiryThresholds { warn_days: 30, critical_days: 7 }
+    }
+
+    /// Inspects the only certificate `config` uses, as of `now`
+    fn inspect(dir: &TempDir, config: &str, now: i64) -> CertificateInfo {
+        let tree = nginx_config::parse_config_str(config, "nginx.conf").unwrap();
+        let pairs = find_certificate_pairs(&tree);
+        assert_eq!(pairs.len(), 1);
+        inspect_pair(&pairs[0], &dir.0, &thresholds(), now)
+    }
+
+    fn server(names: &str, certificate: &str, key: &str) -> String {
+        format!(
+            "http {{\n    server {{\n        listen 443 ssl;\n        server_name {};\n        ssl_certificate {};\n        ssl_certificate_key {};\n    }}\n}}\n",
+            names, certificate, key
+        )
+    }
+
+    #[test]
+    fn wildcard_sans_cover_one_label() {
+        assert!(san_covers("example.com", "EXAMPLE.com"));
+        assert!(san_covers("*.example.com", "www.example.com"));
+        assert!(!san_covers("*.example.com", "example.com"));
+        assert!(!san_covers("*.example.com", "a.b.example.com"));
+        assert!(!san_covers("*.example.com", "*.example.com.evil.org"));
+
+        let sans = vec!["example.com".to_string(), "*.example.com".to_string()];
+        let names: Vec<String> = ["example.com", "www.example.com", ".example.com", "other.org", "_", "~^api\\d+", ""]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        assert_eq!(uncovered_names(&names, &sans), vec!["other.org"]);
+        assert_eq!(uncovered_names(&[".example.com".to_string()], &["example.com".to_string()]), vec![".example.com"]);
+    }
+
+    #[test]
+    fn reports_sans_key_and_uncovered_names() {
+        let dir = TempDir::new("inspect");
+        issue(&dir, "site", &["example.com", "*.example.com", "127.0.0.1"], 90);
+        let config = server("example.com www.example.com other.org", &dir.file("site.pem"), &dir.file("site.key"));
+        let info = inspect(&dir, &config, health::now_secs() as i64);
+
+        assert_eq!(info.sans, vec!["example.com", "*.example.com", "127.0.0.1"]);
+        assert_eq!(info.subject.as_deref(), Some("CN=site"));
+        assert_eq!(info.key_type.as_deref(), Some("EC prime256v1"));
+        assert_eq!(info.chain_length, 0);
+        assert_eq!(info.key_matches, Some(true));
+        assert_eq!(info.uncovered_names, vec!["other.org"]);
+        assert_eq!(info.status, CertificateStatus::Ok);
+        assert_eq!(info.source, "nginx.conf:5");
+    }
+
+    #[test]
+    fn flags_a_key_that_belongs_to_another_certificate() {
+        let dir = TempDir::new("mismatch");
+        issue(&dir, "site", &["example.com"], 90);
+        issue(&dir, "other", &["example.com"], 90);
+        let config = server("example.com", &dir.file("site.pem"), &dir.file("other.key"));
+        assert_eq!(inspect(&dir, &config, health::now_secs() as i64).key_matches, Some(false));
+
+        let config = server("example.com", &dir.file("site.pem"), &dir.file("missing.key"));
+        let info = inspect(&dir, &config, health::now_secs() as i64);
+        assert_eq!(info.key_matches, None);
+        assert!(info.key_error.unwrap().contains("missing.key"));
+    }
+
+    #[test]
+    fn falls_back_to_the_common_name_without_sans() {
+        let dir = TempDir::new("cn");
+        issue(&dir, "example.com", &[], 90);
+        let config = server("example.com www.example.com", &dir.file("example.com.pem"), &dir.file("example.com.key"));
+        let info = inspect(&dir, &config, health::now_secs() as i64);
+        assert!(info.sans.is_empty());
+        assert_eq!(info.uncovered_names, vec!["www.example.com"]);
+    }
+
+    #[test]
+    fn status_follows_the_expiry_thresholds() {
+        let dir = TempDir::new("expiry");
+        issue(&dir, "site", &["example.com"], 40);
+        let config = server("example.com", &dir.file("site.pem"), &dir.file("site.key"));
+        let now = health::now_secs() as i64;
+
+        let at = |days_later: i64| inspect(&dir, &config, now + days_later * SECS_PER_DAY);
+        let info = at(0);
+        assert_eq!(info.status, CertificateStatus::Ok);
+        assert!(matches!(info.days_remaining, Some(39..=40)));
+
+        // Shortly before the thresholds, with a minute of slack for the time the test takes
+        let before = |days: i64| now + 40 * SECS_PER_DAY - days * SECS_PER_DAY - 60;
+        assert_eq!(inspect(&dir, &config, before(29)).status, CertificateStatus::Expiring);
+        assert_eq!(inspect(&dir, &config, before(6)).status, CertificateStatus::Critical);
+        assert_eq!(at(41).status, CertificateStatus::Expired);
+        assert!(at(41).days_remaining.unwrap() < 0);
+    }
+
+    #[test]
+    fn certificates_from_variables_are_not_read() {
+        let dir = TempDir::new("dynamic");
+        let info = inspect(&dir, &server("example.com", "$ssl_server_name.pem", "$ssl_server_name.key"), 0);
+        assert_eq!(info.status, CertificateStatus::Dynamic);
+
+        let info = inspect(&dir, &server("example.com", &dir.file("absent.pem"), &dir.file("absent.key")), 0);
+        assert_eq!(info.status, CertificateStatus::Error);
+        assert!(info.error.is_some());
+    }
+}
@@ -0,0 +1,696 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::actix_routes::find_nginx_log_path;
+use crate::event_bus::{self, RustinxEvent};
+use crate::health::{self, AlertSeverity};
+use crate::instances::{self, NginxInstance};
+use crate::log_search::{self, Direction, LogFilter};
+use crate::nginx_config::{self, Directive, ParsedConfig};
+
+// Defaults for RUSTINX_UPSTREAM_PROBE_INTERVAL (seconds), RUSTINX_UPSTREAM_PROBE ("tcp" or "http")
+// and RUSTINX_UPSTREAM_PROBE_PATH
+const DEFAULT_PROBE_INTERVAL_SECS: u64 = 10;
+const DEFAULT_PROBE_PATH: &str = "/";
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Probe results kept per server
+const HISTORY_LENGTH: usize = 60;
+
+// Consecutive failed probes before a server is reported down, so one dropped SYN doesn't alert
+const FAILURES_BEFORE_DOWN: u32 = 2;
+
+// How far back the access log is read for 502/504 rates
+const GATEWAY_ERROR_WINDOW_SECS: i64 = 300;
+
+lazy_static::lazy_static! {
+    // Per instance, then per server
+    static ref SERVERS: Mutex<HashMap<String, HashMap<String, ServerHealth>>> = Mutex::new(HashMap::new());
+    static ref LAST_REPORT: Mutex<HashMap<String, UpstreamReport>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeMode {
+    Tcp,
+    Http,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProbeSettings {
+    pub mode: ProbeMode,
+    pub path: String,
+    pub interval_secs: u64,
+}
+
+impl ProbeSettings {
+    pub fn from_env() -> Self {
+        let mode = match std::env::var("RUSTINX_UPSTREAM_PROBE").as_deref() {
+            Ok("http") => ProbeMode::Http,
+            _ => ProbeMode::Tcp,
+        };
+        let path = std::env::var("RUSTINX_UPSTREAM_PROBE_PATH")
+            .ok()
+            .filter(|path| path.starts_with('/'))
+            .unwrap_or_else(|| DEFAULT_PROBE_PATH.to_string());
+        let interval_secs = std::env::var("RUSTINX_UPSTREAM_PROBE_INTERVAL")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_PROBE_INTERVAL_SECS);
+        ProbeSettings { mode, path, interval_secs }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Endpoint {
+    Tcp { host: String, port: u16 },
+    Unix(String),
+}
+
+/// A backend nginx passes requests to, from an `upstream` block or a direct `*_pass`
+#[derive(Clone, Debug, Serialize)]
+pub struct UpstreamTarget {
+    /// The `upstream` group, `None` when a `*_pass` directive points straight at the server
+    pub upstream: Option<String>,
+    /// As written in the config, e.g. `10.0.0.5:8080` or `unix:/run/app.sock`
+    pub address: String,
+    /// `http`/`https` for `proxy_pass`, the protocol of the other `*_pass` directives, `tcp` in `stream`
+    pub scheme: String,
+    /// Where the server was declared
+    pub source: String,
+    pub backup: bool,
+    /// Marked `down` in the config, so nginx won't use it anyway
+    pub marked_down: bool,
+    /// `*_pass` directives that send traffic here
+    pub referenced_by: Vec<String>,
+    #[serde(skip)]
+    endpoint: Option<Endpoint>,
+}
+
+impl UpstreamTarget {
+    fn key(&self) -> String {
+        match &self.upstream {
+            Some(group) => format!("{}/{}", group, self.address),
+            None => self.address.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProbeResult {
+    pub at: u64,
+    pub up: bool,
+    pub latency_ms: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerState {
+    Unknown,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ServerHealth {
+    pub target: UpstreamTarget,
+    pub state: ServerState,
+    pub latency_ms: Option<f64>,
+    pub last_checked: Option<u64>,
+    pub last_change: Option<u64>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    /// 502/504 responses logged with this server's address in the recent window; needs
+    /// `$upstream_addr` in the access log format
+    pub gateway_errors: u64,
+    pub history: VecDeque<ProbeResult>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct GatewayErrorStats {
+    pub window_secs: i64,
+    pub requests: u64,
+    pub bad_gateway: u64,
+    pub gateway_timeout: u64,
+    /// (502 + 504) / requests over the window
+    pub rate: f64,
+}
+
+/// Published as `upstream_health` after every probe round
+#[derive(Clone, Debug, Serialize)]
+pub struct UpstreamReport {
+    pub instance: String,
+    pub checked_at: u64,
+    pub probe: ProbeSettings,
+    pub servers: Vec<ServerHealth>,
+    pub gateway_errors: GatewayErrorStats,
+}
+
+/// `host[:port]`, `[v6]:port` or `unix:/path` as used by `server` and `proxy_pass`
+fn parse_endpoint(address: &str, default_port: u16) -> Option<Endpoint> {
+    if let Some(path) = address.strip_prefix("unix:") {
+        return Some(Endpoint::Unix(path.trim_end_matches(':').to_string()));
+    }
+    if address.contains('$') {
+        // Resolved per request by nginx; nothing we can probe
+        return None;
+    }
+    if let Some(rest) = address.strip_prefix('[') {
+        let (host, after) = rest.split_once(']')?;
+        let port = match after.strip_prefix(':') {
+            Some(port) => port.parse().ok()?,
+            None => default_port,
+        };
+        return Some(Endpoint::Tcp { host: host.to_string(), port });
+    }
+    match address.rsplit_once(':') {
+        Some((host, port)) => Some(Endpoint::Tcp { host: host.to_string(), port: port.parse().ok()? }),
+        None => Some(Endpoint::Tcp { host: address.to_string(), port: default_port }),
+    }
+}
+
+/// Splits `http://backend:8080/api` into scheme and authority
+fn split_proxy_url(url: &str) -> Option<(String, String)> {
+    let (scheme, rest) = url.split_once("://")?;
+    // A unix socket authority contains the path and ends at the next `:`
+    if let Some(path) = rest.strip_prefix("unix:") {
+        let socket = path.split(':').next().unwrap_or(path);
+        return Some((scheme.to_ascii_lowercase(), format!("unix:{}", socket)));
+    }
+    let authority = rest.split('/').next().unwrap_or(rest);
+    Some((scheme.to_ascii_lowercase(), authority.to_string()))
+}
+
+/// Scheme and authority of a `*_pass` argument. Only `proxy_pass` requires a scheme; the others
+/// default to their protocol, and `stream` blocks pass plain TCP.
+fn split_pass(directive: &str, argument: &str, in_stream: bool) -> Option<(String, String)> {
+    if in_stream {
+        return Some(("tcp".to_string(), argument.to_string()));
+    }
+    if argument.contains("://") {
+        return split_proxy_url(argument);
+    }
+    let scheme = match directive {
+        "fastcgi_pass" => "fastcgi",
+        "grpc_pass" => "grpc",
+        "uwsgi_pass" => "uwsgi",
+        "scgi_pass" => "scgi",
+        _ => return None,
+    };
+    Some((scheme.to_string(), argument.to_string()))
+}
+
+fn in_stream_block(parents: &[&Directive]) -> bool {
+    parents.iter().any(|parent| parent.name == "stream")
+}
+
+/// Every server behind an `upstream` block or a literal `*_pass`
+pub fn discover_targets(parsed: &ParsedConfig) -> Vec<UpstreamTarget> {
+    let tree = parsed.expanded();
+    // `http` and `stream` upstreams live in separate namespaces, so groups are keyed by both
+    let mut groups: Vec<(bool, String, Vec<UpstreamTarget>)> = Vec::new();
+    let mut passes: Vec<(&Directive, bool)> = Vec::new();
+
+    nginx_config::walk(&tree, &mut |directive, parents| match directive.name.as_str() {
+        "upstream" => {
+            let name = match directive.arg(0) {
+                Some(name) => name.to_string(),
+                None => return,
+            };
+            let in_stream = in_stream_block(parents);
+            let servers = directive
+                .children()
+                .iter()
+                .filter(|child| child.name == "server")
+                .filter_map(|server| {
+                    let address = server.arg(0)?.to_string();
+                    let flags = &server.args[1..];
+                    Some(UpstreamTarget {
+                        upstream: Some(name.clone()),
+                        endpoint: parse_endpoint(&address, 80),
+                        address,
+                        scheme: if in_stream { "tcp" } else { "http" }.to_string(),
+                        source: server.location(),
+                        backup: flags.iter().any(|flag| flag == "backup"),
+                        marked_down: flags.iter().any(|flag| flag == "down"),
+                        referenced_by: Vec::new(),
+                    })
+                })
+                .collect();
+            groups.push((in_stream, name, servers));
+        }
+        "proxy_pass" | "fastcgi_pass" | "grpc_pass" | "uwsgi_pass" | "scgi_pass" => {
+            passes.push((directive, in_stream_block(parents)))
+        }
+        _ => {}
+    });
+
+    let mut direct: Vec<UpstreamTarget> = Vec::new();
+    for (directive, in_stream) in passes {
+        let parts = directive.arg(0).and_then(|argument| split_pass(&directive.name, argument, in_stream));
+        let (scheme, authority) = match parts {
+            Some(parts) => parts,
+            None => continue,
+        };
+
+        let group = groups.iter_mut().find(|(stream, name, _)| *stream == in_stream && *name == authority);
+        if let Some((_, _, servers)) = group {
+            for server in servers.iter_mut() {
+                server.scheme = scheme.clone();
+                server.referenced_by.push(directive.location());
+            }
+            continue;
+        }
+
+        let default_port = match scheme.as_str() {
+            "https" | "grpcs" => 443,
+            _ => 80,
+        };
+        let endpoint = match parse_endpoint(&authority, default_port) {
+            Some(endpoint) => endpoint,
+            None => continue,
+        };
+        match direct.iter_mut().find(|target| target.endpoint.as_ref() == Some(&endpoint)) {
+            Some(target) => target.referenced_by.push(directive.location()),
+            None => direct.push(UpstreamTarget {
+                upstream: None,
+                address: authority,
+                scheme,
+                source: directive.location(),
+                backup: false,
+                marked_down: false,
+                referenced_by: vec![directive.location()],
+                endpoint: Some(endpoint),
+            }),
+        }
+    }
+
+    groups.into_iter().flat_map(|(_, _, servers)| servers).chain(direct).collect()
+}
+
+fn connect_tcp(host: &str, port: u16) -> Result<TcpStream, String> {
+    let addresses = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?;
+    let mut last_error = format!("{} did not resolve to any address", host);
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, PROBE_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = format!("{}: {}", address, e),
+        }
+    }
+    Err(last_error)
+}
+
+/// Sends a bare `GET` and reads the status line; 5xx counts as down
+fn probe_http(stream: &mut dyn ReadWrite, host: &str, path: &str) -> Result<(), String> {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: rustinx-probe\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+
+    let mut buffer = [0u8; 64];
+    let read = stream.read(&mut buffer).map_err(|e| e.to_string())?;
+    let status_line = String::from_utf8_lossy(&buffer[..read]);
+    let status: u16 = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| "Not an HTTP response".to_string())?;
+    if status >= 500 {
+        return Err(format!("HTTP {}", status));
+    }
+    Ok(())
+}
+
+trait ReadWrite: Read + Write {}
+impl<T: Read + Write> ReadWrite for T {}
+
+/// Probes a target once, returning the latency on success
+fn probe(target: &UpstreamTarget, settings: &ProbeSettings) -> Result<f64, String> {
+    let endpoint = target.endpoint.as_ref().ok_or("Address can't be probed")?;
+    let started = Instant::now();
+    // TLS backends only get a connect check; an HTTP probe would need a handshake first
+    let use_http = settings.mode == ProbeMode::Http && target.scheme == "http";
+
+    match endpoint {
+        Endpoint::Tcp { host, port } => {
+            let mut stream = connect_tcp(host, *port)?;
+            if use_http {
+                stream.set_read_timeout(Some(PROBE_TIMEOUT)).map_err(|e| e.to_string())?;
+                stream.set_write_timeout(Some(PROBE_TIMEOUT)).map_err(|e| e.to_string())?;
+                probe_http(&mut stream, host, &settings.path)?;
+            }
+        }
+        #[cfg(unix)]
+        Endpoint::Unix(path) => {
+            let mut stream = std::os::unix::net::UnixStream::connect(path).map_err(|e| format!("{}: {}", path, e))?;
+            if use_http {
+                stream.set_read_timeout(Some(PROBE_TIMEOUT)).map_err(|e| e.to_string())?;
+                stream.set_write_timeout(Some(PROBE_TIMEOUT)).map_err(|e| e.to_string())?;
+                probe_http(&mut stream, "localhost", &settings.path)?;
+            }
+        }
+        #[cfg(not(unix))]
+        Endpoint::Unix(_) => return Err("Unix sockets are not supported on this platform".to_string()),
+    }
+
+    Ok(started.elapsed().as_secs_f64() * 1000.0)
+}
+
+/// The addresses whose endpoint appears as a field of the log line. `$upstream_addr` separates the
+/// servers nginx tried with `, ` and internal redirects with ` : `, so fields end at whitespace,
+/// commas and quotes; comparing parsed endpoints keeps `10.0.0.1` from matching `10.0.0.12`.
+fn servers_in_line<'a>(line: &str, addresses: &'a [(String, Endpoint)]) -> Vec<&'a str> {
+    let logged: Vec<Endpoint> = line
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '"')
+        .filter(|field| !field.is_empty())
+        .filter_map(|field| parse_endpoint(field, 80))
+        .collect();
+    addresses
+        .iter()
+        .filter(|(_, endpoint)| logged.contains(endpoint))
+        .map(|(address, _)| address.as_str())
+        .collect()
+}
+
+/// Counts 502 and 504 responses in the recent access log, overall and per server address
+fn gateway_errors(
+    instance: &NginxInstance,
+    addresses: &[(String, Endpoint)],
+) -> (GatewayErrorStats, HashMap<String, u64>) {
+    let mut stats = GatewayErrorStats { window_secs: GATEWAY_ERROR_WINDOW_SECS, ..Default::default() };
+    let mut per_server: HashMap<String, u64> = HashMap::new();
+
+    let path = match find_nginx_log_path(instance, "access") {
+        Ok(path) => path,
+        Err(_) => return (stats, per_server),
+    };
+    let filter = LogFilter {
+        since: Some(format!("-{}s", GATEWAY_ERROR_WINDOW_SECS)),
+        ..Default::default()
+    };
+    let filter = match filter.compile() {
+        Ok(filter) => filter,
+        Err(_) => return (stats, per_server),
+    };
+
+    let mut cursor: Option<String> = None;
+    let log = Path::new(&path);
+    while let Ok(page) = log_search::search_log_file(log, &filter, cursor.as_deref(), Direction::Backward, 5_000) {
+        for entry in &page.entries {
+            stats.requests += 1;
+            match entry.status {
+                Some(502) => stats.bad_gateway += 1,
+                Some(504) => stats.gateway_timeout += 1,
+                _ => continue,
+            }
+            for address in servers_in_line(&entry.line, addresses) {
+                *per_server.entry(address.to_string()).or_insert(0) += 1;
+            }
+        }
+        match page.before {
+            Some(before) => cursor = Some(before),
+            None => break,
+        }
+    }
+
+    if stats.requests > 0 {
+        stats.rate = (stats.bad_gateway + stats.gateway_timeout) as f64 / stats.requests as f64;
+    }
+    (stats, per_server)
+}
+
+fn alert_key(target: &UpstreamTarget) -> String {
+    format!("upstream_down:{}", target.key())
+}
+
+/// Probes every target once and folds the results into the per-server state
+fn run_probe_round(instance: &NginxInstance, targets: Vec<UpstreamTarget>, settings: &ProbeSettings) -> UpstreamReport {
+    let now = health::now_secs();
+
+    // Probe in parallel so one unresponsive backend doesn't delay the others by the full timeout
+    let results: Vec<(UpstreamTarget, Result<f64, String>)> = thread::scope(|scope| {
+        let handles: Vec<_> = targets
+            .into_iter()
+            .map(|target| scope.spawn(move || {
+                let result = probe(&target, settings);
+                (target, result)
+            }))
+            .collect();
+        handles.into_iter().filter_map(|handle| handle.join().ok()).collect()
+    });
+
+    let mut addresses: Vec<(String, Endpoint)> = Vec::new();
+    for (target, _) in &results {
+        if let Some(endpoint) = &target.endpoint {
+            if !addresses.iter().any(|(address, _)| *address == target.address) {
+                addresses.push((target.address.clone(), endpoint.clone()));
+            }
+        }
+    }
+    let (gateway_stats, per_server_errors) = gateway_errors(instance, &addresses);
+
+    let mut all_servers = SERVERS.lock().unwrap_or_else(|e| e.into_inner());
+    let servers = all_servers.entry(instance.id.clone()).or_default();
+    let current_keys: Vec<String> = results.iter().map(|(target, _)| target.key()).collect();
+    // Forget servers that were removed from the config, along with their alerts
+    servers.retain(|key, server| {
+        let keep = current_keys.contains(key);
+        if !keep {
+            health::clear_instance_alert(&instance.id, &alert_key(&server.target));
+        }
+        keep
+    });
+
+    for (target, result) in results {
+        let key = target.key();
+        let server = servers.entry(key).or_insert_with(|| ServerHealth {
+            target: target.clone(),
+            state: ServerState::Unknown,
+            latency_ms: None,
+            last_checked: None,
+            last_change: None,
+            last_error: None,
+            consecutive_failures: 0,
+            gateway_errors: 0,
+            history: VecDeque::new(),
+        });
+        server.target = target;
+        server.last_checked = Some(now);
+        server.gateway_errors = per_server_errors.get(&server.target.address).copied().unwrap_or(0);
+
+        let previous = server.state.clone();
+        match result {
+            Ok(latency) => {
+                server.latency_ms = Some(latency);
+                server.last_error = None;
+                server.consecutive_failures = 0;
+                server.state = ServerState::Up;
+            }
+            Err(e) => {
+                server.latency_ms = None;
+                server.last_error = Some(e);
+                server.consecutive_failures += 1;
+                if server.consecutive_failures >= FAILURES_BEFORE_DOWN {
+                    server.state = ServerState::Down;
+                }
+            }
+        }
+        if server.state != previous {
+            server.last_change = Some(now);
+        }
+
+        server.history.push_back(ProbeResult {
+            at: now,
+            up: server.latency_ms.is_some(),
+            latency_ms: server.latency_ms,
+        });
+        while server.history.len() > HISTORY_LENGTH {
+            server.history.pop_front();
+        }
+
+        let key = alert_key(&server.target);
+        if server.state == ServerState::Down && !server.target.marked_down {
+            // A backup going down matters less than a primary
+            let severity = if server.target.backup { AlertSeverity::Warning } else { AlertSeverity::Critical };
+            let message = format!(
+                "Upstream {} is down: {}",
+                server.target.address,
+                server.last_error.as_deref().unwrap_or("probe failed")
+            );
+            health::raise_instance_alert(&instance.id, &key, severity, &message);
+        } else {
+            health::clear_instance_alert(&instance.id, &key);
+        }
+    }
+
+    let mut report_servers: Vec<ServerHealth> = servers.values().cloned().collect();
+    report_servers.sort_by_key(|server| server.target.key());
+
+    UpstreamReport {
+        instance: instance.id.clone(),
+        checked_at: now,
+        probe: settings.clone(),
+        servers: report_servers,
+        gateway_errors: gateway_stats,
+    }
+}
+
+/// Re-reads the instance's targets from its config and probes them every interval
+pub fn start_upstream_monitor(instance: NginxInstance) {
+    thread::spawn(move || {
+        let settings = ProbeSettings::from_env();
+        loop {
+            let targets = nginx_config::find_main_config(&instance)
+                .map(|root| discover_targets(&nginx_config::parse_config(&root)))
+                .unwrap_or_default();
+
+            let report = run_probe_round(&instance, targets, &settings);
+            if let Ok(mut last) = LAST_REPORT.lock() {
+                last.insert(instance.id.clone(), report.clone());
+            }
+            event_bus::publish_for(&instance.id, RustinxEvent::UpstreamHealth(report));
+
+            thread::sleep(Duration::from_secs(settings.interval_secs));
+        }
+    });
+}
+
+/// The instance's latest probe round; empty until its monitor has completed one
+pub fn upstream_report(instance: &NginxInstance) -> UpstreamReport {
+    LAST_REPORT
+        .lock()
+        .ok()
+        .and_then(|reports| reports.get(&instance.id).cloned())
+        .unwrap_or_else(|| UpstreamReport {
+            instance: instance.id.clone(),
+            checked_at: 0,
+            probe: ProbeSettings::from_env(),
+            servers: Vec::new(),
+            gateway_errors: GatewayErrorStats { window_secs: GATEWAY_ERROR_WINDOW_SECS, ..Default::default() },
+        })
+}
+
+#[tauri::command]
+pub(crate) fn get_upstream_health(instance: Option<String>) -> Result<UpstreamReport, String> {
+    Ok(upstream_report(&instances::get_instance(instance.as_deref())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nginx_config::ConfigFile;
+
+    fn parsed(content: &str) -> ParsedConfig {
+        ParsedConfig {
+            root: "nginx.conf".to_string(),
+            files: vec![ConfigFile {
+                path: "nginx.conf".to_string(),
+                directives: nginx_config::parse_config_str(content, "nginx.conf").unwrap(),
+            }],
+            errors: Vec::new(),
+            include_patterns: Vec::new(),
+        }
+    }
+
+    fn tcp(host: &str, port: u16) -> Endpoint {
+        Endpoint::Tcp { host: host.to_string(), port }
+    }
+
+    #[test]
+    fn split_proxy_url_separates_scheme_and_authority() {
+        assert_eq!(
+            split_proxy_url("http://backend:8080/api"),
+            Some(("http".to_string(), "backend:8080".to_string()))
+        );
+        assert_eq!(split_proxy_url("HTTPS://app"), Some(("https".to_string(), "app".to_string())));
+        assert_eq!(
+            split_proxy_url("http://unix:/run/app.sock:/api"),
+            Some(("http".to_string(), "unix:/run/app.sock".to_string()))
+        );
+        assert_eq!(split_proxy_url("backend:8080"), None);
+    }
+
+    #[test]
+    fn parse_endpoint_handles_ports_ipv6_and_sockets() {
+        assert_eq!(parse_endpoint("10.0.0.5:8080", 80), Some(tcp("10.0.0.5", 8080)));
+        assert_eq!(parse_endpoint("backend", 443), Some(tcp("backend", 443)));
+        assert_eq!(parse_endpoint("[::1]:9000", 80), Some(tcp("::1", 9000)));
+        assert_eq!(parse_endpoint("[::1]", 80), Some(tcp("::1", 80)));
+        assert_eq!(parse_endpoint("unix:/run/app.sock", 80), Some(Endpoint::Unix("/run/app.sock".to_string())));
+        assert_eq!(parse_endpoint("$backend", 80), None);
+        assert_eq!(parse_endpoint("backend:http", 80), None);
+    }
+
+    #[test]
+    fn gateway_errors_go_to_the_logged_server_only() {
+        let addresses = vec![
+            ("10.0.0.1".to_string(), tcp("10.0.0.1", 80)),
+            ("10.0.0.12:80".to_string(), tcp("10.0.0.12", 80)),
+            ("10.0.0.5:80".to_string(), tcp("10.0.0.5", 80)),
+            ("10.0.0.5:8080".to_string(), tcp("10.0.0.5", 8080)),
+            ("unix:/run/app.sock".to_string(), Endpoint::Unix("/run/app.sock".to_string())),
+        ];
+        let line = |upstream: &str| {
+            format!(
+                "1.2.3.4 - - [19/Oct/2026:10:00:00 +0000] \"GET / HTTP/1.1\" 502 157 \"-\" \"curl/8.0\" {}",
+                upstream
+            )
+        };
+
+        assert_eq!(servers_in_line(&line("10.0.0.12:80"), &addresses), vec!["10.0.0.12:80"]);
+        assert_eq!(servers_in_line(&line("10.0.0.5:8080"), &addresses), vec!["10.0.0.5:8080"]);
+        assert_eq!(servers_in_line(&line("10.0.0.1:80"), &addresses), vec!["10.0.0.1"]);
+        assert_eq!(
+            servers_in_line(&line("10.0.0.5:80, 10.0.0.5:8080 : unix:/run/app.sock"), &addresses),
+            vec!["10.0.0.5:80", "10.0.0.5:8080", "unix:/run/app.sock"]
+        );
+        assert!(servers_in_line(&line("10.0.0.50:80"), &addresses).is_empty());
+        assert!(servers_in_line(&line("-"), &addresses).is_empty());
+    }
+
+    #[test]
+    fn targets_take_their_scheme_from_the_pass_directive() {
+        let config = parsed(
+            "http {
+                upstream app { server 10.0.0.1:8080; }
+                upstream php { server unix:/run/php-fpm.sock; }
+                upstream rpc { server 10.0.0.2:50051; }
+                server {
+                    location / { proxy_pass https://app; }
+                    location ~ \\.php$ { fastcgi_pass php; }
+                    location /rpc { grpc_pass rpc; }
+                    location /direct { uwsgi_pass 127.0.0.1:3031; }
+                }
+            }
+            stream {
+                upstream app { server 10.0.0.3:5432; }
+                server { listen 5432; proxy_pass app; }
+            }",
+        );
+        let targets = discover_targets(&config);
+        let scheme = |address: &str| {
+            targets
+                .iter()
+                .find(|target| target.address == address)
+                .map(|target| target.scheme.as_str())
+        };
+
+        assert_eq!(scheme("10.0.0.1:8080"), Some("https"));
+        assert_eq!(scheme("unix:/run/php-fpm.sock"), Some("fastcgi"));
+        assert_eq!(scheme("10.0.0.2:50051"), Some("grpc"));
+        assert_eq!(scheme("127.0.0.1:3031"), Some("uwsgi"));
+        assert_eq!(scheme("10.0.0.3:5432"), Some("tcp"));
+        assert_eq!(targets.len(), 5);
+    }
+}
@@ -7,11 +7,7 @@ pub(crate) fn check_sudo_status() -> Result<bool, String> {
     #[cfg(unix)]
     {
         // Check if the current user is root on Unix-like systems
-        if unsafe { geteuid() } == 0 {
-            return Ok(true);
-        } else {
-            return Ok(false);
-        }
+        Ok(unsafe { geteuid() } == 0)
     }
 
     #[cfg(windows)]
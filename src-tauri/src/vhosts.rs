@@ -0,0 +1,643 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commands;
+use crate::instances::{self, NginxInstance};
+use crate::nginx_config::{self, Directive, ParsedConfig};
+use crate::templates::{self, TemplateContext};
+
+const SITES_AVAILABLE: &str = "sites-available";
+const SITES_ENABLED: &str = "sites-enabled";
+
+// First line of every site Rustinx writes; sites without it are only changed when forced
+const MANAGED_HEADER: &str = "# Managed by Rustinx";
+
+lazy_static::lazy_static! {
+    static ref SITE_NAME: Regex = Regex::new(r"^[A-Za-z0-9][A-Za-z0-9._-]*$").unwrap();
+}
+
+#[derive(Debug)]
+pub enum VhostError {
+    /// Bad name, template values or a site that already exists
+    InvalidRequest(String),
+    NotFound(String),
+    /// Writing the files failed, or nginx rejected the result (the change is rolled back)
+    Failed(String),
+}
+
+impl std::fmt::Display for VhostError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VhostError::InvalidRequest(message) | VhostError::NotFound(message) | VhostError::Failed(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+/// One `server` block
+#[derive(Clone, Debug, Serialize)]
+pub struct Vhost {
+    pub server_names: Vec<String>,
+    pub listen: Vec<String>,
+    pub root: Option<String>,
+    /// The first `proxy_pass` in the server, when it proxies
+    pub proxy_pass: Option<String>,
+    /// The target of a server-level `return 301/302`
+    pub redirect: Option<String>,
+    pub tls: bool,
+    pub certificate: Option<String>,
+    pub file: String,
+    pub line: usize,
+    /// Part of the running config
+    pub enabled: bool,
+    /// The `sites-available` entry the block lives in, if it's managed that way
+    pub site: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Site {
+    pub name: String,
+    pub file: String,
+    pub enabled: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TlsFiles {
+    pub certificate: String,
+    pub key: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VhostTemplate {
+    Static { root: String },
+    ReverseProxy { upstream: String },
+    /// Static files with every unknown path falling back to `index.html`
+    Spa { root: String },
+    /// Sends every request to `target` with the original URI appended
+    Redirect {
+        target: String,
+        #[serde(default = "default_permanent")]
+        permanent: bool,
+    },
+}
+
+fn default_permanent() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct VhostRequest {
+    /// File name under `sites-available`
+    pub name: String,
+    pub server_names: Vec<String>,
+    pub port: Option<u16>,
+    pub tls: Option<TlsFiles>,
+    pub template: VhostTemplate,
+    #[serde(default)]
+    pub enable: bool,
+    /// Overwrite the site even if Rustinx didn't write it
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// `?force=true` deletes a site Rustinx didn't write
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct DeleteSiteQuery {
+    #[serde(default)]
+    pub force: bool,
+}
+
+struct SiteDirs {
+    available: PathBuf,
+    enabled: PathBuf,
+}
+
+fn site_dirs(parsed: &ParsedConfig) -> SiteDirs {
+    let conf_dir = Path::new(&parsed.root).parent().unwrap_or(Path::new("/etc/nginx")).to_path_buf();
+    SiteDirs {
+        available: conf_dir.join(SITES_AVAILABLE),
+        enabled: conf_dir.join(SITES_ENABLED),
+    }
+}
+
+fn load_config(instance: &NginxInstance) -> Result<ParsedConfig, VhostError> {
+    let root = nginx_config::find_main_config(instance).ok_or_else(|| VhostError::Failed("Could not find nginx.conf".to_string()))?;
+    Ok(nginx_config::parse_config(&root))
+}
+
+fn is_enabled(dirs: &SiteDirs, name: &str) -> bool {
+    fs::symlink_metadata(dirs.enabled.join(name)).is_ok()
+}
+
+/// The `sites-available` entry `file` belongs to, following the `sites-enabled` symlink if needed
+fn site_for_file(dirs: &SiteDirs, file: &str) -> Option<String> {
+    let path = Path::new(file);
+    let in_dir = |dir: &Path| path.parent() == Some(dir);
+    if in_dir(&dirs.available) || in_dir(&dirs.enabled) {
+        let name = path.file_name()?.to_string_lossy().to_string();
+        if dirs.available.join(&name).exists() {
+            return Some(name);
+        }
+    }
+    None
+}
+
+fn describe(server: &Directive, enabled: bool, dirs: &SiteDirs) -> Vhost {
+    let children = server.children();
+    let values = |name: &str| -> Vec<String> {
+        children
+            .iter()
+            .filter(|d| d.name == name)
+            .flat_map(|d| d.args.iter().cloned())
+            .collect()
+    };
+    let first = |name: &str| children.iter().find(|d| d.name == name).and_then(|d| d.arg(0)).map(|a| a.to_string());
+
+    let mut proxy_pass = None;
+    nginx_config::walk(children, &mut |directive, _| {
+        if proxy_pass.is_none() && directive.name == "proxy_pass" {
+            proxy_pass = directive.arg(0).map(|target| target.to_string());
+        }
+    });
+
+    let listen: Vec<String> = children
+        .iter()
+        .filter(|d| d.name == "listen")
+        .map(|d| d.args.join(" "))
+        .collect();
+    let certificate = first("ssl_certificate");
+    let redirect = children
+        .iter()
+        .find(|d| d.name == "return" && matches!(d.arg(0), Some("301" | "302" | "307" | "308")))
+        .and_then(|d| d.arg(1))
+        .map(|target| target.to_string());
+
+    Vhost {
+        server_names: values("server_name"),
+        tls: certificate.is_some() || listen.iter().any(|l| l.split_whitespace().any(|arg| arg == "ssl" || arg == "quic")),
+        listen,
+        root: first("root"),
+        proxy_pass,
+        redirect,
+        certificate,
+        file: server.file.clone(),
+        line: server.line,
+        enabled,
+        site: site_for_file(dirs, &server.file),
+    }
+}
+
+/// Every server block in the running config, plus those in disabled `sites-available` entries
+pub fn list_vhosts(instance: &NginxInstance) -> Result<Vec<Vhost>, VhostError> {
+    let parsed = load_config(instance)?;
+    let dirs = site_dirs(&parsed);
+    let tree = parsed.expanded();
+
+    let mut vhosts = Vec::new();
+    nginx_config::walk(&tree, &mut |directive, parents| {
+        if directive.name == "server" && parents.last().is_some_and(|parent| parent.name == "http") {
+            vhosts.push(describe(directive, true, &dirs));
+        }
+    });
+
+    for site in list_sites_in(&dirs) {
+        if site.enabled {
+            continue;
+        }
+        let parsed = nginx_config::parse_config(&site.file);
+        let tree = parsed.expanded();
+        nginx_config::walk(&tree, &mut |directive, parents| {
+            if directive.name == "server" && parents.is_empty() {
+                vhosts.push(describe(directive, false, &dirs));
+            }
+        });
+    }
+
+    Ok(vhosts)
+}
+
+fn list_sites_in(dirs: &SiteDirs) -> Vec<Site> {
+    let entries = match fs::read_dir(&dirs.available) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut sites: Vec<Site> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            Site {
+                enabled: is_enabled(dirs, &name),
+                file: entry.path().to_string_lossy().to_string(),
+                name,
+            }
+        })
+        .collect();
+    sites.sort_by(|a, b| a.name.cmp(&b.name));
+    sites
+}
+
+fn validate_site_name(name: &str) -> Result<(), VhostError> {
+    if SITE_NAME.is_match(name) {
+        Ok(())
+    } else {
+        Err(VhostError::InvalidRequest(format!("{:?} is not a valid site name", name)))
+    }
+}
+
+/// Refuses to replace or delete a hand-written site unless `force` is set
+fn require_managed(name: &str, content: &str, force: bool) -> Result<(), VhostError> {
+    if force || content.starts_with(MANAGED_HEADER) {
+        Ok(())
+    } else {
+        Err(invalid(format!(
+            "Site {} wasn't written by Rustinx (no \"{}\" header); force the change to overwrite it",
+            name, MANAGED_HEADER
+        )))
+    }
+}
+
+fn invalid(message: String) -> VhostError {
+    VhostError::InvalidRequest(message)
+}
+
+/// Renders the server block for a request, rejecting values that could break out of a directive
+pub fn render_vhost(request: &VhostRequest) -> Result<String, VhostError> {
+    if request.server_names.is_empty() {
+        return Err(invalid("At least one server_name is required".to_string()));
+    }
+    if let Some(name) = request.server_names.iter().find(|name| !nginx_config::is_server_name(name)) {
+        return Err(invalid(format!("{:?} is not a valid server_name", name)));
+    }
+    let check_path = |path: &str| {
+        if nginx_config::is_safe_path(path) {
+            Ok(())
+        } else {
+            Err(invalid(format!("{:?} must be an absolute path without spaces, quotes or #", path)))
+        }
+    };
+
+    let mut lines: Vec<String> = Vec::new();
+    let port = request.port.unwrap_or(if request.tls.is_some() { 443 } else { 80 });
+    match &request.tls {
+        Some(tls) => {
+            check_path(&tls.certificate)?;
+            check_path(&tls.key)?;
+            lines.push(format!("listen {} ssl;", port));
+            lines.push(format!("listen [::]:{} ssl;", port));
+        }
+        None => {
+            lines.push(format!("listen {};", port));
+            lines.push(format!("listen [::]:{};", port));
+        }
+    }
+    lines.push(format!("server_name {};", request.server_names.join(" ")));
+    if let Some(tls) = &request.tls {
+        lines.push(String::new());
+        lines.push(format!("ssl_certificate {};", tls.certificate));
+        lines.push(format!("ssl_certificate_key {};", tls.key));
+    }
+    lines.push(String::new());
+
+    match &request.template {
+        VhostTemplate::Static { root } => {
+            check_path(root)?;
+            lines.push(format!("root {};", root));
+            lines.push("index index.html index.htm;".to_string());
+            lines.push(String::new());
+            lines.push("location / {".to_string());
+            lines.push("    try_files $uri $uri/ =404;".to_string());
+            lines.push("}".to_string());
+        }
+        VhostTemplate::Spa { root } => {
+            check_path(root)?;
+            lines.push(format!("root {};", root));
+            lines.push("index index.html;".to_string());
+            lines.push(String::new());
+            lines.push("location / {".to_string());
+            lines.push("    try_files $uri $uri/ /index.html;".to_string());
+            lines.push("}".to_string());
+        }
+        VhostTemplate::ReverseProxy { upstream } => {
+            if !nginx_config::is_safe_url(upstream) {
+                return Err(invalid(format!("{:?} must be an http:// or https:// URL", upstream)));
+            }
+            lines.push("location / {".to_string());
+            lines.push(format!("    proxy_pass {};", upstream));
+            lines.push("    proxy_set_header Host $host;".to_string());
+            lines.push("    proxy_set_header X-Real-IP $remote_addr;".to_string());
+            lines.push("    proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;".to_string());
+            lines.push("    proxy_set_header X-Forwarded-Proto $scheme;".to_string());
+            lines.push("}".to_string());
+        }
+        VhostTemplate::Redirect { target, permanent } => {
+            if !nginx_config::is_safe_url(target) {
+                return Err(invalid(format!("{:?} must be an http:// or https:// URL", target)));
+            }
+            let code = if *permanent { 301 } else { 302 };
+            lines.push(format!("return {} {}$request_uri;", code, target.trim_end_matches('/')));
+        }
+    }
+
+    let body: Vec<String> = lines
+        .into_iter()
+        .map(|line| if line.is_empty() { line } else { format!("    {}", line) })
+        .collect();
+    Ok(format!("{}\nserver {{\n{}\n}}\n", MANAGED_HEADER, body.join("\n")))
+}
+
+/// Fails unless nginx.conf pulls in `sites-enabled`, since otherwise enabling a site does nothing
+fn require_sites_enabled_include(parsed: &ParsedConfig, dirs: &SiteDirs) -> Result<(), VhostError> {
+    let enabled = dirs.enabled.to_string_lossy().to_string();
+    if parsed.include_patterns.iter().any(|pattern| pattern.starts_with(&enabled)) {
+        Ok(())
+    } else {
+        Err(VhostError::Failed(format!("{} doesn't include {}/*", parsed.root, enabled)))
+    }
+}
+
+/// Syntax-checks a site that won't be reloaded, so a disabled site can't be saved broken either
+fn check_site(instance: &NginxInstance, content: &str) -> Result<(), VhostError> {
+    let check = templates::check_snippet(instance, TemplateContext::Http, content)
+        .map_err(|e| VhostError::Failed(e.to_string()))?;
+    if !check.valid {
+        return Err(VhostError::Failed(format!("nginx rejected the site: {}", check.message)));
+    }
+    Ok(())
+}
+
+/// Validates and reloads, running `undo` when nginx rejects the change
+fn apply(instance: &NginxInstance, undo: impl FnOnce()) -> Result<(), VhostError> {
+    commands::reload_nginx(instance).map_err(|e| {
+        undo();
+        VhostError::Failed(format!("Change rolled back: {}", e))
+    })?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link_site(dirs: &SiteDirs, name: &str) -> Result<(), VhostError> {
+    fs::create_dir_all(&dirs.enabled).map_err(|e| VhostError::Failed(format!("Failed to create {}: {}", dirs.enabled.display(), e)))?;
+    std::os::unix::fs::symlink(dirs.available.join(name), dirs.enabled.join(name))
+        .map_err(|e| VhostError::Failed(format!("Failed to enable {}: {}", name, e)))
+}
+
+#[cfg(not(unix))]
+fn link_site(_dirs: &SiteDirs, _name: &str) -> Result<(), VhostError> {
+    Err(VhostError::Failed("sites-enabled symlinks are only supported on Unix".to_string()))
+}
+
+fn unlink_site(dirs: &SiteDirs, name: &str) -> Result<(), VhostError> {
+    let link = dirs.enabled.join(name);
+    let metadata = fs::symlink_metadata(&link).map_err(|_| VhostError::NotFound(format!("{} is not enabled", name)))?;
+    if !metadata.file_type().is_symlink() {
+        // A plain file here wasn't put there by us; don't delete someone's config
+        return Err(VhostError::Failed(format!("{} is a regular file, not a link to {}", link.display(), SITES_AVAILABLE)));
+    }
+    fs::remove_file(&link).map_err(|e| VhostError::Failed(format!("Failed to disable {}: {}", name, e)))
+}
+
+/// Writes a new site from a template, optionally enabling it
+pub fn create_vhost(instance: &NginxInstance, request: &VhostRequest) -> Result<Site, VhostError> {
+    validate_site_name(&request.name)?;
+    let content = render_vhost(request)?;
+    let _guard = commands::lock_config_changes();
+
+    let parsed = load_config(instance)?;
+    let dirs = site_dirs(&parsed);
+    let file = dirs.available.join(&request.name);
+    if file.exists() {
+        return Err(invalid(format!("Site {} already exists", request.name)));
+    }
+    if request.enable {
+        require_sites_enabled_include(&parsed, &dirs)?;
+    } else {
+        check_site(instance, &content)?;
+    }
+
+    fs::create_dir_all(&dirs.available)
+        .map_err(|e| VhostError::Failed(format!("Failed to create {}: {}", dirs.available.display(), e)))?;
+    fs::write(&file, content).map_err(|e| VhostError::Failed(format!("Failed to write {}: {}", file.display(), e)))?;
+
+    if request.enable {
+        if let Err(e) = link_site(&dirs, &request.name) {
+            let _ = fs::remove_file(&file);
+            return Err(e);
+        }
+        apply(instance, || {
+            let _ = fs::remove_file(dirs.enabled.join(&request.name));
+            let _ = fs::remove_file(&file);
+        })?;
+    }
+
+    Ok(Site {
+        name: request.name.clone(),
+        file: file.to_string_lossy().to_string(),
+        enabled: request.enable,
+    })
+}
+
+/// Replaces a site's server block with a freshly rendered one
+pub fn update_vhost(instance: &NginxInstance, request: &VhostRequest) -> Result<Site, VhostError> {
+    validate_site_name(&request.name)?;
+    let content = render_vhost(request)?;
+    let _guard = commands::lock_config_changes();
+
+    let dirs = site_dirs(&load_config(instance)?);
+    let file = dirs.available.join(&request.name);
+    let previous = fs::read_to_string(&file).map_err(|_| VhostError::NotFound(format!("Site {} does not exist", request.name)))?;
+    require_managed(&request.name, &previous, request.force)?;
+    let enabled = is_enabled(&dirs, &request.name);
+    if !enabled {
+        check_site(instance, &content)?;
+    }
+    fs::write(&file, content).map_err(|e| VhostError::Failed(format!("Failed to write {}: {}", file.display(), e)))?;
+
+    if enabled {
+        apply(instance, || {
+            let _ = fs::write(&file, &previous);
+        })?;
+    }
+
+    Ok(Site { name: request.name.clone(), file: file.to_string_lossy().to_string(), enabled })
+}
+
+pub fn enable_site(instance: &NginxInstance, name: &str) -> Result<Site, VhostError> {
+    validate_site_name(name)?;
+    let _guard = commands::lock_config_changes();
+
+    let parsed = load_config(instance)?;
+    let dirs = site_dirs(&parsed);
+    let file = dirs.available.join(name);
+    if !file.is_file() {
+        return Err(VhostError::NotFound(format!("Site {} does not exist", name)));
+    }
+    if !is_enabled(&dirs, name) {
+        require_sites_enabled_include(&parsed, &dirs)?;
+        link_site(&dirs, name)?;
+        apply(instance, || {
+            let _ = fs::remove_file(dirs.enabled.join(name));
+        })?;
+    }
+
+    Ok(Site { name: name.to_string(), file: file.to_string_lossy().to_string(), enabled: true })
+}
+
+pub fn disable_site(instance: &NginxInstance, name: &str) -> Result<Site, VhostError> {
+    validate_site_name(name)?;
+    let _guard = commands::lock_config_changes();
+
+    let dirs = site_dirs(&load_config(instance)?);
+    let file = dirs.available.join(name);
+    unlink_site(&dirs, name)?;
+    apply(instance, || {
+        let _ = link_site(&dirs, name);
+    })?;
+
+    Ok(Site { name: name.to_string(), file: file.to_string_lossy().to_string(), enabled: false })
+}
+
+/// Disables the site if needed, then removes it from `sites-available`
+pub fn delete_site(instance: &NginxInstance, name: &str, force: bool) -> Result<(), VhostError> {
+    validate_site_name(name)?;
+    let dirs = site_dirs(&load_config(instance)?);
+    let content = fs::read_to_string(dirs.available.join(name))
+        .map_err(|_| VhostError::NotFound(format!("Site {} does not exist", name)))?;
+    require_managed(name, &content, force)?;
+    if is_enabled(&dirs, name) {
+        disable_site(instance, name)?;
+    }
+    let _guard = commands::lock_config_changes();
+
+    let file = site_dirs(&load_config(instance)?).available.join(name);
+    if !file.is_file() {
+        return Err(VhostError::NotFound(format!("Site {} does not exist", name)));
+    }
+    fs::remove_file(&file).map_err(|e| VhostError::Failed(format!("Failed to delete {}: {}", file.display(), e)))
+}
+
+#[tauri::command]
+pub(crate) fn get_vhosts(instance: Option<String>) -> Result<Vec<Vhost>, String> {
+    list_vhosts(&instances::get_instance(instance.as_deref())?).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn create_site(instance: Option<String>, request: VhostRequest) -> Result<Site, String> {
+    create_vhost(&instances::get_instance(instance.as_deref())?, &request).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn update_site(instance: Option<String>, request: VhostRequest) -> Result<Site, String> {
+    update_vhost(&instances::get_instance(instance.as_deref())?, &request).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn set_site_enabled(instance: Option<String>, name: String, enabled: bool) -> Result<Site, String> {
+    let instance = instances::get_instance(instance.as_deref())?;
+    if enabled {
+        enable_site(&instance, &name)
+    } else {
+        disable_site(&instance, &name)
+    }
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub(crate) fn remove_site(instance: Option<String>, name: String, force: Option<bool>) -> Result<(), String> {
+    delete_site(&instances::get_instance(instance.as_deref())?, &name, force.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(template: VhostTemplate) -> VhostRequest {
+        VhostRequest {
+            name: "example.com".to_string(),
+            server_names: vec!["example.com".to_string(), "www.example.com".to_string()],
+            port: None,
+            tls: None,
+            template,
+            enable: false,
+            force: false,
+        }
+    }
+
+    fn rejected(request: &VhostRequest) -> bool {
+        matches!(render_vhost(request), Err(VhostError::InvalidRequest(_)))
+    }
+
+    #[test]
+    fn renders_each_template() {
+        let site = render_vhost(&request(VhostTemplate::Static { root: "/var/www/example".to_string() })).unwrap();
+        assert!(site.starts_with(MANAGED_HEADER));
+        assert!(site.contains("    listen 80;\n    listen [::]:80;\n    server_name example.com www.example.com;\n"));
+        assert!(site.contains("    root /var/www/example;\n"));
+        assert!(site.contains("        try_files $uri $uri/ =404;\n"));
+
+        let site = render_vhost(&request(VhostTemplate::Spa { root: "/srv/app/dist".to_string() })).unwrap();
+        assert!(site.contains("        try_files $uri $uri/ /index.html;\n"));
+
+        let mut proxy = request(VhostTemplate::ReverseProxy { upstream: "http://127.0.0.1:3000".to_string() });
+        proxy.tls = Some(TlsFiles {
+            certificate: "/etc/ssl/example.pem".to_string(),
+            key: "/etc/ssl/example.key".to_string(),
+        });
+        let site = render_vhost(&proxy).unwrap();
+        assert!(site.contains("    listen 443 ssl;\n"));
+        assert!(site.contains("    ssl_certificate /etc/ssl/example.pem;\n    ssl_certificate_key /etc/ssl/example.key;\n"));
+        assert!(site.contains("        proxy_pass http://127.0.0.1:3000;\n"));
+
+        let mut redirect = request(VhostTemplate::Redirect { target: "https://example.org/".to_string(), permanent: false });
+        redirect.port = Some(8080);
+        let site = render_vhost(&redirect).unwrap();
+        assert!(site.contains("    listen 8080;\n"));
+        assert!(site.contains("    return 302 https://example.org$request_uri;\n"));
+    }
+
+    #[test]
+    fn rejects_values_that_could_break_out_of_a_directive() {
+        for root in ["relative/path", "/var/www; include /etc/shadow", "/var/www/{x}", "/var/www/a b", "/var/www/'x'", "/var/www/#x"] {
+            assert!(rejected(&request(VhostTemplate::Static { root: root.to_string() })), "{:?} should be rejected", root);
+        }
+        for upstream in ["ftp://backend", "http://backend;", "http://backend # x", "http://backend#x", "http://"] {
+            assert!(rejected(&request(VhostTemplate::ReverseProxy { upstream: upstream.to_string() })), "{:?} should be rejected", upstream);
+        }
+        assert!(rejected(&request(VhostTemplate::Redirect { target: "https://x.org/#frag".to_string(), permanent: true })));
+
+        let mut names = request(VhostTemplate::Static { root: "/var/www".to_string() });
+        names.server_names = vec!["example.com;".to_string()];
+        assert!(rejected(&names));
+        names.server_names = Vec::new();
+        assert!(rejected(&names));
+
+        let mut tls = request(VhostTemplate::Static { root: "/var/www".to_string() });
+        tls.tls = Some(TlsFiles { certificate: "/etc/ssl/a.pem#".to_string(), key: "/etc/ssl/a.key".to_string() });
+        assert!(rejected(&tls));
+    }
+
+    #[test]
+    fn only_managed_sites_are_changed_without_force() {
+        let managed = render_vhost(&request(VhostTemplate::Static { root: "/var/www".to_string() })).unwrap();
+        assert!(require_managed("example.com", &managed, false).is_ok());
+
+        let handwritten = "server {\n    listen 80;\n}\n";
+        assert!(matches!(require_managed("default", handwritten, false), Err(VhostError::InvalidRequest(_))));
+        assert!(require_managed("default", handwritten, true).is_ok());
+        // The header only counts at the top of the file
+        assert!(require_managed("default", &format!("# Notes\n{}", managed), false).is_err());
+    }
+
+    #[test]
+    fn site_names_are_plain_file_names() {
+        assert!(validate_site_name("example.com").is_ok());
+        assert!(validate_site_name("my_site-2").is_ok());
+        for name in ["", ".hidden", "../nginx.conf", "a/b", "-rf", "a b"] {
+            assert!(validate_site_name(name).is_err(), "{:?} should be rejected", name);
+        }
+    }
+}
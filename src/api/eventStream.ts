@@ -0,0 +1,46 @@
+// Shared Server-Sent Events connection to /api/events for browser mode.
+// Carries the same events the Tauri build emits (access_event, error_event,
+// nginx_config_check, nginx_status_check).
+
+type EventHandler = (payload: any) => void;
+
+const isDevelopment = window.location.port === "1234";
+const eventsURL = isDevelopment
+  ? "http://localhost:8081/api/events"
+  : "/api/events";
+
+let source: EventSource | null = null;
+const handlers = new Map<string, Set<EventHandler>>();
+
+function ensureSource(): EventSource {
+  if (!source) {
+    source = new EventSource(eventsURL, { withCredentials: true });
+    source.onerror = (error) => {
+      console.error("❌ Event stream error:", error);
+    };
+  }
+  return source;
+}
+
+export function subscribe(eventName: string, handler: EventHandler): () => void {
+  const eventSource = ensureSource();
+
+  if (!handlers.has(eventName)) {
+    handlers.set(eventName, new Set());
+    eventSource.addEventListener(eventName, (event) => {
+      const payload = JSON.parse((event as MessageEvent).data);
+      handlers.get(eventName)?.forEach((fn) => fn(payload));
+    });
+  }
+  handlers.get(eventName)!.add(handler);
+
+  return () => {
+    handlers.get(eventName)?.delete(handler);
+    const remaining = Array.from(handlers.values()).some((set) => set.size > 0);
+    if (!remaining && source) {
+      source.close();
+      source = null;
+      handlers.clear();
+    }
+  };
+}
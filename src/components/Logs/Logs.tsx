@@ -1,5 +1,6 @@
 import { useState, useEffect, useMemo, memo, useCallback } from "react";
 import apiClient from "../../api/axiosInstance";
+import { subscribe } from "../../api/eventStream";
 
 // Check if we're running in Tauri environment
 const isTauri = typeof window !== "undefined" && (window as any).__TAURI__;
@@ -138,12 +139,20 @@ const Logs = memo(() => {
         unlistenError.then((unlistenFn: any) => unlistenFn());
       };
     } else {
-      // Use HTTP API in browser mode
+      // Use HTTP API for history and the event stream for live lines in browser mode
       fetchLogs();
 
-      // Poll for new logs every 10 seconds
-      const interval = setInterval(fetchLogs, 10000);
-      return () => clearInterval(interval);
+      const unsubscribeAccess = subscribe("access_event", (line: string) => {
+        setAccessLogs((prevLogs) => [...prevLogs, line].slice(-MAX_LOG_LINES));
+      });
+      const unsubscribeError = subscribe("error_event", (line: string) => {
+        setErrorLogs((prevLogs) => [...prevLogs, line].slice(-MAX_LOG_LINES));
+      });
+
+      return () => {
+        unsubscribeAccess();
+        unsubscribeError();
+      };
     }
   }, [fetchLogs]);
 
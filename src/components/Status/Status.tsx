@@ -1,5 +1,6 @@
 import { useState, useEffect } from "react";
 import apiClient from "../../api/axiosInstance";
+import { subscribe } from "../../api/eventStream";
 
 // Check if we're running in Tauri environment
 const isTauri = typeof window !== 'undefined' && (window as any).__TAURI__;
@@ -21,12 +22,43 @@ import {
 } from "lucide-react";
 import { cn } from "../../lib/utils";
 
+// Payload of `nginx_config_check`, emitted whenever a file in the config include tree changes
+interface ConfigCheckResult {
+  valid: boolean;
+  message: string;
+  changed_file: string | null;
+  error_file: string | null;
+  error_line: number | null;
+  checked_at: number;
+}
+
+// Payload of the `nginx_health` heartbeat emitted by the backend every 5 seconds
+interface HealthSnapshot {
+  timestamp: number;
+  status: string;
+  config_valid: boolean | null;
+  config_message: string | null;
+  master_pid: number | null;
+  uptime_secs: number | null;
+  worker_count: number;
+  last_reload: number | null;
+  alerts: { key: string; severity: string; message: string; since: number }[];
+}
+
 export default function NginxStatus() {
   const [nginxStatus, setNginxStatus] = useState("Checking...");
   const [configEvent, setConfigEvent] = useState("");
   const [nginxConfigPath, setNginxConfigPath] = useState("");
   const [isLoading, setIsLoading] = useState(true);
 
+  const applyHealth = (health: HealthSnapshot) => {
+    setNginxStatus(health.status);
+    if (health.config_message) {
+      setConfigEvent(health.config_message);
+    }
+    setIsLoading(false);
+  };
+
   useEffect(() => {
     const fetchNginxStatus = async () => {
       if (isTauri && listen) {
@@ -39,12 +71,17 @@ export default function NginxStatus() {
 
         const unlistenConfigCheck = listen("nginx_config_check", (event) => {
           console.log("Nginx Config Check:", event.payload);
-          setConfigEvent(event.payload as string);
+          setConfigEvent((event.payload as ConfigCheckResult).message);
+        });
+
+        const unlistenHealth = listen("nginx_health", (event) => {
+          applyHealth(event.payload as HealthSnapshot);
         });
 
         return () => {
           unlistenNginxStatus.then((unlistenFn) => unlistenFn());
           unlistenConfigCheck.then((unlistenFn) => unlistenFn());
+          unlistenHealth.then((unlistenFn) => unlistenFn());
         };
       } else {
         // Use HTTP API in browser mode
@@ -63,10 +100,21 @@ export default function NginxStatus() {
 
     fetchNginxStatus();
     
-    // Poll status every 30 seconds in browser mode
+    // Follow live status and config checks over the event stream in browser mode
     if (!isTauri) {
-      const interval = setInterval(fetchNginxStatus, 30000);
-      return () => clearInterval(interval);
+      const unsubscribeStatus = subscribe("nginx_status_check", (status: string) => {
+        setNginxStatus(status);
+        setIsLoading(false);
+      });
+      const unsubscribeConfig = subscribe("nginx_config_check", (result: ConfigCheckResult) => {
+        setConfigEvent(result.message);
+      });
+      const unsubscribeHealth = subscribe("nginx_health", applyHealth);
+      return () => {
+        unsubscribeStatus();
+        unsubscribeConfig();
+        unsubscribeHealth();
+      };
     }
   }, []);
 
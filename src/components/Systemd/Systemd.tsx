@@ -58,6 +58,21 @@ interface SystemdOptions {
   reverse: boolean;
 }
 
+interface JournalEntry {
+  cursor: string | null;
+  timestamp_us: number | null;
+  priority: number | null;
+  pid: number | null;
+  unit: string | null;
+  identifier: string | null;
+  message: string;
+}
+
+interface JournalPage {
+  entries: JournalEntry[];
+  cursor: string | null;
+}
+
 interface LogEntry {
   timestamp: string;
   level: string;
@@ -112,7 +127,7 @@ const LINE_OPTIONS = [
 ];
 
 const Systemd: React.FC = memo(() => {
-  const [logs, setLogs] = useState<string[]>([]);
+  const [logs, setLogs] = useState<JournalEntry[]>([]);
   const [serviceName, setServiceName] = useState("nginx.service");
   const [numLines, setNumLines] = useState(50); // Reduced from 100 to 50 for better performance
   const [noPager, setNoPager] = useState(true);
@@ -124,28 +139,69 @@ const Systemd: React.FC = memo(() => {
   const [searchTerm, setSearchTerm] = useState("");
   const [autoRefresh, setAutoRefresh] = useState(false);
   const [refreshInterval, setRefreshInterval] = useState(15000); // Increased from 5000 to 15000 for better performance
+  const [allowedUnits, setAllowedUnits] = useState<string[]>(["nginx.service"]);
   const { toast } = useToast();
 
+  // The backend only serves units on its allowlist, so only offer those
+  useEffect(() => {
+    const loadUnits = async () => {
+      try {
+        const units: string[] =
+          isTauri && invoke
+            ? await invoke<string[]>("get_allowed_units")
+            : (await apiClient.get("/systemd/units")).data.units;
+        if (units.length > 0) setAllowedUnits(units);
+      } catch (err) {
+        console.error("Failed to load allowed units:", err);
+      }
+    };
+    loadUnits();
+  }, []);
+
+  const services = useMemo(
+    () =>
+      allowedUnits.map(
+        (unit) =>
+          SERVICES.find((service) => service.value === unit) ?? {
+            value: unit,
+            label: unit,
+            icon: "⚙️",
+          }
+      ),
+    [allowedUnits]
+  );
+
   const parseLogEntry = useCallback(
-    (logLine: string): LogEntry => {
-      // Simple log parsing - you can enhance this based on your log format
-      const timestampMatch = logLine.match(
-        /^(\w{3}\s+\d{1,2}\s+\d{2}:\d{2}:\d{2})/
-      );
-      const timestamp = timestampMatch ? timestampMatch[1] : "";
+    (entry: JournalEntry): LogEntry => {
+      const timestamp =
+        entry.timestamp_us !== null
+          ? new Date(entry.timestamp_us / 1000).toLocaleString()
+          : "";
 
+      // Syslog priorities: 0-3 are errors, 4 warnings, 5-6 informational, 7 debug
       let level = "DEFAULT";
-      if (logLine.toLowerCase().includes("error")) level = "ERROR";
-      else if (logLine.toLowerCase().includes("warn")) level = "WARN";
-      else if (logLine.toLowerCase().includes("info")) level = "INFO";
-      else if (logLine.toLowerCase().includes("debug")) level = "DEBUG";
+      if (entry.priority !== null) {
+        if (entry.priority <= 3) level = "ERROR";
+        else if (entry.priority === 4) level = "WARN";
+        else if (entry.priority <= 6) level = "INFO";
+        else level = "DEBUG";
+      } else {
+        const message = entry.message.toLowerCase();
+        if (message.includes("error")) level = "ERROR";
+        else if (message.includes("warn")) level = "WARN";
+        else if (message.includes("info")) level = "INFO";
+        else if (message.includes("debug")) level = "DEBUG";
+      }
+
+      const source = entry.identifier || entry.unit || serviceName;
+      const pid = entry.pid !== null ? `[${entry.pid}]` : "";
 
       return {
         timestamp,
         level,
         service: serviceName,
-        message: logLine.replace(timestampMatch?.[0] || "", "").trim(),
-        raw: logLine,
+        message: entry.message,
+        raw: `${timestamp} ${source}${pid}: ${entry.message}`.trim(),
       };
     },
     [serviceName]
@@ -156,11 +212,11 @@ const Systemd: React.FC = memo(() => {
     setError("");
 
     try {
-      let output: string;
+      let page: JournalPage;
 
       if (isTauri && invoke) {
         // Use Tauri in desktop mode
-        output = await invoke<string>("get_systemd_logs", {
+        page = await invoke<JournalPage>("get_systemd_logs", {
           options: {
             service_name: serviceName,
             no_pager: noPager,
@@ -185,16 +241,12 @@ const Systemd: React.FC = memo(() => {
           throw new Error(response.data?.error || 'Failed to fetch systemd logs');
         }
 
-        const data = response.data;
-        output = data.logs;
+        page = response.data;
       }
 
-      const parsedLogs = output
-        .split("\n")
-        .filter((line) => line.trim() !== "");
-      setLogs(parsedLogs);
+      setLogs(page.entries);
 
-      if (parsedLogs.length === 0) {
+      if (page.entries.length === 0) {
         toast({
           title: "No logs found",
           description: "No logs available for the specified criteria",
@@ -225,7 +277,7 @@ const Systemd: React.FC = memo(() => {
   };
 
   const exportLogs = () => {
-    const logContent = logs.join("\n");
+    const logContent = logs.map((log) => parseLogEntry(log).raw).join("\n");
     const blob = new Blob([logContent], { type: "text/plain" });
     const url = URL.createObjectURL(blob);
     const a = document.createElement("a");
@@ -266,7 +318,7 @@ const Systemd: React.FC = memo(() => {
   const filteredLogs = useMemo(() => {
     if (!debouncedSearchTerm) return logs;
     return logs.filter((log) =>
-      log.toLowerCase().includes(debouncedSearchTerm.toLowerCase())
+      log.message.toLowerCase().includes(debouncedSearchTerm.toLowerCase())
     );
   }, [logs, debouncedSearchTerm]);
 
@@ -343,7 +395,7 @@ const Systemd: React.FC = memo(() => {
                     <SelectValue />
                   </SelectTrigger>
                   <SelectContent>
-                    {SERVICES.map((service) => (
+                    {services.map((service) => (
                       <SelectItem key={service.value} value={service.value}>
                         <span className="flex items-center gap-2">
                           <span>{service.icon}</span>
@@ -569,7 +621,9 @@ const Systemd: React.FC = memo(() => {
                 <Button
                   variant="outline"
                   size="sm"
-                  onClick={() => copyToClipboard(filteredLogs.join("\n"))}
+                  onClick={() =>
+                    copyToClipboard(parsedLogs.map((log) => log.raw).join("\n"))
+                  }
                   className="gap-2 bg-transparent"
                 >
                   <Copy className="h-4 w-4" />